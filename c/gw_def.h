@@ -34,6 +34,9 @@ typedef unsigned __int128 uint128_t;
 /* MAX size of rollup config */
 #define GW_MAX_ROLLUP_CONFIG_SIZE (4 * 1024)
 #define GW_MAX_WITNESS_SIZE (300 * 1024)
+/* Size of the optional paused_at after the global state in the rollup cell
+ * data, see `gw_utils::pause` */
+#define GW_PAUSED_AT_SIZE 8
 
 #define GW_LOG_SUDT_TRANSFER 0x0
 #define GW_LOG_SUDT_PAY_FEE 0x1
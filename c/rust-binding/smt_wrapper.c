@@ -1 +1,154 @@
 #include "smt_wrapper.h"
+
+/*
+ * Single key update verification
+ *
+ * `smt_verify` walks the compiled proof once per root, verifying a key is
+ * updated from `old_value` to `new_value` takes two walks and two leaf
+ * states. The walk below follows the same program as `smt_calculate_root`
+ * but carries both the old and the new node, so the proof is parsed once.
+ *
+ * Only a proof of a single leaf is accepted, the 0x48 operation merges two
+ * leaves and is rejected.
+ */
+
+#define _SMT_UPDATE_PROOF_LEAF 0x4C
+#define _SMT_UPDATE_PROOF_SIBLING 0x50
+#define _SMT_UPDATE_PROOF_ZEROS 0x4F
+
+static int _smt_update_is_zero(const uint8_t *value) {
+  for (int i = 0; i < 32; i++) {
+    if (value[i] != 0) {
+      return 0;
+    }
+  }
+  return 1;
+}
+
+static int _smt_update_get_bit(const uint8_t *key, uint8_t height) {
+  return (key[height / 8] >> (height % 8)) & 1;
+}
+
+/* clear bits of `key` up to `height`, the path of the parent node */
+static void _smt_update_parent_path(const uint8_t *key, uint8_t height,
+                                    uint8_t *parent_key) {
+  memcpy(parent_key, key, 32);
+  for (int i = 0; i <= height; i++) {
+    parent_key[i / 8] &= ~(1 << (i % 8));
+  }
+}
+
+static void _smt_update_hash_leaf(const uint8_t *key, const uint8_t *value,
+                                  uint8_t *out) {
+  if (_smt_update_is_zero(value)) {
+    memset(out, 0, 32);
+    return;
+  }
+  blake2b_state blake2b_ctx;
+  blake2b_init(&blake2b_ctx, 32);
+  blake2b_update(&blake2b_ctx, key, 32);
+  blake2b_update(&blake2b_ctx, value, 32);
+  blake2b_final(&blake2b_ctx, out, 32);
+}
+
+/* merge `node` with `sibling` at `height`, `out` may alias `node` */
+static void _smt_update_merge(uint8_t height, const uint8_t *key,
+                              const uint8_t *node, const uint8_t *sibling,
+                              uint8_t *out) {
+  const uint8_t *lhs = node;
+  const uint8_t *rhs = sibling;
+  if (_smt_update_get_bit(key, height)) {
+    lhs = sibling;
+    rhs = node;
+  }
+  if (_smt_update_is_zero(lhs)) {
+    memmove(out, rhs, 32);
+    return;
+  }
+  if (_smt_update_is_zero(rhs)) {
+    memmove(out, lhs, 32);
+    return;
+  }
+  uint8_t parent_key[32];
+  _smt_update_parent_path(key, height, parent_key);
+  blake2b_state blake2b_ctx;
+  blake2b_init(&blake2b_ctx, 32);
+  blake2b_update(&blake2b_ctx, &height, 1);
+  blake2b_update(&blake2b_ctx, parent_key, 32);
+  blake2b_update(&blake2b_ctx, lhs, 32);
+  blake2b_update(&blake2b_ctx, rhs, 32);
+  blake2b_final(&blake2b_ctx, out, 32);
+}
+
+int smt_verify_update(const uint8_t *key, const uint8_t *old_value,
+                      const uint8_t *new_value, const uint8_t *old_root,
+                      const uint8_t *new_root, const uint8_t *proof,
+                      uint32_t proof_length) {
+  static const uint8_t zero[32] = {0};
+  uint8_t old_node[32];
+  uint8_t new_node[32];
+  /* height of the node, 256 is the root */
+  uint16_t height = 0;
+  int has_leaf = 0;
+
+  uint32_t proof_index = 0;
+  while (proof_index < proof_length) {
+    switch (proof[proof_index++]) {
+      case _SMT_UPDATE_PROOF_LEAF: {
+        if (has_leaf) {
+          return ERROR_INVALID_STACK;
+        }
+        _smt_update_hash_leaf(key, old_value, old_node);
+        _smt_update_hash_leaf(key, new_value, new_node);
+        has_leaf = 1;
+      } break;
+      case _SMT_UPDATE_PROOF_SIBLING: {
+        if (!has_leaf) {
+          return ERROR_INVALID_STACK;
+        }
+        if (proof_index + 32 > proof_length) {
+          return ERROR_INVALID_PROOF;
+        }
+        if (height > 255) {
+          return ERROR_INVALID_PROOF;
+        }
+        const uint8_t *sibling = &proof[proof_index];
+        proof_index += 32;
+        _smt_update_merge(height, key, old_node, sibling, old_node);
+        _smt_update_merge(height, key, new_node, sibling, new_node);
+        height++;
+      } break;
+      case _SMT_UPDATE_PROOF_ZEROS: {
+        if (!has_leaf) {
+          return ERROR_INVALID_STACK;
+        }
+        if (proof_index >= proof_length) {
+          return ERROR_INVALID_PROOF;
+        }
+        uint16_t zero_count = proof[proof_index++];
+        if (zero_count == 0) {
+          zero_count = 256;
+        }
+        if (height > 256 - zero_count) {
+          return ERROR_INVALID_PROOF;
+        }
+        for (uint16_t i = 0; i < zero_count; i++) {
+          _smt_update_merge(height, key, old_node, zero, old_node);
+          _smt_update_merge(height, key, new_node, zero, new_node);
+          height++;
+        }
+      } break;
+      default:
+        /* includes 0x48, which merges two leaves */
+        return ERROR_INVALID_PROOF;
+    }
+  }
+  if (!has_leaf) {
+    return ERROR_INVALID_STACK;
+  }
+  if (memcmp(old_node, old_root, 32) != 0 ||
+      memcmp(new_node, new_root, 32) != 0) {
+    return ERROR_INVALID_PROOF;
+  }
+  return 0;
+}
@@ -1,3 +1,8 @@
 #include "stddef.h"
 #include "blake2b.h"
 #include "ckb_smt.h"
+
+int smt_verify_update(const uint8_t *key, const uint8_t *old_value,
+                      const uint8_t *new_value, const uint8_t *old_root,
+                      const uint8_t *new_root, const uint8_t *proof,
+                      uint32_t proof_length);
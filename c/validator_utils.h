@@ -791,7 +791,8 @@ int _load_rollup_config(uint8_t config_cell_data_hash[32],
   mol_seg_t config_seg;
   config_seg.ptr = rollup_config_buf;
   config_seg.size = *rollup_config_size;
-  if (MolReader_RollupConfig_verify(&config_seg, false) != MOL_OK) {
+  /* verify as compatible, the config may contain extra fields */
+  if (MolReader_RollupConfig_verify(&config_seg, true) != MOL_OK) {
     printf("rollup config cell data is not RollupConfig format");
     return GW_FATAL_INVALID_DATA;
   }
@@ -860,15 +861,20 @@ int _load_verification_context(
     uint8_t challenged_block_hash[32], uint8_t block_merkle_root[32],
     uint32_t *tx_index, uint8_t rollup_config[GW_MAX_ROLLUP_CONFIG_SIZE],
     uint64_t *rollup_config_size) {
-  /* load global state from rollup cell */
-  uint8_t global_state_buf[sizeof(MolDefault_GlobalState)] = {0};
-  uint64_t buf_len = sizeof(MolDefault_GlobalState);
+  /* load global state from rollup cell,
+   * the data of a paused rollup ends with the paused_at */
+  uint8_t global_state_buf[sizeof(MolDefault_GlobalState) + GW_PAUSED_AT_SIZE] =
+      {0};
+  uint64_t buf_len = sizeof(global_state_buf);
   int ret = ckb_checked_load_cell_data(global_state_buf, &buf_len, 0,
                                        rollup_cell_index, rollup_cell_source);
   if (ret != 0) {
     printf("_load_verification_context: failed to load cell data");
     return GW_FATAL_INVALID_CONTEXT;
   }
+  if (buf_len == sizeof(global_state_buf)) {
+    buf_len = sizeof(MolDefault_GlobalState);
+  }
   mol_seg_t global_state_seg;
   global_state_seg.ptr = global_state_buf;
   global_state_seg.size = buf_len;
@@ -1120,8 +1126,9 @@ int _load_verify_transaction_witness(uint8_t rollup_script_hash[32],
     return GW_FATAL_INVALID_DATA;
   }
   mol_seg_t verify_tx_witness_seg = MolReader_Bytes_raw_bytes(&content_seg);
+  /* verify as compatible, the raw block may contain extra fields */
   if (MolReader_VerifyTransactionWitness_verify(&verify_tx_witness_seg,
-                                                false) != MOL_OK) {
+                                                true) != MOL_OK) {
     printf("input field is not VerifyTransactionWitness");
     return GW_FATAL_INVALID_DATA;
   }
@@ -1,12 +1,11 @@
 // Import from `core` instead of from `std` since we are in no-std mode
 use core::{convert::TryInto, result::Result};
 
-use gw_utils::cells::rollup::MAX_ROLLUP_WITNESS_SIZE;
 use gw_utils::gw_types;
-use gw_utils::gw_types::packed::RollupActionUnionReader;
 use gw_utils::{
     cells::rollup::{
         load_rollup_config, parse_rollup_action, search_rollup_cell, search_rollup_state,
+        RollupActionType,
     },
     ckb_std::{
         ckb_constants::Source,
@@ -56,19 +55,17 @@ pub fn main() -> Result<(), Error> {
     let (rollup_script_hash, lock_args) = parse_lock_args()?;
 
     // check rollup cell
-    let mut rollup_action_witness = [0u8; MAX_ROLLUP_WITNESS_SIZE];
     let index =
         search_rollup_cell(&rollup_script_hash, Source::Output).ok_or(Error::RollupCellNotFound)?;
-    let action = parse_rollup_action(&mut rollup_action_witness, index, Source::Output)?;
-    match action.to_enum() {
-        RollupActionUnionReader::RollupEnterChallenge(_)
-        | RollupActionUnionReader::RollupRevert(_) => {
+    let action = parse_rollup_action(index, Source::Output)?;
+    match action.action_type() {
+        RollupActionType::EnterChallenge | RollupActionType::Revert => {
             // state-validator will do the verification
             return Ok(());
         }
-        RollupActionUnionReader::RollupCancelChallenge(_) => {}
-        _ => {
-            debug!("unsupport action {:?}", action.to_enum());
+        RollupActionType::CancelChallenge => {}
+        action_type => {
+            debug!("unsupport action {:?}", action_type);
             return Err(Error::InvalidArgs);
         }
     }
@@ -1,47 +1,44 @@
 use crate::verifications::context::{verify_tx_context, TxContext, TxContextInput};
 use core::result::Result;
-use gw_state::{ckb_smt::smt::Pair, constants::GW_MAX_KV_PAIRS, kv_state::KVState};
+use gw_state::kv_state::KVState;
 use gw_types::{
     packed::{ChallengeLockArgs, RollupConfig, VerifyTransactionWitnessReader},
     prelude::*,
 };
+use gw_utils::gw_types;
 use gw_utils::{
-    cells::{rollup::MAX_ROLLUP_WITNESS_SIZE, utils::search_lock_hash},
+    cells::utils::search_lock_hash,
     ckb_std::{ckb_constants::Source, ckb_types::bytes::Bytes, debug},
     error::Error,
-    gw_types::packed::{BytesReader, WitnessArgsReader},
+    witness::WitnessCursor,
 };
-use gw_utils::{ckb_std::syscalls::load_witness, gw_types};
 
 /// Verify tx execution
 pub fn verify_tx_execution(
     rollup_config: &RollupConfig,
     lock_args: &ChallengeLockArgs,
 ) -> Result<(), Error> {
-    let mut buf = [0u8; MAX_ROLLUP_WITNESS_SIZE];
-    let loaded_len = load_witness(&mut buf, 0, 0, Source::GroupInput)?;
-    debug!("verity tx execution witness, loaded len: {}", loaded_len);
+    let witness = WitnessCursor::new(0, Source::GroupInput)?;
+    debug!(
+        "verity tx execution witness, witness len: {}",
+        witness.size()
+    );
 
-    let witness_args: BytesReader = {
-        let reader = WitnessArgsReader::from_slice(&buf[..loaded_len]).map_err(|_err| {
-            debug!("witness is not a valid WitnessArgsReader");
-            Error::Encoding
-        })?;
+    let witness_lock = witness
+        .witness_args_lock()?
+        .ok_or(Error::InvalidArgs)?
+        .load()?;
 
-        reader.lock().to_opt().ok_or(Error::InvalidArgs)?
-    };
-
-    let unlock_args = match VerifyTransactionWitnessReader::verify(witness_args.raw_data(), false) {
-        Ok(_) => VerifyTransactionWitnessReader::new_unchecked(witness_args.raw_data()),
+    // verify as compatible, the raw block may have extra fields
+    let unlock_args = match VerifyTransactionWitnessReader::verify(&witness_lock, true) {
+        Ok(_) => VerifyTransactionWitnessReader::new_unchecked(&witness_lock),
         Err(_) => return Err(Error::InvalidArgs),
     };
 
     let ctx = unlock_args.context();
     let tx = unlock_args.l2tx().to_entity();
-    let mut tree_buffer = [Pair::default(); GW_MAX_KV_PAIRS];
     let kv_state_proof: Bytes = unlock_args.kv_state_proof().unpack();
-    let kv_state = KVState::build(
-        &mut tree_buffer,
+    let kv_state = KVState::build_growable(
         ctx.kv_state(),
         &kv_state_proof,
         ctx.account_count().unpack(),
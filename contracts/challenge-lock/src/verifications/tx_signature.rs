@@ -1,6 +1,6 @@
 use crate::verifications::context::{verify_tx_context, TxContext, TxContextInput};
 use core::result::Result;
-use gw_state::{ckb_smt::smt::Pair, constants::GW_MAX_KV_PAIRS, kv_state::KVState};
+use gw_state::kv_state::KVState;
 use gw_types::{
     packed::{
         ChallengeLockArgs, RollupConfig, VerifyTransactionSignatureWitness,
@@ -55,17 +55,16 @@ pub fn verify_tx_signature(
         .to_opt()
         .ok_or(Error::InvalidArgs)?
         .unpack();
-    let unlock_args = match VerifyTransactionSignatureWitnessReader::verify(&witness_args, false) {
+    // verify as compatible, the raw block may have extra fields
+    let unlock_args = match VerifyTransactionSignatureWitnessReader::verify(&witness_args, true) {
         Ok(_) => VerifyTransactionSignatureWitness::new_unchecked(witness_args),
         Err(_) => return Err(Error::InvalidArgs),
     };
     let ctx = unlock_args.context();
     let tx = unlock_args.l2tx();
     let account_count: u32 = ctx.account_count().unpack();
-    let mut tree_buffer = [Pair::default(); GW_MAX_KV_PAIRS];
     let kv_state_proof: Bytes = unlock_args.kv_state_proof().unpack();
-    let kv_state = KVState::build(
-        &mut tree_buffer,
+    let kv_state = KVState::build_growable(
         ctx.kv_state().as_reader(),
         &kv_state_proof,
         account_count,
@@ -1,5 +1,5 @@
 use core::result::Result;
-use gw_common::{blake2b::new_blake2b, H256};
+use gw_common::H256;
 use gw_types::{
     packed::{
         ChallengeLockArgs, RawWithdrawalRequest, VerifyWithdrawalWitness,
@@ -18,13 +18,18 @@ use gw_utils::{
         ckb_types::{bytes::Bytes, prelude::Unpack as CKBUnpack},
         debug,
         high_level::load_witness_args,
+        since::Since,
     },
     error::Error,
     signature::check_l2_account_signature_cell,
+    withdrawal::{
+        calculate_withdrawal_message, check_withdrawal_valid_until, WithdrawalRequestExt,
+    },
 };
 
 struct WithdrawalContext {
     raw_withdrawal: RawWithdrawalRequest,
+    valid_until: Option<Since>,
     sender_script_hash: H256,
 }
 
@@ -34,7 +39,8 @@ fn verify_withdrawal_proof(lock_args: &ChallengeLockArgs) -> Result<WithdrawalCo
         .to_opt()
         .ok_or(Error::InvalidArgs)?
         .unpack();
-    let unlock_args = match VerifyWithdrawalWitnessReader::verify(&witness_args, false) {
+    // verify as compatible, the raw block may have extra fields
+    let unlock_args = match VerifyWithdrawalWitnessReader::verify(&witness_args, true) {
         Ok(_) => VerifyWithdrawalWitness::new_unchecked(witness_args),
         Err(_) => return Err(Error::InvalidArgs),
     };
@@ -73,26 +79,25 @@ fn verify_withdrawal_proof(lock_args: &ChallengeLockArgs) -> Result<WithdrawalCo
         return Err(Error::MerkleProof);
     }
 
+    // an expired withdrawal is invalid even with a valid signature
+    let valid_until = withdrawal.valid_until()?;
+    if let Some(valid_until) = &valid_until {
+        check_withdrawal_valid_until(
+            valid_until,
+            raw_block.number().unpack(),
+            raw_block.timestamp().unpack(),
+        )?;
+    }
+
     let context = WithdrawalContext {
         raw_withdrawal,
+        valid_until,
         sender_script_hash,
     };
 
     Ok(context)
 }
 
-fn calc_withdrawal_message(
-    rollup_script_hash: &[u8; 32],
-    raw_withdrawal: &RawWithdrawalRequest,
-) -> [u8; 32] {
-    let mut hasher = new_blake2b();
-    hasher.update(rollup_script_hash);
-    hasher.update(raw_withdrawal.as_slice());
-    let mut message = [0u8; 32];
-    hasher.finalize(&mut message);
-    message
-}
-
 /// Verify withdrawal signature
 pub fn verify_withdrawal(
     rollup_script_hash: &[u8; 32],
@@ -100,11 +105,16 @@ pub fn verify_withdrawal(
 ) -> Result<(), Error> {
     let WithdrawalContext {
         raw_withdrawal,
+        valid_until,
         sender_script_hash,
     } = verify_withdrawal_proof(lock_args)?;
 
     // verify withdrawal signature
-    let message = calc_withdrawal_message(rollup_script_hash, &raw_withdrawal);
+    let message = calculate_withdrawal_message(
+        rollup_script_hash,
+        &raw_withdrawal.as_reader(),
+        valid_until.as_ref(),
+    );
     // verify sender's script is in the input
     check_l2_account_signature_cell(&sender_script_hash, message.into())?;
     Ok(())
@@ -139,3 +139,14 @@ extern "C" {
         proof_length: u32,
     ) -> cty::c_int;
 }
+extern "C" {
+    pub fn smt_verify_update(
+        key: *const u8,
+        old_value: *const u8,
+        new_value: *const u8,
+        old_root: *const u8,
+        new_root: *const u8,
+        proof: *const u8,
+        proof_length: u32,
+    ) -> cty::c_int;
+}
@@ -1,5 +1,7 @@
 #![cfg_attr(not(tests), no_std)]
 
+extern crate alloc;
+
 #[allow(dead_code)]
 #[allow(non_upper_case_globals)]
 mod bindings;
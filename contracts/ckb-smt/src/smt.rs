@@ -1,28 +1,64 @@
 use crate::bindings::{
     smt_calculate_root, smt_pair_t, smt_state_fetch, smt_state_init, smt_state_insert,
-    smt_state_normalize, smt_state_t, smt_verify, SMTErrorCode,
+    smt_state_normalize, smt_state_t, smt_verify, smt_verify_update, SMTErrorCode,
+    SMTErrorCode_ERROR_INSUFFICIENT_CAPACITY,
 };
+use alloc::{vec, vec::Vec};
 pub type Pair = smt_pair_t;
 
+/// Pairs buffer of the tree
+enum Buffer<'a> {
+    /// Fixed buffer supplied by the caller
+    Borrowed(&'a mut [Pair]),
+    /// Heap buffer, grows when the pairs exceed the capacity
+    Owned(Vec<Pair>),
+}
+
 pub struct Tree<'a> {
-    _buf: &'a mut [Pair],
+    buf: Buffer<'a>,
     state: smt_state_t,
 }
 
 impl<'a> Tree<'a> {
     pub fn new(buf: &'a mut [Pair]) -> Tree<'a> {
-        let state = unsafe {
+        let state = Self::init_state(buf);
+        Self {
+            buf: Buffer::Borrowed(buf),
+            state,
+        }
+    }
+
+    fn init_state(buf: &mut [Pair]) -> smt_state_t {
+        unsafe {
             let mut state = core::mem::MaybeUninit::uninit();
             smt_state_init(state.as_mut_ptr(), buf.as_mut_ptr(), buf.len() as u32);
             state.assume_init()
-        };
-        Self { _buf: buf, state }
+        }
+    }
+
+    /// Double the capacity of a heap buffer
+    /// returns false if the buffer is supplied by the caller
+    fn grow(&mut self) -> bool {
+        match &mut self.buf {
+            Buffer::Owned(buf) => {
+                let capacity = buf.len() * 2;
+                buf.resize(capacity, Pair::default());
+                // the buffer may be reallocated
+                self.state.pairs = buf.as_mut_ptr();
+                self.state.capacity = capacity as u32;
+                true
+            }
+            Buffer::Borrowed(_) => false,
+        }
     }
 
     pub fn update(&mut self, key: &[u8; 32], value: &[u8; 32]) -> Result<(), SMTErrorCode> {
-        match unsafe { smt_state_insert(&mut self.state, key.as_ptr(), value.as_ptr()) } {
-            0 => Ok(()),
-            err => Err(err as u32),
+        loop {
+            match unsafe { smt_state_insert(&mut self.state, key.as_ptr(), value.as_ptr()) } {
+                0 => return Ok(()),
+                err if err as u32 == SMTErrorCode_ERROR_INSUFFICIENT_CAPACITY && self.grow() => {}
+                err => return Err(err as u32),
+            }
         }
     }
 
@@ -73,3 +109,200 @@ impl<'a> Tree<'a> {
         self.state.len == 0
     }
 }
+
+impl Tree<'static> {
+    /// Create a tree on the heap, the capacity grows as pairs are inserted
+    pub fn with_capacity(capacity: usize) -> Tree<'static> {
+        let mut buf = vec![Pair::default(); capacity.max(1)];
+        let state = Self::init_state(&mut buf);
+        Self {
+            buf: Buffer::Owned(buf),
+            state,
+        }
+    }
+}
+
+/// Verify the value of `key` is updated from `old_value` under `old_root`
+/// to `new_value` under `new_root`, both roots are proved by the same `proof`.
+///
+/// The proof is walked once, the old and the new node are merged with the
+/// same siblings, see `smt_verify_update` in `c/rust-binding/smt_wrapper.c`.
+/// Only a single leaf proof is accepted.
+pub fn verify_update(
+    key: &[u8; 32],
+    old_value: &[u8; 32],
+    new_value: &[u8; 32],
+    old_root: &[u8; 32],
+    new_root: &[u8; 32],
+    proof: &[u8],
+) -> Result<(), SMTErrorCode> {
+    match unsafe {
+        smt_verify_update(
+            key.as_ptr(),
+            old_value.as_ptr(),
+            new_value.as_ptr(),
+            old_root.as_ptr(),
+            new_root.as_ptr(),
+            proof.as_ptr(),
+            proof.len() as u32,
+        )
+    } {
+        0 => Ok(()),
+        err => Err(err as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: u32) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[..4].copy_from_slice(&i.to_le_bytes());
+        key[31] = 1;
+        key
+    }
+
+    fn value(i: u32) -> [u8; 32] {
+        let mut value = [0u8; 32];
+        value[28..].copy_from_slice(&i.to_le_bytes());
+        value[0] = 1;
+        value
+    }
+
+    #[test]
+    fn test_owned_tree_grows_beyond_initial_capacity() {
+        const COUNT: u32 = 1000;
+        let mut tree = Tree::with_capacity(1);
+        for i in 0..COUNT {
+            tree.update(&key(i), &value(i)).expect("update");
+        }
+        // update existing keys after the buffer grew
+        for i in (0..COUNT).step_by(2) {
+            tree.update(&key(i), &value(i + COUNT)).expect("update");
+        }
+        tree.normalize();
+        assert!(tree.state.capacity as usize >= COUNT as usize);
+        for i in 0..COUNT {
+            let expected = if i % 2 == 0 {
+                value(i + COUNT)
+            } else {
+                value(i)
+            };
+            assert_eq!(tree.get(&key(i)), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn test_borrowed_tree_does_not_grow() {
+        let mut buf = [Pair::default(); 1];
+        let mut tree = Tree::new(&mut buf);
+        tree.update(&key(0), &value(0)).expect("update");
+        assert_eq!(
+            tree.update(&key(1), &value(1)),
+            Err(SMTErrorCode_ERROR_INSUFFICIENT_CAPACITY)
+        );
+        assert_eq!(tree.get(&key(0)), Ok(value(0)));
+    }
+
+    /// Roots of `key` with `old_value` and `new_value` by `smt_calculate_root`
+    fn calculate_roots(
+        key: &[u8; 32],
+        old_value: &[u8; 32],
+        new_value: &[u8; 32],
+        proof: &[u8],
+    ) -> ([u8; 32], [u8; 32]) {
+        let calculate_root = |value: &[u8; 32]| {
+            let mut tree = Tree::with_capacity(1);
+            tree.update(key, value).expect("update");
+            tree.calculate_root(proof).expect("calculate root")
+        };
+        (calculate_root(old_value), calculate_root(new_value))
+    }
+
+    /// Update verification by two `smt_verify` walks
+    fn verify_update_by_two_walks(
+        key: &[u8; 32],
+        old_value: &[u8; 32],
+        new_value: &[u8; 32],
+        old_root: &[u8; 32],
+        new_root: &[u8; 32],
+        proof: &[u8],
+    ) -> bool {
+        [(old_value, old_root), (new_value, new_root)]
+            .iter()
+            .all(|(value, root)| {
+                let mut tree = Tree::with_capacity(1);
+                tree.update(key, value).expect("update");
+                tree.verify(root, proof).is_ok()
+            })
+    }
+
+    /// Single leaf proofs: without siblings, with siblings at the lowest
+    /// heights, and with siblings on both sides of the path
+    fn single_leaf_proofs() -> Vec<Vec<u8>> {
+        let mut proofs = vec![vec![0x4C, 0x4F, 0x00]];
+        let mut proof = vec![0x4C];
+        for i in 0..2u8 {
+            proof.push(0x50);
+            proof.extend_from_slice(&[i + 7; 32]);
+        }
+        proof.extend_from_slice(&[0x4F, 254]);
+        proofs.push(proof);
+        let mut proof = vec![0x4C, 0x4F, 31];
+        for i in 0..225u8 {
+            proof.push(0x50);
+            proof.extend_from_slice(&[i; 32]);
+        }
+        proofs.push(proof);
+        proofs
+    }
+
+    #[test]
+    fn test_verify_update_matches_two_walks() {
+        let zero = [0u8; 32];
+        let cases = [
+            (key(1), zero, value(1)),
+            (key(2), value(2), value(3)),
+            (key(3), value(4), zero),
+        ];
+        for proof in single_leaf_proofs() {
+            for (key, old_value, new_value) in cases.iter() {
+                let (old_root, new_root) = calculate_roots(key, old_value, new_value, &proof);
+                assert!(verify_update_by_two_walks(
+                    key, old_value, new_value, &old_root, &new_root, &proof
+                ));
+                assert_eq!(
+                    verify_update(key, old_value, new_value, &old_root, &new_root, &proof),
+                    Ok(())
+                );
+
+                // wrong roots are rejected by both
+                for (old_root, new_root) in [(new_root, new_root), (old_root, old_root)].iter() {
+                    if old_value == new_value {
+                        continue;
+                    }
+                    assert!(!verify_update_by_two_walks(
+                        key, old_value, new_value, old_root, new_root, &proof
+                    ));
+                    assert!(
+                        verify_update(key, old_value, new_value, old_root, new_root, &proof)
+                            .is_err()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_update_rejects_multi_leaf_proof() {
+        let (old_value, new_value) = ([0u8; 32], value(1));
+        // two leaves merged by 0x48
+        let proof = [0x4C, 0x4C, 0x48, 0x4F, 0x00];
+        let root = [0u8; 32];
+        assert!(verify_update(&key(1), &old_value, &new_value, &root, &root, &proof).is_err());
+        // a truncated sibling
+        let proof = [0x4C, 0x50, 0x01];
+        assert!(verify_update(&key(1), &old_value, &new_value, &root, &root, &proof).is_err());
+    }
+}
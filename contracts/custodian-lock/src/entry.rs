@@ -5,12 +5,11 @@ use gw_utils::{
     cells::{
         rollup::{
             load_rollup_config, parse_rollup_action, search_rollup_cell, search_rollup_state,
-            MAX_ROLLUP_WITNESS_SIZE,
         },
         utils::search_lock_hash,
     },
     ckb_std::high_level::load_cell_lock,
-    gw_types::packed::{DepositLockArgs, DepositLockArgsReader, RollupActionUnionReader},
+    gw_types::packed::{DepositLockArgs, DepositLockArgsReader},
 };
 
 // Import CKB syscalls and structures
@@ -41,7 +40,7 @@ fn parse_lock_args() -> Result<([u8; 32], CustodianLockArgs), Error> {
         return Err(Error::InvalidArgs);
     }
     rollup_type_hash.copy_from_slice(&args[..32]);
-    match CustodianLockArgsReader::verify(&args.slice(32..), false) {
+    match CustodianLockArgsReader::verify(&args.slice(32..), true) {
         Ok(()) => Ok((
             rollup_type_hash,
             CustodianLockArgs::new_unchecked(args.slice(32..)),
@@ -97,7 +96,7 @@ pub fn main() -> Result<(), Error> {
             return Err(Error::InvalidArgs);
         }
 
-        match DepositLockArgsReader::verify(&args.slice(32..), false) {
+        match DepositLockArgsReader::verify(&args.slice(32..), true) {
             Ok(_) => DepositLockArgs::new_unchecked(args.slice(32..)),
             Err(_) => return Err(Error::InvalidOutput),
         }
@@ -111,24 +110,21 @@ pub fn main() -> Result<(), Error> {
 
     // check deposit block is reverted
     let deposit_block_hash = lock_args.deposit_block_hash();
-    let mut rollup_action_witness = [0u8; MAX_ROLLUP_WITNESS_SIZE];
     let rollup_action = {
         let index = search_rollup_cell(&rollup_type_hash, Source::Output)
             .ok_or(Error::RollupCellNotFound)?;
-        parse_rollup_action(&mut rollup_action_witness, index, Source::Output)?
+        parse_rollup_action(index, Source::Output)?
     };
 
-    match rollup_action.to_enum() {
-        RollupActionUnionReader::RollupSubmitBlock(args) => {
-            if args
-                .reverted_block_hashes()
-                .iter()
-                .any(|hash| hash.as_slice() == deposit_block_hash.as_slice())
-            {
-                return Ok(());
-            }
-            Err(Error::InvalidRevertedBlocks)
-        }
-        _ => Err(Error::InvalidRevertedBlocks),
+    let reverted_block_hashes = rollup_action
+        .load_reverted_block_hashes()?
+        .ok_or(Error::InvalidRevertedBlocks)?;
+    if reverted_block_hashes
+        .as_reader()
+        .iter()
+        .any(|hash| hash.as_slice() == deposit_block_hash.as_slice())
+    {
+        return Ok(());
     }
+    Err(Error::InvalidRevertedBlocks)
 }
@@ -4,6 +4,12 @@
 //! or can be unlocked by user.
 //!
 //! Args: DepositLockArgs
+//!
+//! If `cancel_pubkey_hash` is set in the deposit lock args, the user can cancel by a signature
+//! instead of an owner cell, anyone can relay the signed cancel.
+//! witness: WitnessArgs { lock: signature(65 bytes) | refund_lock_hash(32 bytes) }
+//! signing message: hash(deposit out point | refund_lock_hash | relayer_fee)
+//! The refund cell is the output at the same index as the deposit cell in the inputs.
 
 // Import from `core` instead of from `std` since we are in no-std mode
 use core::result::Result;
@@ -17,11 +23,26 @@ use core::result::Result;
 use crate::ckb_std::{
     ckb_constants::Source,
     ckb_types::{bytes::Bytes, prelude::Unpack as CKBTypeUnpack},
-    high_level::{load_input_since, load_script},
+    debug,
+    error::SysError,
+    high_level::{
+        load_cell_capacity, load_cell_data, load_cell_lock_hash, load_cell_type_hash, load_input,
+        load_input_since, load_script, load_script_hash, load_witness_args,
+    },
     since::Since,
 };
 
-use gw_utils::cells::{rollup::search_rollup_cell, utils::search_lock_hash};
+use gw_utils::{
+    account_lock::{normalize_signature, SIGNATURE_SIZE},
+    cells::{
+        rollup::{search_rollup_action, RollupActionType},
+        utils::search_lock_hash,
+    },
+    deposit::DepositLockArgsExt,
+    gw_common::blake2b::new_blake2b,
+    since::{check_since_timeout, parse_since},
+};
+use secp256k1_utils::recover_uncompressed_key;
 
 use gw_types::{
     packed::{DepositLockArgs, DepositLockArgsReader},
@@ -40,7 +61,8 @@ fn parse_lock_args() -> Result<([u8; 32], DepositLockArgs), Error> {
         return Err(Error::InvalidArgs);
     }
     rollup_type_hash.copy_from_slice(&args[..32]);
-    match DepositLockArgsReader::verify(&args.slice(32..), false) {
+    // the deposit lock args may have extra fields, see `gw_utils::deposit`
+    match DepositLockArgsReader::verify(&args.slice(32..), true) {
         Ok(()) => Ok((
             rollup_type_hash,
             DepositLockArgs::new_unchecked(args.slice(32..)),
@@ -50,30 +72,122 @@ fn parse_lock_args() -> Result<([u8; 32], DepositLockArgs), Error> {
 }
 
 // We have two unlock paths
-// 1. unlock by Rollup cell
-// 2. unlock by user after timeout
+// 1. unlock by Rollup cell with the RollupSubmitBlock action
+// 2. unlock by user after timeout, or at any time if the timeout is malformed
 //
 // We always try the 1 first, then try 2, otherwise the unlock return a failure.
 pub fn main() -> Result<(), Error> {
     let (rollup_type_hash, lock_args) = parse_lock_args()?;
     // try unlock by Rollup
-    // return success if rollup cell in the inputs, the following verification will be handled by rollup state validator.
-    if search_rollup_cell(&rollup_type_hash, Source::Input).is_some() {
-        return Ok(());
+    // return success if rollup cell in the inputs and the action is submit block,
+    // the following verification will be handled by rollup state validator.
+    if let Some(action) = search_rollup_action(&rollup_type_hash)? {
+        if action.action_type() == RollupActionType::SubmitBlock {
+            return Ok(());
+        }
+        debug!("deposit can't be unlocked by {:?}", action.action_type());
     }
 
     // unlock by user
-    // 1. check since is satisfied the cancel timeout
-    let input_since = Since::new(load_input_since(0, Source::GroupInput)?);
+    // 1. check since is satisfied the cancel timeout,
+    //    a malformed cancel timeout is rejected by the rollup, the user can cancel at any time
     let cancel_timeout = Since::new(lock_args.cancel_timeout().unpack());
-    if input_since.flags() != cancel_timeout.flags()
-        || input_since.as_u64() < cancel_timeout.as_u64()
-    {
-        return Err(Error::InvalidSince);
+    if parse_since(&cancel_timeout).is_ok() {
+        let input_since = Since::new(load_input_since(0, Source::GroupInput)?);
+        check_since_timeout(&input_since, &cancel_timeout)?;
+    } else {
+        debug!("malformed cancel timeout, skip the since check");
     }
     // 2. search owner cell
-    match search_lock_hash(&lock_args.owner_lock_hash().unpack(), Source::Input) {
-        Some(_) => Ok(()),
+    if search_lock_hash(&lock_args.owner_lock_hash().unpack(), Source::Input).is_some() {
+        return Ok(());
+    }
+    // 3. or verify the cancel signature
+    match lock_args.cancel_pubkey_hash()? {
+        Some(pubkey_hash) => verify_cancel_signature(&lock_args, &pubkey_hash),
         None => Err(Error::OwnerCellNotFound),
     }
 }
+
+/// Verify the signed cancel, the deposit is refunded to `refund_lock_hash`
+/// with at most `relayer_fee` CKB taken by the relayer
+fn verify_cancel_signature(
+    lock_args: &DepositLockArgs,
+    pubkey_hash: &[u8; 20],
+) -> Result<(), Error> {
+    // only one deposit cell can be cancelled by a signature
+    match load_input(1, Source::GroupInput) {
+        Err(SysError::IndexOutOfBound) => {}
+        Ok(_) => {
+            debug!("signed cancel only accepts one deposit cell");
+            return Err(Error::InvalidArgs);
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    // parse witness
+    let witness_lock: Bytes = load_witness_args(0, Source::GroupInput)?
+        .lock()
+        .to_opt()
+        .ok_or(Error::OwnerCellNotFound)?
+        .unpack();
+    if witness_lock.len() != SIGNATURE_SIZE + 32 {
+        debug!("invalid signed cancel witness len: {}", witness_lock.len());
+        return Err(Error::InvalidArgs);
+    }
+    let signature = normalize_signature(&witness_lock[..SIGNATURE_SIZE])?;
+    let mut refund_lock_hash = [0u8; 32];
+    refund_lock_hash.copy_from_slice(&witness_lock[SIGNATURE_SIZE..]);
+    let relayer_fee = lock_args.relayer_fee()?;
+
+    // verify signature
+    let out_point = load_input(0, Source::GroupInput)?.previous_output();
+    let mut message = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(out_point.as_slice());
+    hasher.update(&refund_lock_hash);
+    hasher.update(&relayer_fee.to_le_bytes());
+    hasher.finalize(&mut message);
+    let pubkey = recover_uncompressed_key(message, signature).map_err(|err| {
+        debug!("failed to recover secp256k1 pubkey, error number: {}", err);
+        Error::WrongSignature
+    })?;
+    if &blake160_compressed(&pubkey) != pubkey_hash {
+        debug!("wrong cancel signature");
+        return Err(Error::WrongSignature);
+    }
+
+    // check refund cell, the output at the same index as the deposit cell,
+    // so one output can't refund two deposits
+    let index =
+        search_lock_hash(&load_script_hash()?, Source::Input).ok_or(Error::IndexOutOfBound)?;
+    let deposit_capacity = load_cell_capacity(0, Source::GroupInput)?;
+    if load_cell_lock_hash(index, Source::Output)? != refund_lock_hash
+        || load_cell_capacity(index, Source::Output)? < deposit_capacity.saturating_sub(relayer_fee)
+        || load_cell_type_hash(index, Source::Output)?
+            != load_cell_type_hash(0, Source::GroupInput)?
+        || load_cell_data(index, Source::Output)? != load_cell_data(0, Source::GroupInput)?
+    {
+        debug!("invalid refund cell");
+        return Err(Error::InvalidOutput);
+    }
+    Ok(())
+}
+
+/// blake160 of the compressed pubkey, same as the CKB secp256k1 lock
+fn blake160_compressed(uncompressed_pubkey: &[u8; 65]) -> [u8; 20] {
+    let mut compressed = [0u8; 33];
+    compressed[0] = if uncompressed_pubkey[64] & 1 == 0 {
+        0x02
+    } else {
+        0x03
+    };
+    compressed[1..].copy_from_slice(&uncompressed_pubkey[1..33]);
+    let mut hash = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(&compressed);
+    hasher.finalize(&mut hash);
+    let mut pubkey_hash = [0u8; 20];
+    pubkey_hash.copy_from_slice(&hash[..20]);
+    pubkey_hash
+}
@@ -13,7 +13,6 @@
 
 // define modules
 mod entry;
-mod eth_signature;
 
 pub use gw_utils::ckb_std;
 
@@ -25,7 +25,35 @@ impl<'a> KVState<'a> {
         account_count: u32,
         current_root: Option<H256>,
     ) -> Result<KVState<'a>, Error> {
-        let mut tree = Tree::new(buf);
+        Self::build_with_tree(Tree::new(buf), kv_pairs, proof, account_count, current_root)
+    }
+
+    /// Build a KVState on the heap, the number of kv pairs is only limited by cycles
+    /// params: same as `build`
+    pub fn build_growable(
+        kv_pairs: KVPairVecReader,
+        proof: &'a [u8],
+        account_count: u32,
+        current_root: Option<H256>,
+    ) -> Result<KVState<'a>, Error> {
+        // reserve space for the updates of the state
+        let capacity = kv_pairs.len() * 2;
+        Self::build_with_tree(
+            Tree::with_capacity(capacity),
+            kv_pairs,
+            proof,
+            account_count,
+            current_root,
+        )
+    }
+
+    fn build_with_tree(
+        mut tree: Tree<'a>,
+        kv_pairs: KVPairVecReader,
+        proof: &'a [u8],
+        account_count: u32,
+        current_root: Option<H256>,
+    ) -> Result<KVState<'a>, Error> {
         for pair in kv_pairs.iter() {
             tree.update(&pair.k().unpack(), &pair.v().unpack())
                 .map_err(|err| {
@@ -0,0 +1,192 @@
+//! Layer2 account lock
+//!
+//! Shared verification of account locks, a lock only implements the
+//! signature scheme by implementing `AccountLock`.
+//!
+//! script args: rollup_script_hash(32 bytes) | address
+//! data: see `SignatureCellData`
+//! witness: WitnessArgs { lock: signature(65 bytes or 64 bytes EIP-2098 compact signature) }
+
+use core::fmt::Debug;
+
+use crate::{
+    cells::utils::search_lock_hash,
+    error::Error,
+    signature::{SignatureCellData, SigningType, TypedTransaction},
+};
+use ckb_std::{
+    ckb_constants::Source,
+    ckb_types::{bytes::Bytes, prelude::Unpack as CKBUnpack},
+    debug,
+    high_level::{load_cell_data, load_script, load_witness_args},
+};
+use gw_common::H256;
+
+pub const SIGNATURE_SIZE: usize = 65;
+pub const COMPACT_SIGNATURE_SIZE: usize = 64;
+/// secp256k1 curve order / 2
+const SECP256K1_HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+pub trait AccountLock {
+    /// Address in the script args, after the rollup script hash
+    type Address: Default + AsRef<[u8]> + AsMut<[u8]> + PartialEq + Debug;
+
+    /// Signing message of `SigningType::WithPrefix`
+    fn prefixed_message(&self, message: &H256) -> H256;
+
+    /// Recover signer's address from the signature
+    /// the signature is normalized by `normalize_signature`
+    fn recover_address(
+        &self,
+        message: &H256,
+        signature: &[u8; SIGNATURE_SIZE],
+    ) -> Result<Self::Address, Error>;
+
+    /// Signing message of `SigningType::TypedData`, unsupported by default
+    fn typed_data_message(&self, _typed_tx: &TypedTransaction) -> Result<H256, Error> {
+        debug!("Unsupported typed data signing");
+        Err(Error::WrongSignature)
+    }
+
+    /// Verify the signing message is signed by `address`
+    fn verify_signature(
+        &self,
+        address: &Self::Address,
+        signing_message: &H256,
+        signature: &[u8; SIGNATURE_SIZE],
+    ) -> Result<(), Error> {
+        let signer = self.recover_address(signing_message, signature)?;
+        if &signer != address {
+            debug!("Wrong signature, message: {:?}", signing_message);
+            return Err(Error::WrongSignature);
+        }
+        Ok(())
+    }
+}
+
+/// Account lock entry
+pub fn main<L: AccountLock + Default>() -> Result<(), Error> {
+    // parse args
+    let script = load_script()?;
+    let args: Bytes = CKBUnpack::unpack(&script.args());
+    let (_rollup_script_hash, address) = parse_lock_args::<L>(&args)?;
+    debug!("address {:?}", &address);
+
+    verify_account_lock(&L::default(), &address)
+}
+
+/// Verify the account lock cell of the current script group is signed by `address`
+pub fn verify_account_lock<L: AccountLock>(lock: &L, address: &L::Address) -> Result<(), Error> {
+    // parse data
+    let cell_data = parse_data()?;
+    let owner_lock_hash = cell_data.owner_lock_hash;
+
+    // check owner lock hash cell
+    // to prevent others unlock this cell
+    if search_lock_hash(&owner_lock_hash, Source::Input).is_none() {
+        return Err(Error::OwnerCellNotFound);
+    }
+
+    // verify signature
+    let signing_message = calc_signing_message(lock, &cell_data)?;
+    debug!("Verify message signature {:?}", &signing_message);
+    let signature = load_signature_from_witness()?;
+    lock.verify_signature(address, &signing_message, &signature)
+}
+
+/// Calculate the message signed by the account
+pub fn calc_signing_message<L: AccountLock>(
+    lock: &L,
+    cell_data: &SignatureCellData,
+) -> Result<H256, Error> {
+    let message = cell_data.signing_message()?;
+    match cell_data.signing_type {
+        SigningType::WithPrefix => Ok(lock.prefixed_message(&message)),
+        SigningType::Raw => Ok(message),
+        SigningType::TypedData => {
+            let typed_tx = cell_data.typed_tx.as_ref().ok_or(Error::Encoding)?;
+            // the typed data must be the transaction of the message
+            if typed_tx.calc_message() != message {
+                debug!("Typed transaction mismatch message: {:?}", message);
+                return Err(Error::WrongSignature);
+            }
+            lock.typed_data_message(typed_tx)
+        }
+    }
+}
+
+/// parse script args
+/// return (rollup_script_hash, address)
+pub fn parse_lock_args<L: AccountLock>(args: &[u8]) -> Result<(H256, L::Address), Error> {
+    let mut address = L::Address::default();
+    if args.len() != 32 + address.as_ref().len() {
+        debug!("Invalid lock args len: {}", args.len());
+        return Err(Error::InvalidArgs);
+    }
+    let rollup_script_hash = {
+        let mut buf = [0u8; 32];
+        buf.copy_from_slice(&args[..32]);
+        buf.into()
+    };
+    address.as_mut().copy_from_slice(&args[32..]);
+    Ok((rollup_script_hash, address))
+}
+
+/// load signature from witness
+pub fn load_signature_from_witness() -> Result<[u8; SIGNATURE_SIZE], Error> {
+    let witness_args = load_witness_args(0, Source::GroupInput)?;
+    let signature: Bytes = witness_args
+        .lock()
+        .to_opt()
+        .ok_or(Error::WrongSignature)?
+        .unpack();
+    normalize_signature(&signature)
+}
+
+/// Normalize a signature to r(32 bytes) | s(32 bytes) | recovery_id(1 byte, 0 or 1)
+///
+/// accepts 65 bytes signature with v in 0, 1, 27 or 28,
+/// and 64 bytes EIP-2098 compact signature r | y_parity_and_s.
+/// signatures with high-s are rejected to prevent malleability.
+pub fn normalize_signature(signature: &[u8]) -> Result<[u8; SIGNATURE_SIZE], Error> {
+    let mut buf = [0u8; SIGNATURE_SIZE];
+    match signature.len() {
+        SIGNATURE_SIZE => {
+            buf.copy_from_slice(signature);
+            buf[64] = match buf[64] {
+                v @ 0..=1 => v,
+                v @ 27..=28 => v - 27,
+                v => {
+                    debug!("Invalid signature v: {}", v);
+                    return Err(Error::WrongSignature);
+                }
+            };
+        }
+        COMPACT_SIGNATURE_SIZE => {
+            buf[..64].copy_from_slice(signature);
+            buf[64] = buf[32] >> 7;
+            buf[32] &= 0x7f;
+        }
+        len => {
+            debug!(
+                "signature len: {}, expected len: {} or {}",
+                len, SIGNATURE_SIZE, COMPACT_SIGNATURE_SIZE
+            );
+            return Err(Error::WrongSignature);
+        }
+    }
+    if buf[32..64] > SECP256K1_HALF_ORDER[..] {
+        debug!("Invalid signature with high-s");
+        return Err(Error::WrongSignature);
+    }
+    Ok(buf)
+}
+
+/// parse cell's data
+pub fn parse_data() -> Result<SignatureCellData, Error> {
+    let data = load_cell_data(0, Source::GroupInput)?;
+    SignatureCellData::from_slice(&data)
+}
@@ -0,0 +1,87 @@
+//! RawL2Block extra fields
+//!
+//! Optional fields appended after the upstream `RawL2Block` fields,
+//! the rollup action is parsed as a compatible molecule table.
+//! A missing or empty extra field means the field is unset,
+//! the extra fields are covered by the block hash.
+//!
+//! extra fields:
+//! 0. message_root: 32 bytes, merkle root of the L1 -> L2 messages consumed by the block
+//! 1. deposit_call_count: u32 LE, the leading transactions of the block are the deposit calls,
+//!    see `deposit`
+
+use core::convert::TryInto;
+
+use ckb_std::debug;
+use gw_common::H256;
+use gw_types::{
+    packed::{RawL2Block, RawL2BlockReader},
+    prelude::*,
+};
+
+use crate::{compatible, error::Error};
+
+const MESSAGE_ROOT: usize = 0;
+const DEPOSIT_CALL_COUNT: usize = 1;
+
+pub trait RawL2BlockExt {
+    /// Extra field by the index after the upstream fields,
+    /// return None if the field is missing or empty
+    fn extra_field(&self, index: usize) -> Option<&[u8]>;
+
+    /// Root of the consumed L1 -> L2 messages,
+    /// return None if the block consumes no message
+    fn message_root(&self) -> Result<Option<H256>, Error> {
+        parse_h256(self.extra_field(MESSAGE_ROOT))
+    }
+
+    /// Number of the leading transactions which are the calls of the deposits,
+    /// 0 if the block has no deposit call
+    fn deposit_call_count(&self) -> Result<u32, Error> {
+        match self.extra_field(DEPOSIT_CALL_COUNT) {
+            Some(field) => {
+                let buf: [u8; 4] = field.try_into().map_err(|_| {
+                    debug!("invalid raw block u32 field, len: {}", field.len());
+                    Error::Encoding
+                })?;
+                Ok(u32::from_le_bytes(buf))
+            }
+            None => Ok(0),
+        }
+    }
+}
+
+impl<'r> RawL2BlockExt for RawL2BlockReader<'r> {
+    fn extra_field(&self, index: usize) -> Option<&[u8]> {
+        compatible::extra_field(
+            self.as_slice(),
+            self.field_count(),
+            RawL2BlockReader::FIELD_COUNT,
+            index,
+        )
+    }
+}
+
+impl RawL2BlockExt for RawL2Block {
+    fn extra_field(&self, index: usize) -> Option<&[u8]> {
+        compatible::extra_field(
+            self.as_slice(),
+            self.field_count(),
+            RawL2Block::FIELD_COUNT,
+            index,
+        )
+    }
+}
+
+fn parse_h256(field: Option<&[u8]>) -> Result<Option<H256>, Error> {
+    match field {
+        Some(field) => {
+            let buf: [u8; 32] = field.try_into().map_err(|_| {
+                debug!("invalid raw block h256 field, len: {}", field.len());
+                Error::Encoding
+            })?;
+            Ok(Some(buf.into()))
+        }
+        None => Ok(None),
+    }
+}
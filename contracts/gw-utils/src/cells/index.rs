@@ -0,0 +1,127 @@
+//! Rollup cells index
+//!
+//! Classify the rollup lock cells of a transaction in a single pass over
+//! the inputs and outputs, instead of scanning the cells once for each kind.
+
+use super::{
+    lock_cells::{
+        build_challenge_cell, build_custodian_cell, build_deposit_cell, build_stake_cell,
+        build_withdrawal_cell, check_block_producer_stake_cells, check_challenge_cells,
+        parse_lock_args,
+    },
+    message::{build_message_cell, MessageCell, MessageLockArgs},
+    types::{ChallengeCell, CustodianCell, DepositRequestCell, StakeCell, WithdrawalCell},
+};
+use crate::{error::Error, rollup_config::RollupConfigExt, withdrawal::parse_withdrawal_lock_args};
+use alloc::vec::Vec;
+use ckb_std::{
+    ckb_constants::Source,
+    ckb_types::prelude::{Entity as CKBEntity, Unpack as CKBUnpack},
+    high_level::{load_cell_lock, QueryIter},
+};
+use gw_common::H256;
+use gw_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{Byte32Reader, RollupConfig},
+    prelude::*,
+};
+
+/// Rollup lock cells of a source
+#[derive(Default)]
+pub struct RollupCells {
+    pub deposits: Vec<DepositRequestCell>,
+    pub withdrawals: Vec<WithdrawalCell>,
+    pub custodians: Vec<CustodianCell>,
+    pub stakes: Vec<StakeCell>,
+    pub challenges: Vec<ChallengeCell>,
+    pub messages: Vec<MessageCell>,
+}
+
+impl RollupCells {
+    pub fn collect(
+        rollup_type_hash: &H256,
+        config: &RollupConfig,
+        source: Source,
+    ) -> Result<Self, Error> {
+        let deposit_script_type_hash = config.deposit_script_type_hash();
+        let withdrawal_script_type_hash = config.withdrawal_script_type_hash();
+        let custodian_script_type_hash = config.custodian_script_type_hash();
+        let stake_script_type_hash = config.stake_script_type_hash();
+        let challenge_script_type_hash = config.challenge_script_type_hash();
+        let message_script_type_hash = config.message_script_type_hash()?;
+
+        let mut cells = RollupCells::default();
+        for (index, lock) in QueryIter::new(load_cell_lock, source).enumerate() {
+            let lock_args: Bytes = lock.args().unpack();
+            let is_rollup_lock = lock_args.len() > 32
+                && &lock_args[..32] == rollup_type_hash.as_slice()
+                && lock.hash_type() == ScriptHashType::Type.into();
+            if !is_rollup_lock {
+                continue;
+            }
+
+            let code_hash = lock.code_hash();
+            let raw_args = &lock_args[32..];
+            if code_hash.as_slice() == deposit_script_type_hash.as_slice() {
+                let args = parse_lock_args(raw_args)?;
+                let cell = build_deposit_cell(config, index, source, args)?;
+                cells.deposits.push(cell);
+            } else if code_hash.as_slice() == withdrawal_script_type_hash.as_slice() {
+                let (args, extra_finality_blocks) = parse_withdrawal_lock_args(raw_args)?;
+                let cell =
+                    build_withdrawal_cell(config, index, source, args, extra_finality_blocks)?;
+                cells.withdrawals.push(cell);
+            } else if code_hash.as_slice() == custodian_script_type_hash.as_slice() {
+                let args = parse_lock_args(raw_args)?;
+                let cell = build_custodian_cell(config, index, source, args)?;
+                cells.custodians.push(cell);
+            } else if code_hash.as_slice() == stake_script_type_hash.as_slice() {
+                let args = parse_lock_args(raw_args)?;
+                let cell = build_stake_cell(config, index, source, args)?;
+                cells.stakes.push(cell);
+            } else if code_hash.as_slice() == challenge_script_type_hash.as_slice() {
+                let args = parse_lock_args(raw_args)?;
+                if let Some(cell) = build_challenge_cell(config, index, source, args)? {
+                    cells.challenges.push(cell);
+                }
+            } else if message_script_type_hash.map_or(false, |hash| code_hash.as_slice() == hash) {
+                let args = MessageLockArgs::from_slice(raw_args)?;
+                let cell = build_message_cell(index, source, args)?;
+                cells.messages.push(cell);
+            }
+        }
+        Ok(cells)
+    }
+
+    /// Find block producer's stake cell
+    /// return an error if there are more than 1 stake cell or the owner is unexpected
+    pub fn block_producer_stake_cell(
+        &self,
+        owner_lock_hash: &Byte32Reader,
+    ) -> Result<Option<&StakeCell>, Error> {
+        check_block_producer_stake_cells(&self.stakes, owner_lock_hash)?;
+        Ok(self.stakes.first())
+    }
+
+    /// Find challenge cell
+    /// return an error if there are more than 1 challenge cell
+    pub fn challenge_cell(&self) -> Result<Option<&ChallengeCell>, Error> {
+        check_challenge_cells(&self.challenges)?;
+        Ok(self.challenges.first())
+    }
+}
+
+/// Rollup lock cells of the transaction
+pub struct TxCellsIndex {
+    pub inputs: RollupCells,
+    pub outputs: RollupCells,
+}
+
+impl TxCellsIndex {
+    pub fn build(rollup_type_hash: &H256, config: &RollupConfig) -> Result<Self, Error> {
+        let inputs = RollupCells::collect(rollup_type_hash, config, Source::Input)?;
+        let outputs = RollupCells::collect(rollup_type_hash, config, Source::Output)?;
+        Ok(TxCellsIndex { inputs, outputs })
+    }
+}
@@ -1,10 +1,13 @@
 //! Lock cells
 
-use super::types::{
-    BurnCell, CellValue, ChallengeCell, CustodianCell, DepositRequestCell, StakeCell,
-    WithdrawalCell,
+use super::{
+    token::parse_udt_amount,
+    types::{
+        BurnCell, CellValue, ChallengeCell, CustodianCell, DepositRequestCell, StakeCell,
+        WithdrawalCell,
+    },
 };
-use crate::error::Error;
+use crate::{error::Error, rollup_config::RollupConfigExt, withdrawal::parse_withdrawal_lock_args};
 use alloc::vec::Vec;
 use ckb_std::{
     ckb_constants::Source,
@@ -15,28 +18,58 @@ use ckb_std::{
         load_cell_type_hash, QueryIter,
     },
 };
-use gw_common::{CKB_SUDT_SCRIPT_ARGS, H256};
+use gw_common::{blake2b::new_blake2b, CKB_SUDT_SCRIPT_ARGS, H256};
 use gw_types::{
     bytes::Bytes,
     core::ScriptHashType,
     packed::{
-        Byte32, Byte32Reader, DepositLockArgs, RollupConfig, StakeLockArgs, WithdrawalLockArgs,
-        WithdrawalLockArgsReader,
+        Byte32, Byte32Reader, ChallengeLockArgs, CustodianLockArgs, DepositLockArgs, RollupConfig,
+        StakeLockArgs, WithdrawalLockArgs,
     },
     prelude::*,
 };
 
+/// xUDT args: owner_lock_hash(32 bytes) | flags(u32 LE, optional) | extension args
+const XUDT_FLAGS_OFFSET: usize = 32;
+/// flags of the xUDT extension scripts, the highest 3 bits are the owner mode flags
+const XUDT_EXTENSION_FLAGS_MASK: u32 = 0x1fff_ffff;
+
+/// Token family of a cell's type script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    /// sUDT or xUDT
+    Fungible,
+    /// the cell itself is the token, valued as 1
+    NonFungible,
+}
+
+/// fetch the type hash of a sUDT, xUDT or NFT cell
 fn fetch_sudt_script_hash(
     config: &RollupConfig,
     index: usize,
     source: Source,
-) -> Result<Option<[u8; 32]>, Error> {
+) -> Result<Option<([u8; 32], TokenKind)>, Error> {
     match load_cell_type(index, source)? {
         Some(type_) => {
-            if type_.hash_type() == ScriptHashType::Type.into()
-                && type_.code_hash().as_slice() == config.l1_sudt_script_type_hash().as_slice()
-            {
-                return Ok(load_cell_type_hash(index, source)?);
+            if type_.hash_type() != ScriptHashType::Type.into() {
+                return Err(Error::InvalidSUDTCell);
+            }
+            let load_type_hash = || -> Result<_, Error> {
+                load_cell_type_hash(index, source)?.ok_or(Error::InvalidSUDTCell)
+            };
+            let code_hash = type_.code_hash();
+            if code_hash.as_slice() == config.l1_sudt_script_type_hash().as_slice() {
+                return Ok(Some((load_type_hash()?, TokenKind::Fungible)));
+            }
+            if let Some(xudt_script_type_hash) = config.l1_xudt_script_type_hash()? {
+                if code_hash.as_slice() == xudt_script_type_hash {
+                    let args: Bytes = type_.args().unpack();
+                    check_xudt_args(&args)?;
+                    return Ok(Some((load_type_hash()?, TokenKind::Fungible)));
+                }
+            }
+            if config.is_l1_nft_script_type_hash(code_hash.as_slice())? {
+                return Ok(Some((load_type_hash()?, TokenKind::NonFungible)));
             }
             Err(Error::InvalidSUDTCell)
         }
@@ -44,12 +77,44 @@ fn fetch_sudt_script_hash(
     }
 }
 
-/// used in filter_map
-fn extract_args_from_lock<ArgsType: Entity>(
+/// xUDT with extension scripts is NOT supported.
+///
+/// Extension scripts run on every transfer of the token and can refuse the
+/// transfer of custodian cells, locking the assets under custody, so any of
+/// the extension flags is rejected.
+/// The owner mode flags only change how the owner lock is checked when
+/// minting, the cells are accepted with any of them.
+fn check_xudt_args(args: &[u8]) -> Result<(), Error> {
+    if args.len() < XUDT_FLAGS_OFFSET {
+        debug!("invalid xUDT args len: {}", args.len());
+        return Err(Error::InvalidSUDTCell);
+    }
+    if args.len() == XUDT_FLAGS_OFFSET {
+        return Ok(());
+    }
+    if args.len() < XUDT_FLAGS_OFFSET + 4 {
+        debug!("invalid xUDT args len: {}", args.len());
+        return Err(Error::InvalidSUDTCell);
+    }
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&args[XUDT_FLAGS_OFFSET..XUDT_FLAGS_OFFSET + 4]);
+    let flags = u32::from_le_bytes(buf);
+    if flags & XUDT_EXTENSION_FLAGS_MASK != 0 {
+        debug!(
+            "unsupported xUDT with extension scripts, flags: {:#x}",
+            flags
+        );
+        return Err(Error::InvalidSUDTCell);
+    }
+    Ok(())
+}
+
+/// return the lock args after the rollup type hash, or None if it isn't the rollup lock
+fn extract_raw_args_from_lock(
     lock: &crate::ckb_std::ckb_types::packed::Script,
     rollup_type_hash: &H256,
     lock_script_type_hash: &Byte32,
-) -> Option<Result<ArgsType, Error>> {
+) -> Option<Bytes> {
     let lock_args: Bytes = lock.args().unpack();
     let is_lock = lock_args.len() > 32
         && &lock_args[..32] == rollup_type_hash.as_slice()
@@ -60,13 +125,72 @@ fn extract_args_from_lock<ArgsType: Entity>(
     if !is_lock {
         return None;
     }
+    Some(lock_args.slice(32..))
+}
 
+/// used in filter_map
+fn extract_args_from_lock<ArgsType: LockArgs>(
+    lock: &crate::ckb_std::ckb_types::packed::Script,
+    rollup_type_hash: &H256,
+    lock_script_type_hash: &Byte32,
+) -> Option<Result<ArgsType, Error>> {
+    let raw_args = extract_raw_args_from_lock(lock, rollup_type_hash, lock_script_type_hash)?;
     // parse the remaining lock_args
-    let raw_args = lock_args[32..].to_vec();
-    Some(ArgsType::from_slice(&raw_args).map_err(|_err| Error::Encoding))
+    Some(parse_lock_args(&raw_args))
+}
+
+/// Args of rollup locks
+pub(crate) trait LockArgs: Entity {
+    /// Parse as a compatible molecule table to accept extra fields
+    const COMPATIBLE: bool = false;
+}
+
+impl LockArgs for StakeLockArgs {}
+impl LockArgs for ChallengeLockArgs {}
+
+/// see `deposit::DepositLockArgsExt`
+impl LockArgs for DepositLockArgs {
+    const COMPATIBLE: bool = true;
+}
+
+/// the custodian lock args contain the deposit lock args
+impl LockArgs for CustodianLockArgs {
+    const COMPATIBLE: bool = true;
+}
+
+pub(crate) fn parse_lock_args<ArgsType: LockArgs>(raw_args: &[u8]) -> Result<ArgsType, Error> {
+    let args = if ArgsType::COMPATIBLE {
+        ArgsType::from_compatible_slice(raw_args)
+    } else {
+        ArgsType::from_slice(raw_args)
+    };
+    args.map_err(|_err| Error::Encoding)
+}
+
+/// collect cells of a rollup lock, `build` returns None to skip a cell
+fn collect_lock_cells<ArgsType, Cell, F>(
+    rollup_type_hash: &H256,
+    lock_script_type_hash: &Byte32,
+    source: Source,
+    build: F,
+) -> Result<Vec<Cell>, Error>
+where
+    ArgsType: LockArgs,
+    F: Fn(usize, ArgsType) -> Result<Option<Cell>, Error>,
+{
+    QueryIter::new(load_cell_lock, source)
+        .enumerate()
+        .filter_map(|(index, lock)| {
+            match extract_args_from_lock(&lock, rollup_type_hash, lock_script_type_hash)? {
+                Ok(args) => build(index, args).transpose(),
+                Err(err) => Some(Err(err)),
+            }
+        })
+        .collect::<Result<_, Error>>()
 }
 
-/// fetch capacity and SUDT value of a cell
+/// fetch capacity and SUDT value of a cell,
+/// xUDT is valued as SUDT, and a NFT cell is valued as 1 token of its type hash
 pub fn fetch_capacity_and_sudt_value(
     config: &RollupConfig,
     index: usize,
@@ -74,11 +198,11 @@ pub fn fetch_capacity_and_sudt_value(
 ) -> Result<CellValue, Error> {
     let capacity = load_cell_capacity(index, source)?;
     let value = match fetch_sudt_script_hash(config, index, source)? {
-        Some(sudt_script_hash) => {
-            let data = load_cell_data(index, source)?;
-            let mut buf = [0u8; 16];
-            buf.copy_from_slice(&data[..16]);
-            let amount = u128::from_le_bytes(buf);
+        Some((sudt_script_hash, kind)) => {
+            let amount = match kind {
+                TokenKind::Fungible => parse_udt_amount(&load_cell_data(index, source)?)?,
+                TokenKind::NonFungible => 1,
+            };
             CellValue {
                 sudt_script_hash: sudt_script_hash.into(),
                 amount,
@@ -94,61 +218,120 @@ pub fn fetch_capacity_and_sudt_value(
     Ok(value)
 }
 
-pub fn collect_stake_cells(
-    rollup_type_hash: &H256,
+/// check the cell is a NFT cell
+pub fn is_nft_cell(config: &RollupConfig, index: usize, source: Source) -> Result<bool, Error> {
+    let kind = fetch_sudt_script_hash(config, index, source)?.map(|(_type_hash, kind)| kind);
+    Ok(kind == Some(TokenKind::NonFungible))
+}
+
+/// fetch (type hash, data hash) of a NFT cell, return None if it isn't a NFT cell
+pub fn fetch_nft_type_and_data_hash(
     config: &RollupConfig,
+    index: usize,
     source: Source,
-) -> Result<Vec<StakeCell>, Error> {
-    let iter = QueryIter::new(load_cell_lock, source)
-        .enumerate()
-        .filter_map(|(index, lock)| -> Option<Result<StakeCell, _>> {
-            let args = match extract_args_from_lock::<StakeLockArgs>(
-                &lock,
-                rollup_type_hash,
-                &config.stake_script_type_hash(),
-            ) {
-                Some(Ok(args)) => args,
-                Some(Err(err)) => return Some(Err(err)),
-                None => return None,
-            };
-            let value = match fetch_capacity_and_sudt_value(config, index, source) {
-                Ok(value) => value,
-                Err(err) => return Some(Err(err)),
-            };
-            // we only accept CKB as staking assets for now
-            if value.sudt_script_hash != CKB_SUDT_SCRIPT_ARGS.into() || value.amount != 0 {
-                debug!("found a stake cell with simple UDT");
-                return Some(Err(Error::InvalidStakeCell));
-            }
-            let cell = StakeCell {
-                index,
-                args,
-                capacity: value.capacity,
-            };
-            Some(Ok(cell))
-        });
-    // reject if found multiple stake cells
-    let cells = iter.collect::<Result<Vec<_>, Error>>()?;
-    Ok(cells)
+) -> Result<Option<(H256, H256)>, Error> {
+    match fetch_sudt_script_hash(config, index, source)? {
+        Some((type_hash, TokenKind::NonFungible)) => {
+            // NOTE: load_cell_data_hash from inputs throw ItemMissing error,
+            // hash the data directly
+            let data = load_cell_data(index, source)?;
+            let mut data_hash = [0u8; 32];
+            let mut hasher = new_blake2b();
+            hasher.update(&data);
+            hasher.finalize(&mut data_hash);
+            Ok(Some((type_hash.into(), data_hash.into())))
+        }
+        _ => Ok(None),
+    }
 }
 
-/// Find block producer's stake cell
-/// this function return Option<StakeCell> if we have 1 or zero stake cell,
-/// otherwise return an error.
-pub fn find_block_producer_stake_cell(
-    rollup_type_hash: &H256,
+pub(crate) fn build_stake_cell(
+    config: &RollupConfig,
+    index: usize,
+    source: Source,
+    args: StakeLockArgs,
+) -> Result<StakeCell, Error> {
+    let value = fetch_capacity_and_sudt_value(config, index, source)?;
+    // we only accept CKB as staking assets for now
+    if value.sudt_script_hash != CKB_SUDT_SCRIPT_ARGS.into() || value.amount != 0 {
+        debug!("found a stake cell with simple UDT");
+        return Err(Error::InvalidStakeCell);
+    }
+    Ok(StakeCell {
+        index,
+        args,
+        capacity: value.capacity,
+    })
+}
+
+/// return None if the challenge cell contains simple UDT
+pub(crate) fn build_challenge_cell(
+    config: &RollupConfig,
+    index: usize,
+    source: Source,
+    args: ChallengeLockArgs,
+) -> Result<Option<ChallengeCell>, Error> {
+    let value = fetch_capacity_and_sudt_value(config, index, source)?;
+    if value.sudt_script_hash != CKB_SUDT_SCRIPT_ARGS.into() || value.amount != 0 {
+        debug!("found a challenge cell with simple UDT");
+        return Ok(None);
+    }
+    Ok(Some(ChallengeCell { index, args, value }))
+}
+
+pub(crate) fn build_withdrawal_cell(
+    config: &RollupConfig,
+    index: usize,
+    source: Source,
+    args: WithdrawalLockArgs,
+    extra_finality_blocks: u64,
+) -> Result<WithdrawalCell, Error> {
+    let value = fetch_capacity_and_sudt_value(config, index, source)?;
+    Ok(WithdrawalCell {
+        index,
+        args,
+        extra_finality_blocks,
+        value,
+    })
+}
+
+pub(crate) fn build_custodian_cell(
     config: &RollupConfig,
+    index: usize,
     source: Source,
+    args: CustodianLockArgs,
+) -> Result<CustodianCell, Error> {
+    let value = fetch_capacity_and_sudt_value(config, index, source)?;
+    Ok(CustodianCell { index, args, value })
+}
+
+pub(crate) fn build_deposit_cell(
+    config: &RollupConfig,
+    index: usize,
+    source: Source,
+    args: DepositLockArgs,
+) -> Result<DepositRequestCell, Error> {
+    let value = fetch_capacity_and_sudt_value(config, index, source)?;
+    let account_script = args.layer2_lock();
+    let account_script_hash = account_script.hash().into();
+    Ok(DepositRequestCell {
+        index,
+        args,
+        value,
+        account_script,
+        account_script_hash,
+    })
+}
+
+/// Check block producer's stake cells,
+/// expect no more than 1 stake cell which owned by `owner_lock_hash`
+pub(crate) fn check_block_producer_stake_cells(
+    cells: &[StakeCell],
     owner_lock_hash: &Byte32Reader,
-) -> Result<Option<StakeCell>, Error> {
-    let mut cells = collect_stake_cells(rollup_type_hash, config, source)?;
+) -> Result<(), Error> {
     // return an error if more than one stake cell returned
     if cells.len() > 1 {
-        debug!(
-            "expected no more than 1 stake cell from {:?}, found {}",
-            source,
-            cells.len()
-        );
+        debug!("expected no more than 1 stake cell, found {}", cells.len());
         return Err(Error::InvalidStakeCell);
     }
     if cells
@@ -158,6 +341,41 @@ pub fn find_block_producer_stake_cell(
         debug!("found stake cell with unexpected owner_lock_hash");
         return Err(Error::InvalidStakeCell);
     }
+    Ok(())
+}
+
+/// Check there is no more than 1 challenge cell
+pub(crate) fn check_challenge_cells(cells: &[ChallengeCell]) -> Result<(), Error> {
+    if cells.len() > 1 {
+        return Err(Error::InvalidChallengeCell);
+    }
+    Ok(())
+}
+
+pub fn collect_stake_cells(
+    rollup_type_hash: &H256,
+    config: &RollupConfig,
+    source: Source,
+) -> Result<Vec<StakeCell>, Error> {
+    collect_lock_cells(
+        rollup_type_hash,
+        &config.stake_script_type_hash(),
+        source,
+        |index, args| build_stake_cell(config, index, source, args).map(Some),
+    )
+}
+
+/// Find block producer's stake cell
+/// this function return Option<StakeCell> if we have 1 or zero stake cell,
+/// otherwise return an error.
+pub fn find_block_producer_stake_cell(
+    rollup_type_hash: &H256,
+    config: &RollupConfig,
+    source: Source,
+    owner_lock_hash: &Byte32Reader,
+) -> Result<Option<StakeCell>, Error> {
+    let mut cells = collect_stake_cells(rollup_type_hash, config, source)?;
+    check_block_producer_stake_cells(&cells, owner_lock_hash)?;
     Ok(cells.pop())
 }
 
@@ -166,37 +384,14 @@ pub fn find_challenge_cell(
     config: &RollupConfig,
     source: Source,
 ) -> Result<Option<ChallengeCell>, Error> {
-    let iter = QueryIter::new(load_cell_lock, source)
-        .enumerate()
-        .filter_map(|(index, lock)| {
-            let args = match extract_args_from_lock(
-                &lock,
-                rollup_type_hash,
-                &config.challenge_script_type_hash(),
-            ) {
-                Some(Ok(args)) => args,
-                Some(Err(err)) => return Some(Err(err)),
-                None => return None,
-            };
-            let value = match fetch_capacity_and_sudt_value(config, index, source) {
-                Ok(value) => value,
-                Err(err) => {
-                    return Some(Err(err));
-                }
-            };
-            if value.sudt_script_hash != CKB_SUDT_SCRIPT_ARGS.into() || value.amount != 0 {
-                debug!("found a challenge cell with simple UDT");
-                return None;
-            }
-            let cell = ChallengeCell { index, args, value };
-            Some(Ok(cell))
-        })
-        .take(2);
-    // reject if found multiple stake cells
-    let mut cells = iter.collect::<Result<Vec<_>, Error>>()?;
-    if cells.len() > 1 {
-        return Err(Error::InvalidChallengeCell);
-    }
+    let mut cells = collect_lock_cells(
+        rollup_type_hash,
+        &config.challenge_script_type_hash(),
+        source,
+        |index, args| build_challenge_cell(config, index, source, args),
+    )?;
+    // reject if found multiple challenge cells
+    check_challenge_cells(&cells)?;
     Ok(cells.pop())
 }
 
@@ -205,31 +400,19 @@ pub fn collect_withdrawal_locks(
     config: &RollupConfig,
     source: Source,
 ) -> Result<Vec<WithdrawalCell>, Error> {
+    // the withdrawal lock args may carry the extra finality blocks, see `withdrawal`
+    let withdrawal_script_type_hash = config.withdrawal_script_type_hash();
     QueryIter::new(load_cell_lock, source)
         .enumerate()
         .filter_map(|(index, lock)| {
-            let lock_args: Bytes = lock.args().unpack();
-            let is_withdrawal_lock = lock_args.len() > 32
-                && &lock_args[..32] == rollup_type_hash.as_slice()
-                && lock.code_hash().as_slice() == config.withdrawal_script_type_hash().as_slice()
-                && lock.hash_type() == ScriptHashType::Type.into();
-            if !is_withdrawal_lock {
-                return None;
-            }
-            let raw_args = lock_args[32..].to_vec();
-            let args = match WithdrawalLockArgsReader::verify(&raw_args, false) {
-                Ok(_) => WithdrawalLockArgs::new_unchecked(raw_args.into()),
-                Err(_) => {
-                    return Some(Err(Error::Encoding));
-                }
-            };
-            let value = match fetch_capacity_and_sudt_value(config, index, source) {
-                Ok(value) => value,
-                Err(err) => return Some(Err(err)),
-            };
-            Some(Ok(WithdrawalCell { index, args, value }))
+            let raw_args =
+                extract_raw_args_from_lock(&lock, rollup_type_hash, &withdrawal_script_type_hash)?;
+            let cell = parse_withdrawal_lock_args(&raw_args).and_then(|(args, extra)| {
+                build_withdrawal_cell(config, index, source, args, extra)
+            });
+            Some(cell)
         })
-        .collect::<Result<_, Error>>()
+        .collect()
 }
 
 pub fn collect_custodian_locks(
@@ -237,26 +420,12 @@ pub fn collect_custodian_locks(
     config: &RollupConfig,
     source: Source,
 ) -> Result<Vec<CustodianCell>, Error> {
-    QueryIter::new(load_cell_lock, source)
-        .enumerate()
-        .filter_map(|(index, lock)| {
-            let args = match extract_args_from_lock(
-                &lock,
-                rollup_type_hash,
-                &config.custodian_script_type_hash(),
-            ) {
-                Some(Ok(args)) => args,
-                Some(Err(err)) => return Some(Err(err)),
-                None => return None,
-            };
-            let value = match fetch_capacity_and_sudt_value(config, index, source) {
-                Ok(value) => value,
-                Err(err) => return Some(Err(err)),
-            };
-            let cell = CustodianCell { index, args, value };
-            Some(Ok(cell))
-        })
-        .collect::<Result<_, Error>>()
+    collect_lock_cells(
+        rollup_type_hash,
+        &config.custodian_script_type_hash(),
+        source,
+        |index, args| build_custodian_cell(config, index, source, args).map(Some),
+    )
 }
 
 pub fn collect_deposit_locks(
@@ -264,34 +433,12 @@ pub fn collect_deposit_locks(
     config: &RollupConfig,
     source: Source,
 ) -> Result<Vec<DepositRequestCell>, Error> {
-    QueryIter::new(load_cell_lock, source)
-        .enumerate()
-        .filter_map(|(index, lock)| {
-            let args: DepositLockArgs = match extract_args_from_lock(
-                &lock,
-                rollup_type_hash,
-                &config.deposit_script_type_hash(),
-            ) {
-                Some(Ok(args)) => args,
-                Some(Err(err)) => return Some(Err(err)),
-                None => return None,
-            };
-            let value = match fetch_capacity_and_sudt_value(config, index, source) {
-                Ok(value) => value,
-                Err(err) => return Some(Err(err)),
-            };
-            let account_script = args.layer2_lock();
-            let account_script_hash = account_script.hash().into();
-            let cell = DepositRequestCell {
-                index,
-                args,
-                value,
-                account_script,
-                account_script_hash,
-            };
-            Some(Ok(cell))
-        })
-        .collect::<Result<_, Error>>()
+    collect_lock_cells(
+        rollup_type_hash,
+        &config.deposit_script_type_hash(),
+        source,
+        |index, args| build_deposit_cell(config, index, source, args).map(Some),
+    )
 }
 
 pub fn collect_burn_cells(config: &RollupConfig, source: Source) -> Result<Vec<BurnCell>, Error> {
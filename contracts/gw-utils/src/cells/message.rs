@@ -0,0 +1,161 @@
+//! L1 -> L2 message cells
+//!
+//! A message cell carries an arbitrary payload to a layer2 contract account,
+//! the rollup consumes message cells in a block, commits them into
+//! the message root of the block and delivers them to the receivers.
+//!
+//! The block moves every consumed message to an output message cell
+//! marked with the block, which holds the capacity until the block is finalized,
+//! then the owner can reclaim the capacity.
+//! If the block is reverted, the marked message is restored as a pending message.
+//!
+//! lock args: rollup_type_hash(32 bytes) | MessageLockArgs
+//! data: payload
+//!
+//! A layer2 receiver reads the delivered messages from its own account state,
+//! see `MESSAGE_COUNT_KEY` and `build_message_key`.
+
+use crate::error::Error;
+use alloc::vec::Vec;
+use ckb_std::{
+    ckb_constants::Source,
+    debug,
+    high_level::{load_cell_capacity, load_cell_data, load_cell_type_hash},
+};
+use gw_common::{
+    blake2b::new_blake2b,
+    merkle_utils::{calculate_ckb_merkle_root, ckb_merkle_leaf_hash},
+    H256,
+};
+
+/// Number of the messages delivered to a layer2 account,
+/// u64 LE in the value under the receiver account
+pub const MESSAGE_COUNT_KEY: [u8; 32] = [0xfe; 32];
+
+/// Key of the n-th message delivered to a layer2 account,
+/// the value is the message hash, see `MessageCell::hash`
+pub fn build_message_key(n: u64) -> H256 {
+    let mut key = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(b"message");
+    hasher.update(&n.to_le_bytes());
+    hasher.finalize(&mut key);
+    key.into()
+}
+
+/// The block which delivered a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeliveredBlock {
+    pub hash: H256,
+    pub number: u64,
+}
+
+/// owner_lock_hash(32 bytes) | to_id(u32 LE) | cancel_timeout(u64 LE, since)
+/// [| delivered_block_hash(32 bytes) | delivered_block_number(u64 LE)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageLockArgs {
+    /// owner of the message cell, who can cancel the message after the timeout
+    pub owner_lock_hash: [u8; 32],
+    /// receiver layer2 contract account
+    pub to_id: u32,
+    pub cancel_timeout: u64,
+    /// None if the message is pending
+    pub delivered_block: Option<DeliveredBlock>,
+}
+
+impl MessageLockArgs {
+    pub const SIZE: usize = 32 + 4 + 8;
+    pub const DELIVERED_SIZE: usize = Self::SIZE + 32 + 8;
+
+    pub fn from_slice(args: &[u8]) -> Result<Self, Error> {
+        if args.len() != Self::SIZE && args.len() != Self::DELIVERED_SIZE {
+            debug!("invalid message lock args len: {}", args.len());
+            return Err(Error::InvalidArgs);
+        }
+        let mut owner_lock_hash = [0u8; 32];
+        owner_lock_hash.copy_from_slice(&args[..32]);
+        let mut to_id = [0u8; 4];
+        to_id.copy_from_slice(&args[32..36]);
+        let mut cancel_timeout = [0u8; 8];
+        cancel_timeout.copy_from_slice(&args[36..44]);
+        let delivered_block = if args.len() == Self::DELIVERED_SIZE {
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&args[44..76]);
+            let mut number = [0u8; 8];
+            number.copy_from_slice(&args[76..84]);
+            Some(DeliveredBlock {
+                hash: hash.into(),
+                number: u64::from_le_bytes(number),
+            })
+        } else {
+            None
+        };
+        Ok(MessageLockArgs {
+            owner_lock_hash,
+            to_id: u32::from_le_bytes(to_id),
+            cancel_timeout: u64::from_le_bytes(cancel_timeout),
+            delivered_block,
+        })
+    }
+}
+
+pub struct MessageCell {
+    pub index: usize,
+    pub args: MessageLockArgs,
+    pub capacity: u64,
+    pub payload_hash: H256,
+}
+
+impl MessageCell {
+    /// hash(owner_lock_hash | to_id | payload_hash)
+    pub fn hash(&self) -> H256 {
+        let mut hash = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&self.args.owner_lock_hash);
+        hasher.update(&self.args.to_id.to_le_bytes());
+        hasher.update(self.payload_hash.as_slice());
+        hasher.finalize(&mut hash);
+        hash.into()
+    }
+
+    /// The message and its capacity, the same before and after the delivery
+    pub fn content(&self) -> (H256, u64, u64) {
+        (self.hash(), self.args.cancel_timeout, self.capacity)
+    }
+}
+
+/// message cells only carry CKB, the payload is the cell data
+pub(crate) fn build_message_cell(
+    index: usize,
+    source: Source,
+    args: MessageLockArgs,
+) -> Result<MessageCell, Error> {
+    if load_cell_type_hash(index, source)?.is_some() {
+        debug!("found a message cell with type script");
+        return Err(Error::InvalidMessageCell);
+    }
+    let capacity = load_cell_capacity(index, source)?;
+    let payload = load_cell_data(index, source)?;
+    let mut payload_hash = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(&payload);
+    hasher.finalize(&mut payload_hash);
+    Ok(MessageCell {
+        index,
+        args,
+        capacity,
+        payload_hash: payload_hash.into(),
+    })
+}
+
+/// Merkle root of messages, in the order of the cells
+pub fn calculate_message_root<'a, I: IntoIterator<Item = &'a MessageCell>>(
+    cells: I,
+) -> Result<H256, Error> {
+    let leaves: Vec<H256> = cells
+        .into_iter()
+        .enumerate()
+        .map(|(idx, cell)| ckb_merkle_leaf_hash(idx as u32, &cell.hash()))
+        .collect();
+    Ok(calculate_ckb_merkle_root(leaves)?)
+}
@@ -1,4 +1,6 @@
+pub mod index;
 pub mod lock_cells;
+pub mod message;
 pub mod rollup;
 pub mod token;
 pub mod types;
@@ -1,21 +1,21 @@
+use core::convert::TryFrom;
+
 use ckb_std::{
     ckb_constants::Source,
     debug,
     high_level::{load_cell_data, load_cell_data_hash, load_cell_type_hash, QueryIter},
-    syscalls::{load_witness, SysError},
+    syscalls::SysError,
 };
 use gw_types::{
+    bytes::Bytes,
     packed::{
-        GlobalState, GlobalStateReader, GlobalStateV0, GlobalStateV0Reader, RollupActionReader,
-        RollupConfig, RollupConfigReader, WitnessArgsReader,
+        Byte32Vec, Byte32VecReader, GlobalState, GlobalStateReader, GlobalStateV0,
+        GlobalStateV0Reader, RollupAction, RollupActionReader, RollupConfig, RollupConfigReader,
     },
     prelude::*,
 };
 
-use crate::error::Error;
-
-/// 524_288 we choose this value because it is smaller than the MAX_BLOCK_BYTES which is 597K
-pub const MAX_ROLLUP_WITNESS_SIZE: usize = 1 << 19;
+use crate::{error::Error, pause::split_rollup_cell_data, witness::WitnessCursor};
 
 pub fn search_rollup_cell(rollup_type_hash: &[u8; 32], source: Source) -> Option<usize> {
     QueryIter::new(load_cell_type_hash, source)
@@ -27,10 +27,11 @@ fn search_rollup_config_cell(rollup_config_hash: &[u8; 32]) -> Option<usize> {
         .position(|data_hash| data_hash.as_ref() == rollup_config_hash)
 }
 
+/// Load rollup config, the config may contains extra fields, see `rollup_config::RollupConfigExt`
 pub fn load_rollup_config(rollup_config_hash: &[u8; 32]) -> Result<RollupConfig, Error> {
     let index = search_rollup_config_cell(rollup_config_hash).ok_or(Error::RollupConfigNotFound)?;
     let data = load_cell_data(index, Source::CellDep)?;
-    match RollupConfigReader::verify(&data, false) {
+    match RollupConfigReader::verify(&data, true) {
         Ok(_) => Ok(RollupConfig::new_unchecked(data.into())),
         Err(_) => Err(Error::Encoding),
     }
@@ -40,6 +41,15 @@ pub fn search_rollup_state(
     rollup_type_hash: &[u8; 32],
     source: Source,
 ) -> Result<Option<GlobalState>, SysError> {
+    let rollup_state = search_rollup_state_with_pause(rollup_type_hash, source)?;
+    Ok(rollup_state.map(|(global_state, _paused_at)| global_state))
+}
+
+/// Search the rollup state and the paused_at of the rollup cell, see `pause`
+pub fn search_rollup_state_with_pause(
+    rollup_type_hash: &[u8; 32],
+    source: Source,
+) -> Result<Option<(GlobalState, Option<u64>)>, SysError> {
     let index = match QueryIter::new(load_cell_type_hash, source)
         .position(|type_hash| type_hash.as_ref() == Some(rollup_type_hash))
     {
@@ -47,35 +57,124 @@ pub fn search_rollup_state(
         None => return Ok(None),
     };
     let data = load_cell_data(index, source)?;
-    match GlobalStateReader::verify(&data, false) {
-        Ok(_) => Ok(Some(GlobalState::new_unchecked(data.into()))),
-        Err(_) if GlobalStateV0Reader::verify(&data, false).is_ok() => {
-            let global_state_v0 = GlobalStateV0::new_unchecked(data.into());
-            Ok(Some(GlobalState::from(global_state_v0)))
+    parse_rollup_cell_data(&data).map(Some)
+}
+
+/// Parse the rollup cell data as the global state and the paused_at, see `pause`
+pub fn parse_rollup_cell_data(data: &[u8]) -> Result<(GlobalState, Option<u64>), SysError> {
+    let (data, paused_at) = split_rollup_cell_data(data);
+    match GlobalStateReader::verify(data, false) {
+        Ok(_) => Ok((
+            GlobalState::new_unchecked(Bytes::copy_from_slice(data)),
+            paused_at,
+        )),
+        Err(_) if paused_at.is_none() && GlobalStateV0Reader::verify(data, false).is_ok() => {
+            let global_state_v0 = GlobalStateV0::new_unchecked(Bytes::copy_from_slice(data));
+            Ok((GlobalState::from(global_state_v0), None))
         }
         Err(_) => Err(SysError::Encoding),
     }
 }
 
-pub fn parse_rollup_action(
-    buf: &mut [u8; MAX_ROLLUP_WITNESS_SIZE],
-    index: usize,
-    source: Source,
-) -> Result<RollupActionReader, Error> {
-    let loaded_len = load_witness(buf, 0, index, source)?;
-    debug!("load rollup witness, loaded len: {}", loaded_len);
+/// Type of the rollup action, the item id of `RollupAction` union
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollupActionType {
+    SubmitBlock = 0,
+    EnterChallenge = 1,
+    CancelChallenge = 2,
+    Revert = 3,
+    /// not in the upstream union, the item is empty, see `pause`
+    Pause = 4,
+}
 
-    let witness_args = WitnessArgsReader::from_slice(&buf[..loaded_len]).map_err(|_err| {
-        debug!("witness is not a valid WitnessArgsReader");
-        Error::Encoding
-    })?;
-    let output = witness_args.output_type().to_opt().ok_or_else(|| {
+impl TryFrom<u32> for RollupActionType {
+    type Error = u32;
+
+    fn try_from(v: u32) -> Result<RollupActionType, u32> {
+        match v {
+            0 => Ok(RollupActionType::SubmitBlock),
+            1 => Ok(RollupActionType::EnterChallenge),
+            2 => Ok(RollupActionType::CancelChallenge),
+            3 => Ok(RollupActionType::Revert),
+            4 => Ok(RollupActionType::Pause),
+            _ => Err(v),
+        }
+    }
+}
+
+/// Rollup action in the `WitnessArgs#output_type` of a witness,
+/// the action is loaded on demand.
+pub struct RollupActionWitness {
+    action_type: RollupActionType,
+    action: WitnessCursor,
+    item: WitnessCursor,
+}
+
+impl RollupActionWitness {
+    pub fn action_type(&self) -> RollupActionType {
+        self.action_type
+    }
+
+    /// Load and verify the whole rollup action,
+    /// the action is verified as compatible to accept the extra fields of `RawL2Block`
+    pub fn load_action(&self) -> Result<RollupAction, Error> {
+        let data = self.action.load()?;
+        match RollupActionReader::verify(&data, true) {
+            Ok(_) => Ok(RollupAction::new_unchecked(data.into())),
+            Err(_) => {
+                debug!("output is not a valid RollupActionReader");
+                Err(Error::Encoding)
+            }
+        }
+    }
+
+    /// Load `RollupSubmitBlock#reverted_block_hashes` without loading the block,
+    /// return None if the action isn't a submit block action
+    pub fn load_reverted_block_hashes(&self) -> Result<Option<Byte32Vec>, Error> {
+        if self.action_type != RollupActionType::SubmitBlock {
+            return Ok(None);
+        }
+        // RollupSubmitBlock { block, reverted_block_hashes, reverted_block_proof }
+        let data = self.item.table_field(1)?.load()?;
+        match Byte32VecReader::verify(&data, false) {
+            Ok(_) => Ok(Some(Byte32Vec::new_unchecked(data.into()))),
+            Err(_) => {
+                debug!("reverted_block_hashes is not a valid Byte32VecReader");
+                Err(Error::Encoding)
+            }
+        }
+    }
+}
+
+pub fn parse_rollup_action(index: usize, source: Source) -> Result<RollupActionWitness, Error> {
+    let witness = WitnessCursor::new(index, source)?;
+    debug!("load rollup witness, witness len: {}", witness.size());
+
+    let action = witness.witness_args_output_type()?.ok_or_else(|| {
         debug!("WitnessArgs#output_type is none");
         Error::Encoding
     })?;
-    let action = RollupActionReader::from_slice(output.raw_data()).map_err(|_err| {
-        debug!("output is not a valid RollupActionReader");
+    let (item_id, item) = action.union_item()?;
+    let action_type = RollupActionType::try_from(item_id).map_err(|item_id| {
+        debug!("invalid rollup action item id: {}", item_id);
         Error::Encoding
     })?;
-    Ok(action)
+    Ok(RollupActionWitness {
+        action_type,
+        action,
+        item,
+    })
+}
+
+/// Search the rollup action of the transaction,
+/// return None if the rollup cell isn't in the inputs
+pub fn search_rollup_action(
+    rollup_type_hash: &[u8; 32],
+) -> Result<Option<RollupActionWitness>, Error> {
+    if search_rollup_cell(rollup_type_hash, Source::Input).is_none() {
+        return Ok(None);
+    }
+    let index =
+        search_rollup_cell(rollup_type_hash, Source::Output).ok_or(Error::RollupCellNotFound)?;
+    parse_rollup_action(index, Source::Output).map(Some)
 }
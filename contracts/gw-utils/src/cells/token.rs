@@ -1,11 +1,27 @@
 use crate::error::Error;
 use ckb_std::{
     ckb_constants::Source,
+    debug,
     high_level::{
         load_cell_capacity, load_cell_data, load_cell_lock_hash, load_cell_type_hash, QueryIter,
     },
 };
 
+/// Size of the amount at the head of sUDT and xUDT cell data
+pub const UDT_AMOUNT_SIZE: usize = 16;
+
+/// Parse the amount of a sUDT or xUDT cell,
+/// the xUDT extension data after the amount is ignored
+pub fn parse_udt_amount(data: &[u8]) -> Result<u128, Error> {
+    if data.len() < UDT_AMOUNT_SIZE {
+        debug!("invalid UDT cell data len: {}", data.len());
+        return Err(Error::InvalidSUDTCell);
+    }
+    let mut buf = [0u8; UDT_AMOUNT_SIZE];
+    buf.copy_from_slice(&data[..UDT_AMOUNT_SIZE]);
+    Ok(u128::from_le_bytes(buf))
+}
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum TokenType {
     CKB,
@@ -50,9 +66,7 @@ pub fn fetch_token_amount_by_lock_hash(
         let amount = match load_cell_type_hash(i, source)? {
             Some(type_hash) if &TokenType::SUDT(type_hash) == token_type => {
                 let data = load_cell_data(i, source)?;
-                let mut buf = [0u8; 16];
-                buf.copy_from_slice(&data[..16]);
-                u128::from_le_bytes(buf)
+                parse_udt_amount(&data)?
             }
             _ => 0,
         };
@@ -6,7 +6,7 @@ use crate::gw_types::packed::{
     WithdrawalLockArgs,
 };
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
 pub struct CellValue {
     pub sudt_script_hash: H256,
     pub amount: u128,
@@ -17,6 +17,8 @@ pub struct CellValue {
 pub struct WithdrawalCell {
     pub index: usize,
     pub args: WithdrawalLockArgs,
+    /// finality blocks added to the rollup finality, see `withdrawal`
+    pub extra_finality_blocks: u64,
     pub value: CellValue,
 }
 
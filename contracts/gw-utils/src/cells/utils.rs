@@ -3,7 +3,7 @@ use ckb_std::{
     ckb_constants::Source,
     high_level::{load_cell_lock_hash, QueryIter},
 };
-use gw_common::H256;
+use gw_common::{blake2b::new_blake2b, H256};
 use gw_types::{
     bytes::Bytes,
     core::ScriptHashType,
@@ -28,6 +28,36 @@ pub fn search_lock_hash(owner_lock_hash: &[u8; 32], source: Source) -> Option<us
     QueryIter::new(load_cell_lock_hash, source).position(|lock_hash| &lock_hash == owner_lock_hash)
 }
 
+/// Script of the layer2 NFT account of a L1 NFT, one account per NFT type hash
+pub fn build_l2_nft_script(
+    rollup_script_hash: &H256,
+    l2_nft_validator_script_type_hash: &[u8; 32],
+    l1_nft_type_hash: &H256,
+) -> Script {
+    let args = {
+        let mut args = Vec::with_capacity(64);
+        args.extend(rollup_script_hash.as_slice());
+        args.extend(l1_nft_type_hash.as_slice());
+        Bytes::from(args)
+    };
+    Script::new_builder()
+        .args(args.pack())
+        .code_hash(l2_nft_validator_script_type_hash.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .build()
+}
+
+/// Key of the owner of a layer2 NFT account, under the NFT account,
+/// the value is the script hash of the layer2 account holding the NFT,
+/// zero if the NFT isn't on layer2
+pub fn build_nft_owner_key() -> H256 {
+    let mut key = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(b"nft_owner");
+    hasher.finalize(&mut key);
+    key.into()
+}
+
 pub fn build_l2_sudt_script(
     rollup_script_hash: &H256,
     config: &RollupConfig,
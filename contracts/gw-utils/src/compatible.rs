@@ -0,0 +1,42 @@
+//! Compatible molecule tables
+//!
+//! A table verified in compatible mode may have extra fields after the
+//! upstream fields, these helpers read the extra fields by index.
+
+use core::convert::TryInto;
+
+const NUMBER_SIZE: usize = 4;
+
+/// Extra field of a verified molecule table,
+/// return None if the field is missing or empty
+///
+/// `field_count` is the actual field count of the table,
+/// `upstream_field_count` is the field count of the upstream schema.
+pub(crate) fn extra_field(
+    slice: &[u8],
+    field_count: usize,
+    upstream_field_count: usize,
+    index: usize,
+) -> Option<&[u8]> {
+    let field_index = upstream_field_count.checked_add(index)?;
+    if field_index >= field_count {
+        return None;
+    }
+    // the offsets are checked by the compatible verification
+    let read_offset = |i: usize| {
+        let pos = NUMBER_SIZE * (i + 1);
+        u32::from_le_bytes(slice[pos..pos + NUMBER_SIZE].try_into().expect("offset")) as usize
+    };
+    let start = read_offset(field_index);
+    let end = if field_index + 1 < field_count {
+        read_offset(field_index + 1)
+    } else {
+        slice.len()
+    };
+    let field = &slice[start..end];
+    if field.is_empty() {
+        None
+    } else {
+        Some(field)
+    }
+}
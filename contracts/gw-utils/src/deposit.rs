@@ -0,0 +1,152 @@
+//! DepositLockArgs extra fields
+//!
+//! Optional fields appended after the upstream `DepositLockArgs` fields,
+//! the deposit lock args and the custodian lock args are parsed as compatible molecule tables.
+//! A missing or empty extra field means the option is unset.
+//!
+//! extra fields:
+//! 0. call: to_id(u32 LE) | args, a layer2 call sent by the deposit account
+//! 1. cancel_pubkey_hash: 20 bytes, blake160 of the secp256k1 pubkey which can sign to cancel
+//! 2. relayer_fee: u64 LE, CKB paid to the relayer of a signed cancel
+//! 3. fee: ckb(u64 LE) | sudt(u128 LE), paid to the block producer which includes the deposit
+//!
+//! The calls of the consumed deposits are the leading transactions of the block,
+//! in the order of the deposit cells, see `block::RawL2BlockExt::deposit_call_count`.
+//! A call is authorized by the deposit cell instead of a layer2 signature,
+//! so it is only accepted from an account created by the deposit itself,
+//! its execution can be challenged like other layer2 transactions.
+
+use core::convert::TryInto;
+
+use ckb_std::debug;
+use gw_types::{
+    packed::{DepositLockArgs, DepositLockArgsReader, RawL2TransactionReader},
+    prelude::*,
+};
+
+use crate::{compatible, error::Error};
+
+const CALL: usize = 0;
+const CANCEL_PUBKEY_HASH: usize = 1;
+const RELAYER_FEE: usize = 2;
+const FEE: usize = 3;
+
+/// Deposit fee paid to the block producer, deducted from the deposited assets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DepositFee {
+    pub ckb: u64,
+    /// fee in the deposited sUDT, must be zero for CKB deposits
+    pub sudt: u128,
+}
+
+impl DepositFee {
+    pub const SIZE: usize = 8 + 16;
+}
+
+/// A layer2 call of a deposit, sent by the deposit account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepositCall<'a> {
+    /// receiver layer2 contract account
+    pub to_id: u32,
+    pub args: &'a [u8],
+}
+
+impl<'a> DepositCall<'a> {
+    /// Check the layer2 transaction is the call sent by the deposit account `from_id`
+    pub fn is_sent_by(&self, raw_tx: &RawL2TransactionReader, from_id: u32) -> bool {
+        let tx_from_id: u32 = raw_tx.from_id().unpack();
+        let tx_to_id: u32 = raw_tx.to_id().unpack();
+        tx_from_id == from_id && tx_to_id == self.to_id && raw_tx.args().raw_data() == self.args
+    }
+}
+
+pub trait DepositLockArgsExt {
+    /// Extra field by the index after the upstream fields,
+    /// return None if the field is missing or empty
+    fn extra_field(&self, index: usize) -> Option<&[u8]>;
+
+    /// Layer2 call of the deposit, return None if the deposit has no call
+    fn call(&self) -> Result<Option<DepositCall>, Error> {
+        match self.extra_field(CALL) {
+            Some(field) => {
+                if field.len() < 4 {
+                    debug!("invalid deposit call, len: {}", field.len());
+                    return Err(Error::Encoding);
+                }
+                let to_id = u32::from_le_bytes(field[..4].try_into().expect("to_id"));
+                Ok(Some(DepositCall {
+                    to_id,
+                    args: &field[4..],
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Pubkey hash to cancel the deposit by signature,
+    /// return None if the deposit can only be cancelled by the owner cell
+    fn cancel_pubkey_hash(&self) -> Result<Option<[u8; 20]>, Error> {
+        match self.extra_field(CANCEL_PUBKEY_HASH) {
+            Some(field) => {
+                let buf = field.try_into().map_err(|_| {
+                    debug!("invalid cancel pubkey hash, len: {}", field.len());
+                    Error::Encoding
+                })?;
+                Ok(Some(buf))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Max CKB a relayer can take from a signed cancel, 0 by default
+    fn relayer_fee(&self) -> Result<u64, Error> {
+        match self.extra_field(RELAYER_FEE) {
+            Some(field) => {
+                let buf = field.try_into().map_err(|_| {
+                    debug!("invalid relayer fee, len: {}", field.len());
+                    Error::Encoding
+                })?;
+                Ok(u64::from_le_bytes(buf))
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Fee paid to the block producer, zero by default
+    fn fee(&self) -> Result<DepositFee, Error> {
+        match self.extra_field(FEE) {
+            Some(field) => {
+                if field.len() != DepositFee::SIZE {
+                    debug!("invalid deposit fee, len: {}", field.len());
+                    return Err(Error::Encoding);
+                }
+                let ckb = u64::from_le_bytes(field[..8].try_into().expect("ckb fee"));
+                let sudt = u128::from_le_bytes(field[8..].try_into().expect("sudt fee"));
+                Ok(DepositFee { ckb, sudt })
+            }
+            None => Ok(DepositFee::default()),
+        }
+    }
+}
+
+impl DepositLockArgsExt for DepositLockArgs {
+    fn extra_field(&self, index: usize) -> Option<&[u8]> {
+        compatible::extra_field(
+            self.as_slice(),
+            self.field_count(),
+            DepositLockArgs::FIELD_COUNT,
+            index,
+        )
+    }
+}
+
+impl<'r> DepositLockArgsExt for DepositLockArgsReader<'r> {
+    fn extra_field(&self, index: usize) -> Option<&[u8]> {
+        compatible::extra_field(
+            self.as_slice(),
+            self.field_count(),
+            DepositLockArgsReader::FIELD_COUNT,
+            index,
+        )
+    }
+}
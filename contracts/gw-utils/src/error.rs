@@ -51,6 +51,16 @@ pub enum Error {
     UnexpectedTxNonce,
     // raise from signature verification script
     WrongSignature,
+    // raise from recovery account lock
+    RecoveryRegistryNotFound,
+    InvalidRecoveryRegistry,
+    InsufficientGuardians,
+    // raise from state validator
+    ExceededBlockLimit,
+    ExceededDepositCap,
+    InvalidNFTCell,
+    InvalidMessageCell,
+    RollupPaused,
 }
 
 impl From<SysError> for Error {
@@ -0,0 +1,53 @@
+//! Secp256k1 Eth implementation
+//!
+//! Shared by the eth account lock and the recovery account lock,
+//! enabled by the `eth-signature` feature.
+
+use ckb_std::debug;
+use gw_common::H256;
+use secp256k1_utils::recover_uncompressed_key;
+use sha3::{Digest, Keccak256};
+
+use crate::{
+    account_lock::{AccountLock, SIGNATURE_SIZE},
+    error::Error,
+};
+
+pub type EthAddress = [u8; 20];
+
+#[derive(Default)]
+pub struct Secp256k1Eth;
+
+impl AccountLock for Secp256k1Eth {
+    type Address = EthAddress;
+
+    fn prefixed_message(&self, message: &H256) -> H256 {
+        let mut hasher = Keccak256::new();
+        hasher.update("\x19Ethereum Signed Message:\n32");
+        hasher.update(message.as_slice());
+        let buf = hasher.finalize();
+        let mut signing_message = [0u8; 32];
+        signing_message.copy_from_slice(&buf[..]);
+        H256::from(signing_message)
+    }
+
+    fn recover_address(
+        &self,
+        message: &H256,
+        signature: &[u8; SIGNATURE_SIZE],
+    ) -> Result<EthAddress, Error> {
+        let pubkey = recover_uncompressed_key((*message).into(), *signature).map_err(|err| {
+            debug!("failed to recover secp256k1 pubkey, error number: {}", err);
+            Error::WrongSignature
+        })?;
+        let pubkey_hash = {
+            let mut hasher = Keccak256::new();
+            hasher.update(&pubkey[1..]);
+            let buf = hasher.finalize();
+            let mut pubkey_hash = [0u8; 20];
+            pubkey_hash.copy_from_slice(&buf[12..]);
+            pubkey_hash
+        };
+        Ok(pubkey_hash)
+    }
+}
@@ -9,7 +9,19 @@ pub use ckb_std;
 pub use gw_common;
 pub use gw_types;
 
+pub mod account_lock;
+pub mod block;
 pub mod cells;
+mod compatible;
+pub mod deposit;
 pub mod error;
+#[cfg(feature = "eth-signature")]
+pub mod eth_signature;
+pub mod outbound_message;
+pub mod pause;
+pub mod rollup_config;
 pub mod signature;
+pub mod since;
 pub mod type_id;
+pub mod withdrawal;
+pub mod witness;
@@ -0,0 +1,113 @@
+//! L2 -> L1 outbound messages
+//!
+//! A layer2 contract emits the n-th outbound message by writing the message hash
+//! under `build_outbound_message_key(n)` of its own account state.
+//! The messages are committed by the post account merkle root of the `RawL2Block`,
+//! which is checked by the challenges of the block like any other state,
+//! so no extra root is trusted from the block producer.
+//!
+//! After the block is finalized, any L1 script can verify a message by
+//! `verify_outbound_message` with a block merkle proof and a kv state proof.
+
+use alloc::vec;
+use ckb_std::debug;
+use gw_common::{
+    blake2b::new_blake2b,
+    smt::{Blake2bHasher, CompiledMerkleProof},
+    state::{build_account_field_key, build_account_key, GW_ACCOUNT_SCRIPT_HASH_TYPE},
+    H256,
+};
+use gw_types::{
+    packed::{GlobalState, RawL2Block, RawL2BlockReader},
+    prelude::*,
+};
+
+use crate::error::Error;
+
+/// Key of the n-th outbound message of a layer2 account, under the sender account,
+/// the value is the message hash, see `calculate_outbound_message_hash`
+pub fn build_outbound_message_key(n: u64) -> H256 {
+    let mut key = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(b"outbound_message");
+    hasher.update(&n.to_le_bytes());
+    hasher.finalize(&mut key);
+    key.into()
+}
+
+/// Hash of a L2 -> L1 message, hash(payload)
+pub fn calculate_outbound_message_hash(payload: &[u8]) -> H256 {
+    let mut hash = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(payload);
+    hasher.finalize(&mut hash);
+    hash.into()
+}
+
+/// The n-th outbound message of a layer2 account
+pub struct OutboundMessage {
+    pub sender_id: u32,
+    pub sender_script_hash: H256,
+    pub n: u64,
+    pub message_hash: H256,
+}
+
+/// Verify a L2 -> L1 message is emitted before a finalized block
+///
+/// `global_state` is the state of the rollup cell, usually loaded from the cell deps,
+/// `block_proof` proves the block is in the block merkle tree of the global state,
+/// `kv_state_proof` proves the message and the sender script hash are in
+/// the post account state of the block.
+pub fn verify_outbound_message(
+    global_state: &GlobalState,
+    raw_block: &RawL2BlockReader,
+    block_proof: &[u8],
+    message: &OutboundMessage,
+    kv_state_proof: &[u8],
+) -> Result<(), Error> {
+    // check block is finalized
+    let block_number: u64 = raw_block.number().unpack();
+    let last_finalized_block_number: u64 = global_state.last_finalized_block_number().unpack();
+    if block_number > last_finalized_block_number {
+        debug!(
+            "outbound message block {} isn't finalized, last finalized block: {}",
+            block_number, last_finalized_block_number
+        );
+        return Err(Error::InvalidBlock);
+    }
+
+    // verify block merkle proof
+    let block_merkle_proof = CompiledMerkleProof(block_proof.to_vec());
+    let leaves = vec![(
+        RawL2Block::compute_smt_key(block_number).into(),
+        raw_block.hash().into(),
+    )];
+    let valid = block_merkle_proof
+        .verify::<Blake2bHasher>(&global_state.block().merkle_root().unpack(), leaves)?;
+    if !valid {
+        debug!("[verify outbound message] block merkle verify error");
+        return Err(Error::MerkleProof);
+    }
+
+    // verify the message and the sender in the post account state
+    let kv_merkle_proof = CompiledMerkleProof(kv_state_proof.to_vec());
+    let message_key = build_outbound_message_key(message.n);
+    let mut leaves = vec![
+        (
+            build_account_key(message.sender_id, message_key.as_slice()),
+            message.message_hash,
+        ),
+        (
+            build_account_field_key(message.sender_id, GW_ACCOUNT_SCRIPT_HASH_TYPE),
+            message.sender_script_hash,
+        ),
+    ];
+    leaves.sort_unstable_by_key(|(key, _value)| *key);
+    let post_account_root = raw_block.post_account().merkle_root().unpack();
+    let valid = kv_merkle_proof.verify::<Blake2bHasher>(&post_account_root, leaves)?;
+    if !valid {
+        debug!("[verify outbound message] kv state merkle verify error");
+        return Err(Error::MerkleProof);
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Emergency pause
+//!
+//! The guardian named in the rollup config can pause the rollup by the pause action,
+//! while paused, blocks can't contain withdrawals or deposits and withdrawal cells
+//! can't be finalized. L2 transactions are still allowed.
+//!
+//! `GlobalState` is a molecule struct which can't be extended,
+//! the pause is appended after it in the rollup cell data:
+//! GlobalState | paused_at(u64 LE, optional)
+//!
+//! `paused_at` is the timestamp of the rollup input since of the pause action,
+//! the pause expires once a timestamp reaches `paused_at + max_pause_duration`.
+//! The guardian can end an active pause early, the pause action moves `paused_at`
+//! back so the pause expires at the rollup input since timestamp.
+//!
+//! The guardian can't pause again until `pause_cooldown` after the pause expires,
+//! so a pause can't be extended by pausing again. The first block submitted
+//! after the cooldown clears the `paused_at`.
+//!
+//! The timestamp is the rollup input since in the pause action and the block
+//! timestamp in submit_block. The withdrawal lock takes the later one of the tip
+//! block timestamp and its input since, the tip block timestamp is frozen if no
+//! blocks are submitted and is 0 in the v0 global state.
+//!
+//! The block timestamp is only checked against the rollup input since by a v1
+//! global state, submit_block refuses a v0 post global state with a guardian.
+
+use core::convert::TryInto;
+
+use ckb_std::debug;
+use gw_types::packed::{GlobalStateReader, RollupConfig};
+
+use crate::{error::Error, rollup_config::RollupConfigExt};
+
+pub const PAUSED_AT_SIZE: usize = 8;
+
+/// Split the rollup cell data into the global state data and the paused_at,
+/// return None as paused_at if the rollup isn't paused
+pub fn split_rollup_cell_data(data: &[u8]) -> (&[u8], Option<u64>) {
+    if data.len() == GlobalStateReader::TOTAL_SIZE + PAUSED_AT_SIZE {
+        let (global_state, paused_at) = data.split_at(GlobalStateReader::TOTAL_SIZE);
+        let paused_at = u64::from_le_bytes(paused_at.try_into().expect("paused at"));
+        (global_state, Some(paused_at))
+    } else {
+        (data, None)
+    }
+}
+
+/// Check the rollup is paused at the timestamp,
+/// a pause without the max pause duration in the config is ignored
+pub fn is_paused(
+    config: &RollupConfig,
+    paused_at: Option<u64>,
+    timestamp: u64,
+) -> Result<bool, Error> {
+    let paused_at = match paused_at {
+        Some(paused_at) => paused_at,
+        None => return Ok(false),
+    };
+    let max_pause_duration = match config.max_pause_duration()? {
+        Some(duration) => duration,
+        None => return Ok(false),
+    };
+    Ok(timestamp < paused_at.saturating_add(max_pause_duration))
+}
+
+/// Check the pause has expired and the cooldown has passed at the timestamp,
+/// the rollup can be paused again and the paused_at can be cleared
+pub fn is_pause_cleared(
+    config: &RollupConfig,
+    paused_at: u64,
+    timestamp: u64,
+) -> Result<bool, Error> {
+    let max_pause_duration = match config.max_pause_duration()? {
+        Some(duration) => duration,
+        None => return Ok(true),
+    };
+    let pause_cooldown = config.pause_cooldown()?.unwrap_or(0);
+    let cleared_at = paused_at
+        .saturating_add(max_pause_duration)
+        .saturating_add(pause_cooldown);
+    Ok(timestamp >= cleared_at)
+}
+
+/// Return the paused_at after a block of the timestamp,
+/// the paused_at is cleared once the pause is cleared
+pub fn clear_expired_pause(
+    config: &RollupConfig,
+    paused_at: Option<u64>,
+    timestamp: u64,
+) -> Result<Option<u64>, Error> {
+    match paused_at {
+        Some(paused_at) if !is_pause_cleared(config, paused_at, timestamp)? => Ok(Some(paused_at)),
+        _ => Ok(None),
+    }
+}
+
+/// Check the rollup isn't paused at the timestamp
+pub fn check_not_paused(
+    config: &RollupConfig,
+    paused_at: Option<u64>,
+    timestamp: u64,
+) -> Result<(), Error> {
+    if is_paused(config, paused_at, timestamp)? {
+        debug!(
+            "rollup is paused at {:?}, timestamp {}",
+            paused_at, timestamp
+        );
+        return Err(Error::RollupPaused);
+    }
+    Ok(())
+}
@@ -0,0 +1,253 @@
+//! RollupConfig extra fields
+//!
+//! Optional fields appended after the upstream `RollupConfig` fields,
+//! the config cell is parsed as a compatible molecule table.
+//! A missing or empty extra field means the option is unset.
+//!
+//! extra fields:
+//! 0. max_txs_per_block: u32 LE
+//! 1. max_withdrawals_per_block: u32 LE
+//! 2. max_deposits_per_block: u32 LE
+//! 3. max_kv_pairs: u32 LE
+//! 4. min_deposit_capacity: u64 LE
+//! 5. sudt_deposit_caps: [sudt_script_hash(32 bytes) | cap: u128 LE]
+//! 6. l1_sudt_allowlist: [sudt_script_hash(32 bytes)], of sUDT and xUDT only
+//! 7. l1_sudt_blocklist: [token type hash(32 bytes)], of sUDT, xUDT and NFT
+//! 8. l1_xudt_script_type_hash: 32 bytes, xUDT with extension scripts isn't supported
+//! 9. l1_nft_script_type_hashes: [code_hash(32 bytes)]
+//! 10. message_script_type_hash: 32 bytes
+//! 11. min_deposit_cancel_timeout: u64 LE, a relative since
+//! 12. withdrawal_finality_tiers: [sudt_script_hash(32 bytes) | min_capacity: u64 LE |
+//!     min_amount: u128 LE | extra_finality_blocks: u64 LE]
+//! 13. guardian_lock_hash: 32 bytes, the lock which can pause the rollup,
+//!     requires the v1 global state
+//! 14. max_pause_duration: u64 LE, in the unit of the block timestamp
+//! 15. l2_nft_validator_script_type_hash: 32 bytes
+//! 16. pause_cooldown: u64 LE, in the unit of the block timestamp
+
+use core::convert::TryInto;
+
+use ckb_std::debug;
+use gw_common::H256;
+use gw_types::{packed::RollupConfig, prelude::*};
+
+use crate::{cells::types::CellValue, compatible, error::Error};
+
+const MAX_TXS_PER_BLOCK: usize = 0;
+const MAX_WITHDRAWALS_PER_BLOCK: usize = 1;
+const MAX_DEPOSITS_PER_BLOCK: usize = 2;
+const MAX_KV_PAIRS: usize = 3;
+const MIN_DEPOSIT_CAPACITY: usize = 4;
+const SUDT_DEPOSIT_CAPS: usize = 5;
+const L1_SUDT_ALLOWLIST: usize = 6;
+const L1_SUDT_BLOCKLIST: usize = 7;
+const L1_XUDT_SCRIPT_TYPE_HASH: usize = 8;
+const L1_NFT_SCRIPT_TYPE_HASHES: usize = 9;
+const MESSAGE_SCRIPT_TYPE_HASH: usize = 10;
+const MIN_DEPOSIT_CANCEL_TIMEOUT: usize = 11;
+const WITHDRAWAL_FINALITY_TIERS: usize = 12;
+const GUARDIAN_LOCK_HASH: usize = 13;
+const MAX_PAUSE_DURATION: usize = 14;
+const L2_NFT_VALIDATOR_SCRIPT_TYPE_HASH: usize = 15;
+const PAUSE_COOLDOWN: usize = 16;
+
+const SUDT_DEPOSIT_CAP_SIZE: usize = 32 + 16;
+const WITHDRAWAL_FINALITY_TIER_SIZE: usize = 32 + 8 + 16 + 8;
+
+pub trait RollupConfigExt {
+    /// Extra field by the index after the upstream fields,
+    /// return None if the field is missing or empty
+    fn extra_field(&self, index: usize) -> Option<&[u8]>;
+
+    fn max_txs_per_block(&self) -> Result<Option<u32>, Error> {
+        parse_u32(self.extra_field(MAX_TXS_PER_BLOCK))
+    }
+
+    fn max_withdrawals_per_block(&self) -> Result<Option<u32>, Error> {
+        parse_u32(self.extra_field(MAX_WITHDRAWALS_PER_BLOCK))
+    }
+
+    fn max_deposits_per_block(&self) -> Result<Option<u32>, Error> {
+        parse_u32(self.extra_field(MAX_DEPOSITS_PER_BLOCK))
+    }
+
+    fn max_kv_pairs(&self) -> Result<Option<u32>, Error> {
+        parse_u32(self.extra_field(MAX_KV_PAIRS))
+    }
+
+    fn min_deposit_capacity(&self) -> Result<Option<u64>, Error> {
+        parse_u64(self.extra_field(MIN_DEPOSIT_CAPACITY))
+    }
+
+    /// Minimal cancel timeout of deposits, a relative since,
+    /// to prevent deposits from being cancelled before inclusion
+    fn min_deposit_cancel_timeout(&self) -> Result<Option<u64>, Error> {
+        parse_u64(self.extra_field(MIN_DEPOSIT_CANCEL_TIMEOUT))
+    }
+
+    /// Lock hash of the guardian, return None if the rollup can't be paused
+    fn guardian_lock_hash(&self) -> Result<Option<[u8; 32]>, Error> {
+        parse_byte32(self.extra_field(GUARDIAN_LOCK_HASH))
+    }
+
+    /// Max duration of a pause, see `pause`
+    fn max_pause_duration(&self) -> Result<Option<u64>, Error> {
+        parse_u64(self.extra_field(MAX_PAUSE_DURATION))
+    }
+
+    /// Min duration between the expiry of a pause and the next pause, see `pause`
+    fn pause_cooldown(&self) -> Result<Option<u64>, Error> {
+        parse_u64(self.extra_field(PAUSE_COOLDOWN))
+    }
+
+    /// Cap of the custodian balance of a L1 sUDT,
+    /// return None if the sUDT is uncapped
+    fn sudt_deposit_cap(&self, sudt_script_hash: &H256) -> Result<Option<u128>, Error> {
+        let field = match self.extra_field(SUDT_DEPOSIT_CAPS) {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+        if field.len() % SUDT_DEPOSIT_CAP_SIZE != 0 {
+            debug!("invalid sudt deposit caps, len: {}", field.len());
+            return Err(Error::Encoding);
+        }
+        let cap = field
+            .chunks_exact(SUDT_DEPOSIT_CAP_SIZE)
+            .find(|item| &item[..32] == sudt_script_hash.as_slice())
+            .map(|item| u128::from_le_bytes(item[32..].try_into().expect("cap")));
+        Ok(cap)
+    }
+
+    /// Extra finality blocks of a withdrawal, added to the `finality_blocks`,
+    /// 0 if the withdrawal doesn't reach any tier.
+    /// The value is the total withdrawn by an account in a block.
+    ///
+    /// a tier is reached if the withdrawal capacity >= min_capacity,
+    /// or the withdrawal sUDT is the tier's sUDT and the amount >= min_amount,
+    /// the withdrawal takes the longest reached tier.
+    /// Set min_capacity to u64::MAX for a sUDT only tier.
+    fn withdrawal_extra_finality_blocks(&self, value: &CellValue) -> Result<u64, Error> {
+        let field = match self.extra_field(WITHDRAWAL_FINALITY_TIERS) {
+            Some(field) => field,
+            None => return Ok(0),
+        };
+        if field.len() % WITHDRAWAL_FINALITY_TIER_SIZE != 0 {
+            debug!("invalid withdrawal finality tiers, len: {}", field.len());
+            return Err(Error::Encoding);
+        }
+        let extra_finality_blocks = field
+            .chunks_exact(WITHDRAWAL_FINALITY_TIER_SIZE)
+            .filter(|tier| {
+                let min_capacity = u64::from_le_bytes(tier[32..40].try_into().expect("capacity"));
+                let min_amount = u128::from_le_bytes(tier[40..56].try_into().expect("amount"));
+                value.capacity >= min_capacity
+                    || (!value.sudt_script_hash.is_zero()
+                        && &tier[..32] == value.sudt_script_hash.as_slice()
+                        && value.amount >= min_amount)
+            })
+            .map(|tier| u64::from_le_bytes(tier[56..].try_into().expect("blocks")))
+            .max()
+            .unwrap_or(0);
+        Ok(extra_finality_blocks)
+    }
+
+    /// Type hash of the L1 xUDT script, return None if xUDT isn't supported
+    fn l1_xudt_script_type_hash(&self) -> Result<Option<[u8; 32]>, Error> {
+        parse_byte32(self.extra_field(L1_XUDT_SCRIPT_TYPE_HASH))
+    }
+
+    /// Type hash of the L1 -> L2 message lock, return None if messages aren't supported
+    fn message_script_type_hash(&self) -> Result<Option<[u8; 32]>, Error> {
+        parse_byte32(self.extra_field(MESSAGE_SCRIPT_TYPE_HASH))
+    }
+
+    /// Type hash of the layer2 NFT validator, the script of NFT accounts,
+    /// return None if NFTs can't be deposited
+    fn l2_nft_validator_script_type_hash(&self) -> Result<Option<[u8; 32]>, Error> {
+        parse_byte32(self.extra_field(L2_NFT_VALIDATOR_SCRIPT_TYPE_HASH))
+    }
+
+    /// Check the code hash is a L1 NFT type script,
+    /// every cell of a NFT type script must have an unique type hash
+    fn is_l1_nft_script_type_hash(&self, code_hash: &[u8]) -> Result<bool, Error> {
+        match self.extra_field(L1_NFT_SCRIPT_TYPE_HASHES) {
+            Some(field) => contains_hash(field, code_hash),
+            None => Ok(false),
+        }
+    }
+
+    /// Check a L1 token can be bridged, the token must not be in the blocklist.
+    /// A sUDT or xUDT must be in the allowlist if it is set,
+    /// NFTs are allowed by `l1_nft_script_type_hashes` since every NFT has an unique type hash.
+    fn is_l1_token_allowed(&self, token_script_hash: &H256, is_nft: bool) -> Result<bool, Error> {
+        if let Some(allowlist) = self.extra_field(L1_SUDT_ALLOWLIST) {
+            if !is_nft && !contains_hash(allowlist, token_script_hash.as_slice())? {
+                return Ok(false);
+            }
+        }
+        if let Some(blocklist) = self.extra_field(L1_SUDT_BLOCKLIST) {
+            if contains_hash(blocklist, token_script_hash.as_slice())? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl RollupConfigExt for RollupConfig {
+    fn extra_field(&self, index: usize) -> Option<&[u8]> {
+        compatible::extra_field(
+            self.as_slice(),
+            self.field_count(),
+            RollupConfig::FIELD_COUNT,
+            index,
+        )
+    }
+}
+
+fn contains_hash(field: &[u8], hash: &[u8]) -> Result<bool, Error> {
+    if field.len() % 32 != 0 {
+        debug!("invalid rollup config hash list, len: {}", field.len());
+        return Err(Error::Encoding);
+    }
+    Ok(field.chunks_exact(32).any(|item| item == hash))
+}
+
+fn parse_byte32(field: Option<&[u8]>) -> Result<Option<[u8; 32]>, Error> {
+    match field {
+        Some(field) => {
+            let buf = field.try_into().map_err(|_| {
+                debug!("invalid rollup config byte32 field, len: {}", field.len());
+                Error::Encoding
+            })?;
+            Ok(Some(buf))
+        }
+        None => Ok(None),
+    }
+}
+
+fn parse_u64(field: Option<&[u8]>) -> Result<Option<u64>, Error> {
+    match field {
+        Some(field) => {
+            let buf = field.try_into().map_err(|_| {
+                debug!("invalid rollup config u64 field, len: {}", field.len());
+                Error::Encoding
+            })?;
+            Ok(Some(u64::from_le_bytes(buf)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn parse_u32(field: Option<&[u8]>) -> Result<Option<u32>, Error> {
+    match field {
+        Some(field) => {
+            let buf = field.try_into().map_err(|_| {
+                debug!("invalid rollup config u32 field, len: {}", field.len());
+                Error::Encoding
+            })?;
+            Ok(Some(u32::from_le_bytes(buf)))
+        }
+        None => Ok(None),
+    }
+}
@@ -1,22 +1,193 @@
 use crate::{cells::utils::search_lock_hashes, error::Error};
-use ckb_std::{ckb_constants::Source, debug, syscalls::load_cell_data};
-use gw_common::H256;
+use alloc::vec::Vec;
+use ckb_std::{ckb_constants::Source, debug, high_level::load_cell_data};
+use core::convert::TryFrom;
+use gw_common::{
+    blake2b::new_blake2b,
+    merkle_utils::{calculate_ckb_merkle_root, ckb_merkle_leaf_hash},
+    H256,
+};
+use gw_types::{
+    packed::{RawL2Transaction, RawL2TransactionReader},
+    prelude::*,
+};
+
+/// Max number of messages in a batch signature cell
+pub const MAX_BATCH_MESSAGES: usize = 256;
+
+/// Signing type of account lock cell
+///
+/// `WithPrefix` and `Raw` are the same values as `gw_types::core::SigningType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningType {
+    WithPrefix = 0,
+    Raw = 1,
+    TypedData = 2,
+}
+
+impl TryFrom<u8> for SigningType {
+    type Error = u8;
+
+    fn try_from(v: u8) -> Result<SigningType, u8> {
+        match v {
+            0 => Ok(SigningType::WithPrefix),
+            1 => Ok(SigningType::Raw),
+            2 => Ok(SigningType::TypedData),
+            _ => Err(v),
+        }
+    }
+}
+
+/// Layer2 transaction signed as typed data
+///
+/// rollup_type_hash(32 bytes) | sender_script_hash(32 bytes) | receiver_script_hash(32 bytes)
+/// | raw_tx(RawL2Transaction)
+pub struct TypedTransaction {
+    pub rollup_type_hash: [u8; 32],
+    pub sender_script_hash: [u8; 32],
+    pub receiver_script_hash: [u8; 32],
+    pub raw_tx: RawL2Transaction,
+}
+
+impl TypedTransaction {
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 96 {
+            debug!("Invalid typed transaction size: {}", data.len());
+            return Err(Error::Encoding);
+        }
+        let mut rollup_type_hash = [0u8; 32];
+        rollup_type_hash.copy_from_slice(&data[..32]);
+        let mut sender_script_hash = [0u8; 32];
+        sender_script_hash.copy_from_slice(&data[32..64]);
+        let mut receiver_script_hash = [0u8; 32];
+        receiver_script_hash.copy_from_slice(&data[64..96]);
+        let raw_tx = match RawL2TransactionReader::verify(&data[96..], false) {
+            Ok(_) => RawL2Transaction::new_unchecked(data[96..].to_vec().into()),
+            Err(_) => {
+                debug!("Invalid typed transaction");
+                return Err(Error::Encoding);
+            }
+        };
+        Ok(TypedTransaction {
+            rollup_type_hash,
+            sender_script_hash,
+            receiver_script_hash,
+            raw_tx,
+        })
+    }
+
+    /// The layer2 transaction message, which is checked by challenge
+    pub fn calc_message(&self) -> H256 {
+        let mut hasher = new_blake2b();
+        hasher.update(&self.rollup_type_hash);
+        hasher.update(&self.sender_script_hash);
+        hasher.update(&self.receiver_script_hash);
+        hasher.update(self.raw_tx.as_slice());
+        let mut message = [0u8; 32];
+        hasher.finalize(&mut message);
+        message.into()
+    }
+}
+
+/// Layer2 account lock cell data
+///
+/// single message:
+/// owner_lock_hash(32 bytes) | message(32 bytes)
+/// or owner_lock_hash(32 bytes) | signing_type(1 byte) | message(32 bytes)
+///
+/// batch messages:
+/// owner_lock_hash(32 bytes) | signing_type(1 byte) | message(32 bytes) * n, 2 <= n <= MAX_BATCH_MESSAGES
+/// one signature signs the CKB merkle root of the messages, the messages in the cell
+/// are the inclusion proof of each message.
+///
+/// typed data:
+/// owner_lock_hash(32 bytes) | signing_type(1 byte) | message(32 bytes) | typed_tx(TypedTransaction)
+/// the message must be the message of the typed transaction.
+pub struct SignatureCellData {
+    pub owner_lock_hash: [u8; 32],
+    pub signing_type: SigningType,
+    pub messages: Vec<H256>,
+    pub typed_tx: Option<TypedTransaction>,
+}
+
+impl SignatureCellData {
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 64 {
+            debug!("Invalid data size: {}", data.len());
+            return Err(Error::Encoding);
+        }
+        let (signing_type, messages, typed_tx) = if data.len() == 64 {
+            (SigningType::WithPrefix, &data[32..], None)
+        } else {
+            let signing_type = SigningType::try_from(data[32]).map_err(|err| {
+                debug!("Invalid signature message type {}", err);
+                Error::Encoding
+            })?;
+            match signing_type {
+                SigningType::TypedData => {
+                    let typed_tx = TypedTransaction::from_slice(&data[65..])?;
+                    (signing_type, &data[33..65], Some(typed_tx))
+                }
+                _ if (data.len() - 33) % 32 == 0 => (signing_type, &data[33..], None),
+                _ => {
+                    debug!("Invalid data size: {}", data.len());
+                    return Err(Error::Encoding);
+                }
+            }
+        };
+        if messages.len() / 32 > MAX_BATCH_MESSAGES {
+            debug!("Too many messages: {}", messages.len() / 32);
+            return Err(Error::Encoding);
+        }
+
+        let mut owner_lock_hash = [0u8; 32];
+        owner_lock_hash.copy_from_slice(&data[..32]);
+        let messages = messages
+            .chunks_exact(32)
+            .map(|msg| {
+                let mut buf = [0u8; 32];
+                buf.copy_from_slice(msg);
+                buf.into()
+            })
+            .collect();
+
+        Ok(SignatureCellData {
+            owner_lock_hash,
+            signing_type,
+            messages,
+            typed_tx,
+        })
+    }
+
+    /// The message signed by the account, it is the CKB merkle root for batch messages
+    pub fn signing_message(&self) -> Result<H256, Error> {
+        if self.messages.len() == 1 {
+            return Ok(self.messages[0]);
+        }
+        let leaves = self
+            .messages
+            .iter()
+            .enumerate()
+            .map(|(idx, msg)| ckb_merkle_leaf_hash(idx as u32, msg))
+            .collect();
+        Ok(calculate_ckb_merkle_root(leaves)?)
+    }
+}
 
 /// Check l2 account signature cell
 pub fn check_l2_account_signature_cell(script_hash: &H256, message: H256) -> Result<(), Error> {
     debug!("Check l2 account signature for message {:?}", message);
     // search layer2 account lock cell from inputs
     for index in search_lock_hashes(&(*script_hash).into(), Source::Input) {
-        // expected data is equals to owner_lock_hash(32 bytes) | message(32 bytes)
-        let mut data = [0u8; 32];
-        let len = load_cell_data(&mut data, 32, index, Source::Input)?;
+        let data = load_cell_data(index, Source::Input)?;
 
-        // skip if the data isn't 32 length
-        if len != data.len() {
-            continue;
-        }
+        // skip if the data isn't a signature cell
+        let cell_data = match SignatureCellData::from_slice(&data) {
+            Ok(cell_data) => cell_data,
+            Err(_) => continue,
+        };
 
-        if data == message.as_slice() {
+        if cell_data.messages.contains(&message) {
             return Ok(());
         }
     }
@@ -0,0 +1,84 @@
+//! Since helpers
+//!
+//! Compare since values by their metric, block number, epoch with fraction or timestamp,
+//! instead of comparing the raw u64 values.
+
+use core::cmp::Ordering;
+
+use ckb_std::{
+    debug,
+    since::{EpochNumberWithFraction, LockValue, Since},
+};
+
+use crate::error::Error;
+
+/// Parse a well-formed since, return the lock value
+pub fn parse_since(since: &Since) -> Result<LockValue, Error> {
+    if !since.flags_is_valid() {
+        debug!("invalid since flags: {:#x}", since.as_u64());
+        return Err(Error::InvalidSince);
+    }
+    let value = since.extract_lock_value().ok_or(Error::InvalidSince)?;
+    if let LockValue::EpochNumberWithFraction(epoch) = &value {
+        // an epoch without fraction is encoded as index 0 and length 0
+        let is_well_formed =
+            epoch.index() < epoch.length() || (epoch.index() == 0 && epoch.length() == 0);
+        if !is_well_formed {
+            debug!(
+                "invalid since epoch fraction: {}/{}",
+                epoch.index(),
+                epoch.length()
+            );
+            return Err(Error::InvalidSince);
+        }
+    }
+    Ok(value)
+}
+
+/// Compare the lock values of the same metric,
+/// return None if the metrics are different
+pub fn cmp_lock_value(a: &LockValue, b: &LockValue) -> Option<Ordering> {
+    match (a, b) {
+        (LockValue::BlockNumber(a), LockValue::BlockNumber(b)) => Some(a.cmp(b)),
+        (LockValue::Timestamp(a), LockValue::Timestamp(b)) => Some(a.cmp(b)),
+        (LockValue::EpochNumberWithFraction(a), LockValue::EpochNumberWithFraction(b)) => {
+            Some(cmp_epoch(a, b))
+        }
+        _ => None,
+    }
+}
+
+/// Compare epoch number, then the fraction a.index / a.length with b.index / b.length
+fn cmp_epoch(a: &EpochNumberWithFraction, b: &EpochNumberWithFraction) -> Ordering {
+    a.number().cmp(&b.number()).then_with(|| {
+        // index and length are 16 bits, the products can't overflow
+        let a_fraction = a.index() * b.length().max(1);
+        let b_fraction = b.index() * a.length().max(1);
+        a_fraction.cmp(&b_fraction)
+    })
+}
+
+/// Check the input since satisfies the timeout,
+/// both must be the same metric in the same form (relative or absolute), and since >= timeout
+pub fn check_since_timeout(input_since: &Since, timeout: &Since) -> Result<(), Error> {
+    if input_since.is_relative() != timeout.is_relative() {
+        debug!(
+            "mismatch since form, relative: {}",
+            input_since.is_relative()
+        );
+        return Err(Error::InvalidSince);
+    }
+    let since_value = parse_since(input_since)?;
+    let timeout_value = parse_since(timeout)?;
+    match cmp_lock_value(&since_value, &timeout_value) {
+        Some(Ordering::Greater) | Some(Ordering::Equal) => Ok(()),
+        Some(Ordering::Less) => {
+            debug!("since doesn't satisfy the timeout");
+            Err(Error::InvalidSince)
+        }
+        None => {
+            debug!("mismatch since metric");
+            Err(Error::InvalidSince)
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! WithdrawalRequest extra fields
+//!
+//! Optional fields appended after the upstream `WithdrawalRequest` fields,
+//! the withdrawal requests are parsed as compatible molecule tables within the block.
+//! A missing or empty extra field means the option is unset,
+//! the extra fields are covered by the withdrawal witness hash.
+//!
+//! `RawWithdrawalRequest` is a molecule struct which can't be extended,
+//! a withdrawal with extra fields signs `hash(rollup_type_hash | raw | valid_until)`,
+//! so the block producer can't strip the expiry.
+//!
+//! extra fields:
+//! 0. valid_until: u64 LE, an absolute since by L2 block number or timestamp,
+//!    the last block which can include the withdrawal, a block with a v0 global state
+//!    can't include a withdrawal valid until a timestamp since the block timestamp
+//!    isn't checked
+//!
+//! `WithdrawalLockArgs` is a molecule struct as well, the withdrawal finality tier
+//! is appended after it in the lock args:
+//! rollup_type_hash(32 bytes) | WithdrawalLockArgs | extra_finality_blocks(u64 LE, optional)
+
+use core::convert::TryInto;
+
+use ckb_std::{
+    debug,
+    since::{LockValue, Since},
+};
+use gw_common::blake2b::new_blake2b;
+use gw_types::{
+    packed::{
+        RawWithdrawalRequestReader, WithdrawalLockArgs, WithdrawalLockArgsReader,
+        WithdrawalRequest, WithdrawalRequestReader,
+    },
+    prelude::*,
+};
+
+use crate::{compatible, error::Error, since::parse_since};
+
+const VALID_UNTIL: usize = 0;
+
+pub trait WithdrawalRequestExt {
+    /// Extra field by the index after the upstream fields,
+    /// return None if the field is missing or empty
+    fn extra_field(&self, index: usize) -> Option<&[u8]>;
+
+    /// Expiry of the withdrawal, return None if the withdrawal never expires
+    fn valid_until(&self) -> Result<Option<Since>, Error> {
+        match self.extra_field(VALID_UNTIL) {
+            Some(field) => {
+                let buf = field.try_into().map_err(|_| {
+                    debug!("invalid withdrawal valid until, len: {}", field.len());
+                    Error::Encoding
+                })?;
+                Ok(Some(Since::new(u64::from_le_bytes(buf))))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl WithdrawalRequestExt for WithdrawalRequest {
+    fn extra_field(&self, index: usize) -> Option<&[u8]> {
+        compatible::extra_field(
+            self.as_slice(),
+            self.field_count(),
+            WithdrawalRequest::FIELD_COUNT,
+            index,
+        )
+    }
+}
+
+impl<'r> WithdrawalRequestExt for WithdrawalRequestReader<'r> {
+    fn extra_field(&self, index: usize) -> Option<&[u8]> {
+        compatible::extra_field(
+            self.as_slice(),
+            self.field_count(),
+            WithdrawalRequestReader::FIELD_COUNT,
+            index,
+        )
+    }
+}
+
+/// Check the withdrawal isn't expired at the including block
+pub fn check_withdrawal_valid_until(
+    valid_until: &Since,
+    block_number: u64,
+    block_timestamp: u64,
+) -> Result<(), Error> {
+    if !valid_until.is_absolute() {
+        debug!("withdrawal valid until must be absolute");
+        return Err(Error::InvalidWithdrawalRequest);
+    }
+    let expired = match parse_since(valid_until)? {
+        LockValue::BlockNumber(number) => block_number > number,
+        LockValue::Timestamp(timestamp) => block_timestamp > timestamp,
+        LockValue::EpochNumberWithFraction(_) => {
+            debug!("withdrawal valid until doesn't support epoch");
+            return Err(Error::InvalidWithdrawalRequest);
+        }
+    };
+    if expired {
+        debug!(
+            "withdrawal expired at {:#x}, block number: {} timestamp: {}",
+            valid_until.as_u64(),
+            block_number,
+            block_timestamp
+        );
+        return Err(Error::InvalidWithdrawalRequest);
+    }
+    Ok(())
+}
+
+/// Signing message of a withdrawal,
+/// hash(rollup_type_hash | raw) or hash(rollup_type_hash | raw | valid_until)
+pub fn calculate_withdrawal_message(
+    rollup_type_hash: &[u8; 32],
+    raw_withdrawal: &RawWithdrawalRequestReader,
+    valid_until: Option<&Since>,
+) -> [u8; 32] {
+    let mut hasher = new_blake2b();
+    hasher.update(rollup_type_hash);
+    hasher.update(raw_withdrawal.as_slice());
+    if let Some(valid_until) = valid_until {
+        hasher.update(&valid_until.as_u64().to_le_bytes());
+    }
+    let mut message = [0u8; 32];
+    hasher.finalize(&mut message);
+    message
+}
+
+/// Parse the withdrawal lock args after the rollup type hash,
+/// return the args and the extra finality blocks, 0 if absent
+pub fn parse_withdrawal_lock_args(raw_args: &[u8]) -> Result<(WithdrawalLockArgs, u64), Error> {
+    let (args, extra) = if raw_args.len() == WithdrawalLockArgsReader::TOTAL_SIZE + 8 {
+        raw_args.split_at(WithdrawalLockArgsReader::TOTAL_SIZE)
+    } else {
+        (raw_args, &[][..])
+    };
+    let args = WithdrawalLockArgs::from_slice(args).map_err(|_| Error::Encoding)?;
+    let extra_finality_blocks = if extra.is_empty() {
+        0
+    } else {
+        u64::from_le_bytes(extra.try_into().expect("extra finality blocks"))
+    };
+    Ok((args, extra_finality_blocks))
+}
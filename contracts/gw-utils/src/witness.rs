@@ -0,0 +1,173 @@
+//! Partial witness loading
+//!
+//! Fetch molecule items of a witness on demand by the `offset` argument of
+//! `load_witness`, instead of copying the whole witness into a fixed buffer.
+//!
+//! Only the headers on the path and the loaded items are verified,
+//! the script which owns the witness is responsible to verify the whole structure.
+
+use alloc::{vec, vec::Vec};
+use ckb_std::{
+    ckb_constants::Source,
+    debug,
+    syscalls::{load_witness, SysError},
+};
+
+use crate::error::Error;
+
+const NUMBER_SIZE: usize = 4;
+
+/// Location of a molecule item in a witness
+#[derive(Debug, Clone, Copy)]
+pub struct WitnessCursor {
+    index: usize,
+    source: Source,
+    offset: usize,
+    size: usize,
+}
+
+impl WitnessCursor {
+    /// Cursor of the whole witness, only the witness length is loaded
+    pub fn new(index: usize, source: Source) -> Result<Self, Error> {
+        let size = match load_witness(&mut [], 0, index, source) {
+            Ok(len) => len,
+            Err(SysError::LengthNotEnough(len)) => len,
+            Err(err) => return Err(err.into()),
+        };
+        Ok(WitnessCursor {
+            index,
+            source,
+            offset: 0,
+            size,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Load the item
+    pub fn load(&self) -> Result<Vec<u8>, Error> {
+        self.load_at(0, self.size)
+    }
+
+    /// Load `len` bytes from `pos` of the item
+    fn load_at(&self, pos: usize, len: usize) -> Result<Vec<u8>, Error> {
+        if pos.checked_add(len).ok_or(Error::Encoding)? > self.size {
+            debug!(
+                "load witness out of bound, pos: {} len: {} size: {}",
+                pos, len, self.size
+            );
+            return Err(Error::Encoding);
+        }
+        let mut buf = vec![0u8; len];
+        let loaded_len = match load_witness(&mut buf, self.offset + pos, self.index, self.source) {
+            Ok(loaded_len) => loaded_len,
+            Err(SysError::LengthNotEnough(_)) => len,
+            Err(err) => return Err(err.into()),
+        };
+        if loaded_len < len {
+            return Err(Error::Encoding);
+        }
+        Ok(buf)
+    }
+
+    fn read_number(&self, pos: usize) -> Result<usize, Error> {
+        let mut buf = [0u8; NUMBER_SIZE];
+        buf.copy_from_slice(&self.load_at(pos, NUMBER_SIZE)?);
+        Ok(u32::from_le_bytes(buf) as usize)
+    }
+
+    fn slice(&self, start: usize, end: usize) -> Result<Self, Error> {
+        if start > end || end > self.size {
+            debug!(
+                "invalid molecule item range: {}..{}, size: {}",
+                start, end, self.size
+            );
+            return Err(Error::Encoding);
+        }
+        Ok(WitnessCursor {
+            offset: self.offset + start,
+            size: end - start,
+            ..*self
+        })
+    }
+
+    /// Field of a molecule table
+    pub fn table_field(&self, field_index: usize) -> Result<Self, Error> {
+        let total_size = self.read_number(0)?;
+        if total_size != self.size {
+            debug!(
+                "invalid table size: {}, expected: {}",
+                total_size, self.size
+            );
+            return Err(Error::Encoding);
+        }
+        let first_offset = self.read_number(NUMBER_SIZE)?;
+        if first_offset % NUMBER_SIZE != 0 || first_offset < NUMBER_SIZE * 2 {
+            return Err(Error::Encoding);
+        }
+        let field_count = first_offset / NUMBER_SIZE - 1;
+        if field_index >= field_count {
+            debug!(
+                "table field {} out of bound, field count: {}",
+                field_index, field_count
+            );
+            return Err(Error::Encoding);
+        }
+        let start = self.read_number(NUMBER_SIZE * (field_index + 1))?;
+        let end = if field_index + 1 < field_count {
+            self.read_number(NUMBER_SIZE * (field_index + 2))?
+        } else {
+            total_size
+        };
+        self.slice(start, end)
+    }
+
+    /// Item of a molecule union, return (item_id, item)
+    pub fn union_item(&self) -> Result<(u32, Self), Error> {
+        let item_id = self.read_number(0)? as u32;
+        let item = self.slice(NUMBER_SIZE, self.size)?;
+        Ok((item_id, item))
+    }
+
+    /// Inner item of a molecule option, return None if the option is none
+    pub fn option_item(&self) -> Option<Self> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(*self)
+        }
+    }
+
+    /// Raw data of a molecule `Bytes`
+    pub fn bytes_raw_data(&self) -> Result<Self, Error> {
+        let item_count = self.read_number(0)?;
+        if NUMBER_SIZE + item_count != self.size {
+            debug!("invalid bytes size: {}, items: {}", self.size, item_count);
+            return Err(Error::Encoding);
+        }
+        self.slice(NUMBER_SIZE, self.size)
+    }
+
+    /// Fields of `WitnessArgs`
+    pub fn witness_args_lock(&self) -> Result<Option<Self>, Error> {
+        self.witness_args_field(0)
+    }
+
+    pub fn witness_args_output_type(&self) -> Result<Option<Self>, Error> {
+        self.witness_args_field(2)
+    }
+
+    /// return the raw data of a `BytesOpt` field
+    fn witness_args_field(&self, field_index: usize) -> Result<Option<Self>, Error> {
+        match self.table_field(field_index)?.option_item() {
+            Some(bytes) => bytes.bytes_raw_data().map(Some),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Message-lock
+//! A user can send a L1 -> L2 message cell with this lock,
+//! the cell data is the payload to the layer2 contract account `to_id`.
+//! The cell can be unlocked by the rollup cell which match the rollup_type_hash,
+//! the state validator commits the consumed messages into the block's message root,
+//! or can be unlocked by user after the cancel timeout.
+//!
+//! A delivered message is marked with the block which delivered it,
+//! it can be unlocked by the owner after the block is finalized,
+//! or by the rollup cell which reverts the block.
+//! The owner proves the block is still in the canonical chain by a block merkle proof
+//! in the witness lock field, if the block isn't proved, the owner can only
+//! refund the message after the cancel timeout.
+//!
+//! Args: rollup_type_hash | MessageLockArgs
+//! Witness lock of the owner unlock: compiled block merkle proof, optional
+
+use alloc::vec;
+// Import from `core` instead of from `std` since we are in no-std mode
+use core::result::Result;
+
+// Import CKB syscalls and structures
+// https://nervosnetwork.github.io/ckb-std/riscv64imac-unknown-none-elf/doc/ckb_std/index.html
+use crate::ckb_std::{
+    ckb_constants::Source,
+    ckb_types::{bytes::Bytes, prelude::Unpack as CKBTypeUnpack},
+    debug,
+    error::SysError,
+    high_level::{load_input_since, load_script, load_witness_args},
+    since::Since,
+};
+
+use gw_utils::{
+    cells::{
+        message::{DeliveredBlock, MessageLockArgs},
+        rollup::{search_rollup_action, search_rollup_state, RollupActionType},
+        utils::search_lock_hash,
+    },
+    gw_common::smt::{Blake2bHasher, CompiledMerkleProof},
+    gw_types::{packed::GlobalState, packed::RawL2Block, prelude::*},
+    since::check_since_timeout,
+};
+
+use crate::error::Error;
+
+/// args: rollup_type_hash | message lock args
+fn parse_lock_args() -> Result<([u8; 32], MessageLockArgs), Error> {
+    let mut rollup_type_hash = [0u8; 32];
+    let script = load_script()?;
+    let args: Bytes = script.args().unpack();
+    if args.len() < rollup_type_hash.len() {
+        return Err(Error::InvalidArgs);
+    }
+    rollup_type_hash.copy_from_slice(&args[..32]);
+    let lock_args = MessageLockArgs::from_slice(&args[32..])?;
+    Ok((rollup_type_hash, lock_args))
+}
+
+/// Prove the delivered block is in the canonical chain of the global state
+/// by the block merkle proof in the witness lock field,
+/// return false if the proof is missing or the block isn't in the chain
+fn is_canonical_block(
+    global_state: &GlobalState,
+    delivered_block: &DeliveredBlock,
+) -> Result<bool, Error> {
+    let witness_args = match load_witness_args(0, Source::GroupInput) {
+        Ok(witness_args) => witness_args,
+        Err(SysError::IndexOutOfBound) => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    let proof: Bytes = match witness_args.lock().to_opt() {
+        Some(proof) => proof.unpack(),
+        None => return Ok(false),
+    };
+    let block_merkle_proof = CompiledMerkleProof(proof.to_vec());
+    let leaves = vec![(
+        RawL2Block::compute_smt_key(delivered_block.number).into(),
+        delivered_block.hash,
+    )];
+    let block_merkle_root = global_state.block().merkle_root().unpack();
+    match block_merkle_proof.verify::<Blake2bHasher>(&block_merkle_root, leaves) {
+        Ok(is_canonical) => Ok(is_canonical),
+        Err(_) => {
+            debug!("invalid block merkle proof");
+            Ok(false)
+        }
+    }
+}
+
+// A delivered message has three unlock paths
+// 1. unlock by Rollup cell with the RollupSubmitBlock action which reverts the delivered block,
+//    the state validator restores the message
+// 2. unlock by owner after the delivered block is finalized and proved in the canonical chain
+// 3. refund to owner after the cancel timeout, if the delivered block isn't proved
+fn unlock_delivered_message(
+    rollup_type_hash: &[u8; 32],
+    lock_args: &MessageLockArgs,
+    delivered_block: &DeliveredBlock,
+) -> Result<(), Error> {
+    if let Some(action) = search_rollup_action(rollup_type_hash)? {
+        if let Some(reverted_block_hashes) = action.load_reverted_block_hashes()? {
+            if reverted_block_hashes
+                .as_reader()
+                .iter()
+                .any(|hash| hash.as_slice() == delivered_block.hash.as_slice())
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    if search_lock_hash(&lock_args.owner_lock_hash, Source::Input).is_none() {
+        return Err(Error::OwnerCellNotFound);
+    }
+    // the rollup cell in inputs or cell deps
+    let global_state = match search_rollup_state(rollup_type_hash, Source::Input)? {
+        Some(state) => state,
+        None => search_rollup_state(rollup_type_hash, Source::CellDep)?
+            .ok_or(Error::RollupCellNotFound)?,
+    };
+    if is_canonical_block(&global_state, delivered_block)? {
+        let last_finalized_block_number: u64 = global_state.last_finalized_block_number().unpack();
+        if delivered_block.number > last_finalized_block_number {
+            debug!("delivered block {} isn't finalized", delivered_block.number);
+            return Err(Error::InvalidRevertedBlocks);
+        }
+        return Ok(());
+    }
+    // the delivered block may be reverted, refund the message after the cancel timeout
+    debug!("delivered block {:?} isn't proved", delivered_block.hash);
+    let input_since = Since::new(load_input_since(0, Source::GroupInput)?);
+    let cancel_timeout = Since::new(lock_args.cancel_timeout);
+    check_since_timeout(&input_since, &cancel_timeout)
+}
+
+// We have two unlock paths
+// 1. unlock by Rollup cell with the RollupSubmitBlock action
+// 2. unlock by user after timeout
+//
+// We always try the 1 first, then try 2, otherwise the unlock return a failure.
+pub fn main() -> Result<(), Error> {
+    let (rollup_type_hash, lock_args) = parse_lock_args()?;
+    if let Some(delivered_block) = &lock_args.delivered_block {
+        return unlock_delivered_message(&rollup_type_hash, &lock_args, delivered_block);
+    }
+    // try unlock by Rollup
+    // return success if rollup cell in the inputs and the action is submit block,
+    // the following verification will be handled by rollup state validator.
+    if let Some(action) = search_rollup_action(&rollup_type_hash)? {
+        if action.action_type() == RollupActionType::SubmitBlock {
+            return Ok(());
+        }
+        debug!("message can't be unlocked by {:?}", action.action_type());
+    }
+
+    // unlock by user
+    // 1. check since is satisfied the cancel timeout
+    let input_since = Since::new(load_input_since(0, Source::GroupInput)?);
+    let cancel_timeout = Since::new(lock_args.cancel_timeout);
+    check_since_timeout(&input_since, &cancel_timeout)?;
+    // 2. search owner cell
+    match search_lock_hash(&lock_args.owner_lock_hash, Source::Input) {
+        Some(_) => Ok(()),
+        None => Err(Error::OwnerCellNotFound),
+    }
+}
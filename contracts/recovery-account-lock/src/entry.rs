@@ -0,0 +1,160 @@
+// Import from `core` instead of from `std` since we are in no-std mode
+use core::result::Result;
+
+// Import CKB syscalls and structures
+// https://nervosnetwork.github.io/ckb-std/riscv64imac-unknown-none-elf/doc/ckb_std/index.html
+use crate::{
+    ckb_std::{
+        ckb_constants::Source,
+        ckb_types::{bytes::Bytes, prelude::Unpack as CKBUnpack},
+        debug,
+        error::SysError,
+        high_level::{load_input_since, load_script, load_tx_hash, load_witness_args},
+        since::Since,
+    },
+    registry::{load_group_registry, load_registry_from_deps, Registry},
+};
+use gw_utils::{
+    account_lock::{self, normalize_signature, AccountLock, SIGNATURE_SIZE},
+    cells::utils::search_lock_hash,
+    error::Error,
+    eth_signature::{EthAddress, Secp256k1Eth},
+    since::check_since_timeout,
+    type_id::{check_type_id, TYPE_ID_SIZE},
+};
+
+const ACCOUNT_LOCK_ARGS_SIZE: usize = 64;
+
+/// Recovery account lock
+///
+/// The same script is used as the account lock and as the type script of
+/// the recovery registry, distinguished by the length of the script args.
+///
+/// As account lock:
+/// script args: rollup_script_hash(32 bytes) | registry_type_hash(32 bytes)
+/// data: owner_lock_hash(32 bytes) | message(32 bytes)
+/// the message is signed by the owner address of the registry in cell deps.
+///
+/// As registry type script:
+/// script args: type_id(32 bytes)
+/// data: see `registry::Registry`
+pub fn main() -> Result<(), Error> {
+    let script = load_script()?;
+    let args: Bytes = CKBUnpack::unpack(&script.args());
+    match args.len() {
+        ACCOUNT_LOCK_ARGS_SIZE => verify_account_lock(args),
+        TYPE_ID_SIZE => {
+            let mut type_id = [0u8; 32];
+            type_id.copy_from_slice(&args);
+            verify_registry(type_id)
+        }
+        len => {
+            debug!("Invalid args len: {}", len);
+            Err(Error::InvalidArgs)
+        }
+    }
+}
+
+fn verify_account_lock(args: Bytes) -> Result<(), Error> {
+    let mut registry_type_hash = [0u8; 32];
+    registry_type_hash.copy_from_slice(&args[32..64]);
+    let registry = load_registry_from_deps(&registry_type_hash)?;
+    debug!("owner_address {:?}", &registry.owner_address);
+
+    account_lock::verify_account_lock(&Secp256k1Eth, &registry.owner_address)
+}
+
+/// Verify registry transitions
+///
+/// 1. owner: any update or destroy, signed by the owner over the tx hash,
+///    this is also how the owner vetoes a pending recovery
+/// 2. guardians: start a recovery by setting the pending address,
+///    requires K of N guardian cells in the inputs
+/// 3. anyone: finalize a pending recovery after the recovery delay
+fn verify_registry(type_id: [u8; 32]) -> Result<(), Error> {
+    check_type_id(type_id)?;
+
+    let input_registry = load_group_registry(Source::GroupInput)?;
+    let output_registry = load_group_registry(Source::GroupOutput)?;
+    let input_registry = match input_registry {
+        Some(registry) => registry,
+        None => {
+            // create registry
+            let output_registry = output_registry.ok_or(Error::InvalidRecoveryRegistry)?;
+            if output_registry.has_pending_recovery() {
+                debug!("Can't create registry with a pending recovery");
+                return Err(Error::InvalidRecoveryRegistry);
+            }
+            return Ok(());
+        }
+    };
+
+    if let Some(signature) = load_registry_signature()? {
+        return verify_owner_signature(input_registry.owner_address, signature);
+    }
+
+    let output_registry = output_registry.ok_or(Error::InvalidRecoveryRegistry)?;
+    if !input_registry.has_pending_recovery() {
+        // start recovery
+        if !output_registry.has_pending_recovery()
+            || output_registry != input_registry.start_recovery(output_registry.pending_address)
+        {
+            debug!("Guardians can only set the pending address");
+            return Err(Error::InvalidRecoveryRegistry);
+        }
+        return check_guardians(&input_registry);
+    }
+
+    // finalize recovery
+    if output_registry != input_registry.finalize_recovery() {
+        debug!("Invalid finalized registry");
+        return Err(Error::InvalidRecoveryRegistry);
+    }
+    let input_since = Since::new(load_input_since(0, Source::GroupInput)?);
+    let recovery_delay = Since::new(input_registry.recovery_delay);
+    check_since_timeout(&input_since, &recovery_delay).map_err(|err| {
+        debug!("Recovery delay is not satisfied");
+        err
+    })
+}
+
+fn check_guardians(registry: &Registry) -> Result<(), Error> {
+    let approvals = registry
+        .guardian_lock_hashes
+        .iter()
+        .filter(|lock_hash| search_lock_hash(lock_hash, Source::Input).is_some())
+        .count();
+    if approvals < registry.threshold as usize {
+        debug!(
+            "Guardian approvals: {}, threshold: {}",
+            approvals, registry.threshold
+        );
+        return Err(Error::InsufficientGuardians);
+    }
+    Ok(())
+}
+
+fn verify_owner_signature(
+    owner_address: EthAddress,
+    signature: [u8; SIGNATURE_SIZE],
+) -> Result<(), Error> {
+    let tx_hash = load_tx_hash()?;
+    let signing_message = Secp256k1Eth.prefixed_message(&tx_hash.into());
+    Secp256k1Eth.verify_signature(&owner_address, &signing_message, &signature)
+}
+
+/// load owner signature from witness input_type, return None if absent
+fn load_registry_signature() -> Result<Option<[u8; SIGNATURE_SIZE]>, Error> {
+    let witness_args = match load_witness_args(0, Source::GroupInput) {
+        Ok(witness_args) => witness_args,
+        Err(SysError::IndexOutOfBound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    match witness_args.input_type().to_opt() {
+        Some(signature) => {
+            let signature: Bytes = signature.unpack();
+            normalize_signature(&signature).map(Some)
+        }
+        None => Ok(None),
+    }
+}
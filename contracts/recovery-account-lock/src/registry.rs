@@ -0,0 +1,130 @@
+//! Recovery registry
+//!
+//! The registry cell holds the signing key of a recovery account and the
+//! guardians who can rotate it.
+//!
+//! data: owner_address(20 bytes) | pending_address(20 bytes) | recovery_delay(8 bytes)
+//!     | threshold(1 byte) | guardians_count(1 byte) | guardian_lock_hashes(32 bytes * guardians_count)
+//!
+//! A zero `pending_address` means there is no recovery in progress.
+//! `recovery_delay` is a relative since, guardians' recovery can only be finalized
+//! after the registry cell has been kept for the delay.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::convert::TryInto;
+
+use crate::ckb_std::{
+    ckb_constants::Source,
+    debug,
+    error::SysError,
+    high_level::{load_cell_data, load_cell_type_hash, QueryIter},
+    since::Since,
+};
+use gw_utils::{error::Error, eth_signature::EthAddress};
+
+pub const REGISTRY_HEADER_SIZE: usize = 50;
+const EMPTY_ADDRESS: EthAddress = [0u8; 20];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registry {
+    pub owner_address: EthAddress,
+    pub pending_address: EthAddress,
+    pub recovery_delay: u64,
+    pub threshold: u8,
+    pub guardian_lock_hashes: Vec<[u8; 32]>,
+}
+
+impl Registry {
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < REGISTRY_HEADER_SIZE {
+            debug!("Invalid registry data size: {}", data.len());
+            return Err(Error::InvalidRecoveryRegistry);
+        }
+        let owner_address: EthAddress = data[..20].try_into().map_err(|_| Error::Encoding)?;
+        let pending_address: EthAddress = data[20..40].try_into().map_err(|_| Error::Encoding)?;
+        let recovery_delay =
+            u64::from_le_bytes(data[40..48].try_into().map_err(|_| Error::Encoding)?);
+        let threshold = data[48];
+        let guardians_count = data[49] as usize;
+        if data.len() != REGISTRY_HEADER_SIZE + guardians_count * 32 {
+            debug!(
+                "Invalid registry data size: {}, guardians count: {}",
+                data.len(),
+                guardians_count
+            );
+            return Err(Error::InvalidRecoveryRegistry);
+        }
+        let guardian_lock_hashes: Vec<[u8; 32]> = data[REGISTRY_HEADER_SIZE..]
+            .chunks_exact(32)
+            .map(|lock_hash| lock_hash.try_into().map_err(|_| Error::Encoding))
+            .collect::<Result<_, _>>()?;
+
+        // K-of-N guardians
+        if threshold == 0 || threshold as usize > guardians_count {
+            debug!(
+                "Invalid registry threshold: {}, guardians count: {}",
+                threshold, guardians_count
+            );
+            return Err(Error::InvalidRecoveryRegistry);
+        }
+        // a guardian must not be counted twice
+        let unique_guardians: BTreeSet<&[u8; 32]> = guardian_lock_hashes.iter().collect();
+        if unique_guardians.len() != guardians_count {
+            debug!("Duplicated guardian lock hashes");
+            return Err(Error::InvalidRecoveryRegistry);
+        }
+        if owner_address == EMPTY_ADDRESS {
+            debug!("Empty owner address");
+            return Err(Error::InvalidRecoveryRegistry);
+        }
+        if !Since::new(recovery_delay).is_relative() {
+            debug!("Recovery delay must be a relative since");
+            return Err(Error::InvalidRecoveryRegistry);
+        }
+
+        Ok(Registry {
+            owner_address,
+            pending_address,
+            recovery_delay,
+            threshold,
+            guardian_lock_hashes,
+        })
+    }
+
+    pub fn has_pending_recovery(&self) -> bool {
+        self.pending_address != EMPTY_ADDRESS
+    }
+
+    /// The registry after the pending recovery is finalized
+    pub fn finalize_recovery(&self) -> Self {
+        let mut registry = self.clone();
+        registry.owner_address = self.pending_address;
+        registry.pending_address = EMPTY_ADDRESS;
+        registry
+    }
+
+    /// The registry after guardians start a recovery to `new_address`
+    pub fn start_recovery(&self, new_address: EthAddress) -> Self {
+        let mut registry = self.clone();
+        registry.pending_address = new_address;
+        registry
+    }
+}
+
+/// Load registry of the current script group
+pub fn load_group_registry(source: Source) -> Result<Option<Registry>, Error> {
+    match load_cell_data(0, source) {
+        Ok(data) => Registry::from_slice(&data).map(Some),
+        Err(SysError::IndexOutOfBound) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Find registry in the cell deps by the registry type hash
+pub fn load_registry_from_deps(registry_type_hash: &[u8; 32]) -> Result<Registry, Error> {
+    let index = QueryIter::new(load_cell_type_hash, Source::CellDep)
+        .position(|type_hash| type_hash.as_ref() == Some(registry_type_hash))
+        .ok_or(Error::RecoveryRegistryNotFound)?;
+    let data = load_cell_data(index, Source::CellDep)?;
+    Registry::from_slice(&data)
+}
@@ -12,11 +12,12 @@ use core::result::Result;
 use crate::ckb_std::{
     ckb_constants::Source,
     ckb_types::{bytes::Bytes, prelude::Unpack as CKBTypeUnpack},
+    debug,
     high_level::load_script,
 };
 
 use gw_utils::cells::{
-    rollup::{search_rollup_cell, search_rollup_state},
+    rollup::{search_rollup_action, search_rollup_state, RollupActionType},
     utils::search_lock_hash,
 };
 use gw_utils::gw_types;
@@ -65,10 +66,13 @@ pub fn main() -> Result<(), Error> {
     }
 
     // Unlock by Rollup cell
-    // check if rollup cell exists in the inputs, the following verification will be handled
-    // by rollup state validator.
-    if search_rollup_cell(&rollup_type_hash, Source::Input).is_some() {
-        return Ok(());
+    // check if rollup cell exists in the inputs and the action is submit block or revert,
+    // the following verification will be handled by rollup state validator.
+    if let Some(action) = search_rollup_action(&rollup_type_hash)? {
+        match action.action_type() {
+            RollupActionType::SubmitBlock | RollupActionType::Revert => return Ok(()),
+            action_type => debug!("stake can't be unlocked by {:?}", action_type),
+        }
     }
 
     Err(Error::InvalidStakeCellUnlock)
@@ -3,14 +3,17 @@ use core::result::Result;
 
 // Import heap related library from `alloc`
 // https://doc.rust-lang.org/alloc/index.html
+use alloc::vec::Vec;
 use gw_utils::{
-    cells::rollup::{load_rollup_config, parse_rollup_action, MAX_ROLLUP_WITNESS_SIZE},
+    cells::rollup::{
+        load_rollup_config, parse_rollup_action, parse_rollup_cell_data, RollupActionType,
+    },
     ckb_std::{
         ckb_types::prelude::Unpack as CKBUnpack,
         debug,
         high_level::{load_cell_capacity, load_cell_data, load_script},
     },
-    gw_types::packed::{GlobalStateV0, GlobalStateV0Reader, RollupActionUnionReader},
+    gw_types::packed::RollupActionUnionReader,
     type_id::{check_type_id, TYPE_ID_SIZE},
 };
 
@@ -21,27 +24,22 @@ use crate::{
     verifications,
 };
 
-use gw_types::{
-    bytes::Bytes,
-    packed::{GlobalState, GlobalStateReader},
-    prelude::*,
-};
-use gw_utils::gw_types;
+use gw_common::H256;
+use gw_types::{bytes::Bytes, packed::GlobalState, prelude::*};
+use gw_utils::{gw_common, gw_types};
 
 use gw_utils::error::Error;
 
 const MAX_ROLLUP_VERSION: u8 = 1;
 
 pub fn parse_global_state(source: Source) -> Result<GlobalState, Error> {
+    parse_rollup_state(source).map(|(global_state, _paused_at)| global_state)
+}
+
+/// parse the global state and the paused_at, see `gw_utils::pause`
+pub fn parse_rollup_state(source: Source) -> Result<(GlobalState, Option<u64>), Error> {
     let data = load_cell_data(0, source)?;
-    match GlobalStateReader::verify(&data, false) {
-        Ok(_) => Ok(GlobalState::new_unchecked(data.into())),
-        Err(_) if GlobalStateV0Reader::verify(&data, false).is_ok() => {
-            let global_state_v0 = GlobalStateV0::new_unchecked(data.into());
-            Ok(GlobalState::from(global_state_v0))
-        }
-        Err(_) => Err(Error::Encoding),
-    }
+    Ok(parse_rollup_cell_data(&data)?)
 }
 
 /// return true if we are in the initialization, otherwise return false
@@ -73,8 +71,8 @@ pub fn main() -> Result<(), Error> {
         return Ok(());
     }
     // basic verification
-    let prev_global_state = parse_global_state(Source::GroupInput)?;
-    let post_global_state = parse_global_state(Source::GroupOutput)?;
+    let (prev_global_state, prev_paused_at) = parse_rollup_state(Source::GroupInput)?;
+    let (post_global_state, post_paused_at) = parse_rollup_state(Source::GroupOutput)?;
     let rollup_config = load_rollup_config(&prev_global_state.rollup_config_hash().unpack())?;
     let rollup_type_hash = load_script_hash()?.into();
 
@@ -89,10 +87,32 @@ pub fn main() -> Result<(), Error> {
     }
 
     // load rollup action
-    let mut rollup_witness_buf = [0u8; MAX_ROLLUP_WITNESS_SIZE];
-    let action = parse_rollup_action(&mut rollup_witness_buf, 0, Source::GroupOutput)?;
-    match action.to_enum() {
+    let action = parse_rollup_action(0, Source::GroupOutput)?;
+    // the pause action isn't in the RollupAction union, verify it before loading the action
+    if action.action_type() == RollupActionType::Pause {
+        verifications::pause::verify(
+            rollup_type_hash,
+            &rollup_config,
+            &prev_global_state,
+            &post_global_state,
+            prev_paused_at,
+            post_paused_at,
+        )?;
+        return Ok(());
+    }
+    let action = action.load_action()?;
+    // only the guardian can pause, submit block clears the pause after the cooldown
+    let is_submit_block = matches!(
+        action.as_reader().to_enum(),
+        RollupActionUnionReader::RollupSubmitBlock(_)
+    );
+    if !is_submit_block && prev_paused_at != post_paused_at {
+        debug!("pause is changed without the pause action");
+        return Err(Error::InvalidPostGlobalState);
+    }
+    match action.as_reader().to_enum() {
         RollupActionUnionReader::RollupSubmitBlock(args) => {
+            let reverted_block_hashes: Vec<H256> = args.reverted_block_hashes().unpack();
             // verify submit block
             verifications::submit_block::verify(
                 rollup_type_hash,
@@ -100,11 +120,14 @@ pub fn main() -> Result<(), Error> {
                 &args.block(),
                 &prev_global_state,
                 &post_global_state,
+                prev_paused_at,
+                post_paused_at,
+                &reverted_block_hashes,
             )?;
             // merkle verify reverted_block_hashes,
             // other rollup locks will check reverted blocks by compare block hash with this field
             verifications::submit_block::verify_reverted_block_hashes(
-                args.reverted_block_hashes().unpack(),
+                reverted_block_hashes,
                 args.reverted_block_proof().unpack(),
                 &prev_global_state,
             )?;
@@ -6,12 +6,12 @@ use gw_types::{
     packed::{GlobalState, RollupConfig},
     prelude::*,
 };
+use gw_utils::{block::RawL2BlockExt, cells::types::ChallengeCell, gw_types};
 use gw_utils::{
-    cells::lock_cells::{collect_burn_cells, find_challenge_cell},
+    cells::{index::TxCellsIndex, lock_cells::collect_burn_cells},
     ckb_std::{ckb_constants::Source, debug},
     error::Error,
 };
-use gw_utils::{cells::types::ChallengeCell, gw_types};
 use gw_utils::{
     gw_common,
     gw_types::packed::{RawL2Block, RollupEnterChallengeReader},
@@ -27,13 +27,15 @@ pub fn verify_enter_challenge(
     post_global_state: &GlobalState,
 ) -> Result<(), Error> {
     check_status(prev_global_state, Status::Running)?;
+    let cells = TxCellsIndex::build(&rollup_type_hash, config)?;
     // check challenge cells
-    let has_input_challenge =
-        find_challenge_cell(&rollup_type_hash, config, Source::Input)?.is_some();
+    let has_input_challenge = cells.inputs.challenge_cell()?.is_some();
     if has_input_challenge {
         return Err(Error::InvalidChallengeCell);
     }
-    let challenge_cell = find_challenge_cell(&rollup_type_hash, config, Source::Output)?
+    let challenge_cell = cells
+        .outputs
+        .challenge_cell()?
         .ok_or(Error::InvalidChallengeCell)?;
     // check that challenge target is exists
     let witness = args.witness();
@@ -74,6 +76,13 @@ pub fn verify_enter_challenge(
             if target_index >= tx_count {
                 return Err(Error::InvalidChallengeTarget);
             }
+            // deposit calls are authorized by the deposit cells instead of signatures
+            if target_type == ChallengeTargetType::TxSignature
+                && target_index < challenged_block.deposit_call_count()?
+            {
+                debug!("enter challenge, the signature target is a deposit call");
+                return Err(Error::InvalidChallengeTarget);
+            }
         }
         ChallengeTargetType::Withdrawal => {
             let withdrawal_count: u32 = challenged_block
@@ -86,7 +95,7 @@ pub fn verify_enter_challenge(
         }
     }
     // check rollup lock cells
-    check_rollup_lock_cells(&rollup_type_hash, config)?;
+    check_rollup_lock_cells(&cells)?;
     // check post global state
     let actual_post_global_state = {
         let status: u8 = Status::Halting.into();
@@ -109,23 +118,22 @@ pub fn verify_cancel_challenge(
     post_global_state: &GlobalState,
 ) -> Result<(), Error> {
     check_status(prev_global_state, Status::Halting)?;
+    let cells = TxCellsIndex::build(&rollup_type_hash, config)?;
     // check challenge cells
-    let has_input_challenge =
-        find_challenge_cell(&rollup_type_hash, config, Source::Input)?.is_some();
-    let has_output_challenge =
-        find_challenge_cell(&rollup_type_hash, config, Source::Output)?.is_some();
-    if !has_input_challenge || has_output_challenge {
-        debug!("cancel challenge, invalid challenge cell");
-        return Err(Error::InvalidChallengeCell);
-    }
+    let has_output_challenge = cells.outputs.challenge_cell()?.is_some();
+    let challenge_cell = match cells.inputs.challenge_cell()? {
+        Some(challenge_cell) if !has_output_challenge => challenge_cell,
+        _ => {
+            debug!("cancel challenge, invalid challenge cell");
+            return Err(Error::InvalidChallengeCell);
+        }
+    };
 
     // Check cancel burn
-    let challenge_cell = find_challenge_cell(&rollup_type_hash, config, Source::Input)?
-        .ok_or(Error::InvalidChallengeCell)?;
-    check_cancel_burn(config, &challenge_cell)?;
+    check_cancel_burn(config, challenge_cell)?;
 
     // check rollup lock cells
-    check_rollup_lock_cells(&rollup_type_hash, config)?;
+    check_rollup_lock_cells(&cells)?;
     // check post global state
     let actual_post_global_state = {
         let status: u8 = Status::Running.into();
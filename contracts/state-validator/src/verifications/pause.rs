@@ -0,0 +1,86 @@
+use gw_common::H256;
+use gw_types::{
+    packed::{GlobalState, RollupConfig},
+    prelude::*,
+};
+use gw_utils::{
+    cells::{index::TxCellsIndex, utils::search_lock_hash},
+    ckb_std::{
+        ckb_constants::Source,
+        debug,
+        high_level::load_input_since,
+        since::{LockValue, Since},
+    },
+    error::Error,
+    pause::{is_pause_cleared, is_paused},
+    rollup_config::RollupConfigExt,
+};
+use gw_utils::{gw_common, gw_types};
+
+use super::check_rollup_lock_cells;
+
+/// Verify the guardian pauses or unpauses the rollup
+///
+/// the global state is unchanged. Pausing records the rollup input since
+/// timestamp as paused_at, unpausing an active pause moves the paused_at back
+/// so the pause expires at the timestamp. The rollup can't be paused again
+/// until the cooldown passes, see `gw_utils::pause`
+pub fn verify(
+    rollup_type_hash: H256,
+    config: &RollupConfig,
+    prev_global_state: &GlobalState,
+    post_global_state: &GlobalState,
+    prev_paused_at: Option<u64>,
+    post_paused_at: Option<u64>,
+) -> Result<(), Error> {
+    // check guardian
+    let guardian_lock_hash = config.guardian_lock_hash()?.ok_or_else(|| {
+        debug!("rollup has no guardian");
+        Error::InvalidArgs
+    })?;
+    let max_pause_duration = config.max_pause_duration()?.ok_or_else(|| {
+        debug!("rollup has no max pause duration");
+        Error::InvalidArgs
+    })?;
+    if search_lock_hash(&guardian_lock_hash, Source::Input).is_none() {
+        debug!("guardian cell not found");
+        return Err(Error::OwnerCellNotFound);
+    }
+
+    // check rollup lock cells
+    let cells = TxCellsIndex::build(&rollup_type_hash, config)?;
+    check_rollup_lock_cells(&cells)?;
+
+    // check post global state
+    if prev_global_state.as_slice() != post_global_state.as_slice() {
+        debug!("pause action changed the global state");
+        return Err(Error::InvalidPostGlobalState);
+    }
+
+    // check paused_at
+    let rollup_input_since = Since::new(load_input_since(0, Source::GroupInput)?);
+    let rollup_input_timestamp = match rollup_input_since.extract_lock_value() {
+        Some(LockValue::Timestamp(time)) if rollup_input_since.is_absolute() => time,
+        _ => return Err(Error::InvalidSince),
+    };
+    let expected_paused_at = match prev_paused_at {
+        // unpause, the pause expires at the timestamp
+        Some(paused_at) if is_paused(config, Some(paused_at), rollup_input_timestamp)? => {
+            rollup_input_timestamp.saturating_sub(max_pause_duration)
+        }
+        Some(paused_at) if !is_pause_cleared(config, paused_at, rollup_input_timestamp)? => {
+            debug!("pause cooldown, paused at {}", paused_at);
+            return Err(Error::RollupPaused);
+        }
+        // pause
+        _ => rollup_input_timestamp,
+    };
+    if post_paused_at != Some(expected_paused_at) {
+        debug!(
+            "paused at {:?}, expected {}",
+            post_paused_at, expected_paused_at
+        );
+        return Err(Error::InvalidPostGlobalState);
+    }
+    Ok(())
+}
@@ -11,11 +11,9 @@ use gw_types::{
 use gw_utils::gw_types;
 use gw_utils::{
     cells::{
-        lock_cells::{
-            collect_burn_cells, collect_stake_cells, fetch_capacity_and_sudt_value,
-            find_challenge_cell,
-        },
-        types::ChallengeCell,
+        index::TxCellsIndex,
+        lock_cells::{collect_burn_cells, fetch_capacity_and_sudt_value},
+        types::{ChallengeCell, StakeCell},
         utils::search_lock_hashes,
     },
     ckb_std::{
@@ -50,16 +48,15 @@ fn check_challenge_maturity(
 }
 
 fn check_challenge_cell(
-    rollup_type_hash: &H256,
     config: &RollupConfig,
+    cells: &TxCellsIndex,
     challenge_cell: &ChallengeCell,
     revert_target_block_hash: &H256,
 ) -> Result<(), Error> {
     // check challenge maturity
     check_challenge_maturity(config, challenge_cell)?;
     // check other challenge cells
-    let has_output_challenge =
-        find_challenge_cell(rollup_type_hash, config, Source::Output)?.is_some();
+    let has_output_challenge = cells.outputs.challenge_cell()?.is_some();
     if has_output_challenge {
         return Err(Error::InvalidChallengeCell);
     }
@@ -90,8 +87,8 @@ pub fn get_receiver_cells_capacity(
 
 /// Check rewards
 fn check_rewards(
-    rollup_type_hash: &H256,
     config: &RollupConfig,
+    stake_cells: &[StakeCell],
     reverted_blocks: &[RawL2BlockReader],
     challenge_cell: &ChallengeCell,
 ) -> Result<(), Error> {
@@ -100,7 +97,6 @@ fn check_rewards(
         .map(|b| b.stake_cell_owner_lock_hash().to_entity())
         .collect();
 
-    let stake_cells = collect_stake_cells(rollup_type_hash, config, Source::Input)?;
     let reverted_stake_cells_set: BTreeSet<_> = stake_cells
         .iter()
         .map(|cell| cell.args.owner_lock_hash())
@@ -292,26 +288,34 @@ pub fn verify(
     // check rollup lock cells,
     // we do not handle the reverting of lock cells in here,
     // instead we handle them in the submitting layer2 block action
-    check_rollup_lock_cells_except_stake(&rollup_type_hash, config)?;
+    let cells = TxCellsIndex::build(&rollup_type_hash, config)?;
+    check_rollup_lock_cells_except_stake(&cells)?;
     // do not accept stake cells in the output
-    if !collect_stake_cells(&rollup_type_hash, config, Source::Output)?.is_empty() {
+    if !cells.outputs.stakes.is_empty() {
         return Err(Error::InvalidStakeCell);
     }
     // load reverted blocks
     let reverted_blocks_vec = revert_args.reverted_blocks();
     let reverted_blocks: Vec<_> = reverted_blocks_vec.iter().collect();
     // check challenge cells
-    let challenge_cell = find_challenge_cell(&rollup_type_hash, config, Source::Input)?
+    let challenge_cell = cells
+        .inputs
+        .challenge_cell()?
         .ok_or(Error::InvalidChallengeCell)?;
     // the first reverted block is challenged target block
     let challenged_block = reverted_blocks.get(0).ok_or(Error::InvalidRevertedBlocks)?;
     check_challenge_cell(
-        &rollup_type_hash,
         config,
-        &challenge_cell,
+        &cells,
+        challenge_cell,
         &challenged_block.hash().into(),
     )?;
-    check_rewards(&rollup_type_hash, config, &reverted_blocks, &challenge_cell)?;
+    check_rewards(
+        config,
+        &cells.inputs.stakes,
+        &reverted_blocks,
+        challenge_cell,
+    )?;
     let reverted_global_state = check_reverted_blocks(
         config,
         &reverted_blocks,
@@ -4,8 +4,7 @@ use core::result::Result;
 // Import heap related library from `alloc`
 // https://doc.rust-lang.org/alloc/index.html
 use alloc::{collections::BTreeMap, vec::Vec};
-use gw_state::ckb_smt::smt::{Pair, Tree};
-use gw_state::constants::GW_MAX_KV_PAIRS;
+use gw_state::ckb_smt::smt::{verify_update, Pair, Tree};
 use gw_utils::ckb_std::high_level::load_input_since;
 use gw_utils::ckb_std::since::{LockValue, Since};
 use gw_utils::gw_types::packed::{L2BlockReader, WithdrawalRequestReader};
@@ -20,15 +19,20 @@ use gw_utils::gw_types;
 use super::check_status;
 use crate::types::BlockContext;
 use gw_utils::{
+    block::RawL2BlockExt,
     cells::{
-        lock_cells::{
-            collect_custodian_locks, collect_deposit_locks, collect_withdrawal_locks,
-            find_block_producer_stake_cell, find_challenge_cell,
-        },
-        types::{CellValue, DepositRequestCell, WithdrawalCell},
-        utils::build_l2_sudt_script,
+        index::{RollupCells, TxCellsIndex},
+        lock_cells::{fetch_nft_type_and_data_hash, is_nft_cell},
+        message::{build_message_key, calculate_message_root, MessageCell, MESSAGE_COUNT_KEY},
+        types::{CellValue, CustodianCell, DepositRequestCell, WithdrawalCell},
+        utils::{build_l2_nft_script, build_l2_sudt_script, build_nft_owner_key},
     },
+    deposit::DepositLockArgsExt,
     error::Error,
+    pause::{clear_expired_pause, is_paused},
+    rollup_config::RollupConfigExt,
+    since::{check_since_timeout, parse_since},
+    withdrawal::{check_withdrawal_valid_until, WithdrawalRequestExt},
 };
 
 use gw_common::{
@@ -63,11 +67,118 @@ fn build_assets_map_from_cells<'a, I: Iterator<Item = &'a CellValue>>(
     Ok(assets)
 }
 
+/// A multiset to pair cells with requests,
+/// insert and remove are O(log n) instead of the O(n) of searching a Vec
+struct MultiSet<K: Ord>(BTreeMap<K, usize>);
+
+impl<K: Ord> MultiSet<K> {
+    fn new() -> Self {
+        MultiSet(BTreeMap::new())
+    }
+
+    fn insert(&mut self, key: K) {
+        *self.0.entry(key).or_insert(0) += 1;
+    }
+
+    /// remove one of the key, return false if the key isn't in the set
+    fn remove(&mut self, key: &K) -> bool {
+        match self.0.get_mut(key) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                true
+            }
+            Some(_) => {
+                self.0.remove(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.0.values().sum()
+    }
+
+    /// iterate the distinct keys and their counts
+    fn iter(&self) -> impl Iterator<Item = (&K, &usize)> {
+        self.0.iter()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Extra finality blocks of the accounts withdrawing in the block,
+/// the tiers are reached by the total capacity and the total amount of each sUDT of an account
+fn calculate_account_extra_finality_blocks(
+    config: &RollupConfig,
+    withdrawal_requests: &MultiSet<(H256, CellValue)>,
+) -> Result<BTreeMap<H256, u64>, Error> {
+    // total capacity and sUDT amounts of each account
+    let mut totals: BTreeMap<H256, (u64, BTreeMap<H256, u128>)> = BTreeMap::new();
+    for ((account_script_hash, value), count) in withdrawal_requests.iter() {
+        let (capacity, amounts) = totals.entry(*account_script_hash).or_default();
+        let count = *count as u64;
+        *capacity = value
+            .capacity
+            .checked_mul(count)
+            .and_then(|c| capacity.checked_add(c))
+            .ok_or(Error::AmountOverflow)?;
+        if !value.sudt_script_hash.is_zero() {
+            let amount = amounts.entry(value.sudt_script_hash).or_insert(0);
+            *amount = value
+                .amount
+                .checked_mul(count.into())
+                .and_then(|a| amount.checked_add(a))
+                .ok_or(Error::AmountOverflow)?;
+        }
+    }
+    let mut account_extra_finality_blocks = BTreeMap::new();
+    for (account_script_hash, (capacity, amounts)) in totals {
+        let mut extra_finality_blocks = config.withdrawal_extra_finality_blocks(&CellValue {
+            sudt_script_hash: H256::zero(),
+            amount: 0,
+            capacity,
+        })?;
+        for (sudt_script_hash, amount) in amounts {
+            let value = CellValue {
+                sudt_script_hash,
+                amount,
+                capacity,
+            };
+            extra_finality_blocks =
+                extra_finality_blocks.max(config.withdrawal_extra_finality_blocks(&value)?);
+        }
+        account_extra_finality_blocks.insert(account_script_hash, extra_finality_blocks);
+    }
+    Ok(account_extra_finality_blocks)
+}
+
 fn check_withdrawal_cells<'a>(
+    config: &RollupConfig,
     context: &BlockContext,
-    mut withdrawal_requests: Vec<WithdrawalRequestReader<'a>>,
+    withdrawal_requests: Vec<WithdrawalRequestReader<'a>>,
     withdrawal_cells: &[WithdrawalCell],
 ) -> Result<(), Error> {
+    // withdrawal requests keyed by account script hash and assets
+    let mut withdrawal_requests: MultiSet<(H256, CellValue)> = {
+        let mut set = MultiSet::new();
+        for request in withdrawal_requests {
+            let raw = request.raw();
+            let value = CellValue {
+                sudt_script_hash: raw.sudt_script_hash().unpack(),
+                amount: raw.amount().unpack(),
+                capacity: raw.capacity().unpack(),
+            };
+            set.insert((raw.account_script_hash().unpack(), value));
+        }
+        set
+    };
+    // the finality tier of an account is reached by the total withdrawn assets in the block,
+    // so splitting a large withdrawal doesn't skip the tier
+    let account_extra_finality_blocks =
+        calculate_account_extra_finality_blocks(config, &withdrawal_requests)?;
     // iter outputs withdrawal cells, check each cell has a corresponded withdrawal request
     for cell in withdrawal_cells {
         // check withdrawal cell block info
@@ -79,27 +190,25 @@ fn check_withdrawal_cells<'a>(
             return Err(Error::InvalidWithdrawalCell);
         }
 
-        let cell_account_script_hash: H256 = cell.args.account_script_hash().unpack();
+        // check withdrawal cell finality tier
+        let account_script_hash: H256 = cell.args.account_script_hash().unpack();
+        let extra_finality_blocks = account_extra_finality_blocks
+            .get(&account_script_hash)
+            .copied()
+            .unwrap_or(0);
+        if cell.extra_finality_blocks != extra_finality_blocks {
+            debug!(
+                "withdrawal cell extra finality blocks {}, expected {}",
+                cell.extra_finality_blocks, extra_finality_blocks
+            );
+            return Err(Error::InvalidWithdrawalCell);
+        }
+
         // check that there is a corresponded withdrawal request
-        match withdrawal_requests.iter().position(|request| {
-            let raw = request.raw();
-            let account_script_hash: H256 = raw.account_script_hash().unpack();
-            let sudt_script_hash: H256 = raw.sudt_script_hash().unpack();
-            let amount: u128 = raw.amount().unpack();
-            let capacity: u64 = raw.capacity().unpack();
-
-            account_script_hash == cell_account_script_hash
-                && sudt_script_hash == cell.value.sudt_script_hash
-                && amount == cell.value.amount
-                && capacity == cell.value.capacity
-        }) {
-            Some(index) => {
-                withdrawal_requests.remove(index);
-            }
-            None => {
-                debug!("withdrawal cell mismatch the amount of assets");
-                return Err(Error::InvalidWithdrawalCell);
-            }
+        let key = (account_script_hash, cell.value.clone());
+        if !withdrawal_requests.remove(&key) {
+            debug!("withdrawal cell mismatch the amount of assets");
+            return Err(Error::InvalidWithdrawalCell);
         }
     }
     // Some withdrawal requests hasn't has a corresponded withdrawal cell
@@ -113,38 +222,49 @@ fn check_withdrawal_cells<'a>(
     Ok(())
 }
 
+/// check deposit cells == unfinalized custodian cells
+fn check_deposit_cells_with_custodians(
+    deposit_cells: &[DepositRequestCell],
+    unfinalized_custodian_cells: &[&CustodianCell],
+) -> Result<(), Error> {
+    // deposit cells keyed by deposit lock args and assets
+    let mut deposit_cells: MultiSet<(Bytes, CellValue)> = {
+        let mut set = MultiSet::new();
+        for cell in deposit_cells {
+            set.insert((cell.args.as_bytes(), cell.value.clone()));
+        }
+        set
+    };
+    for custodian_cell in unfinalized_custodian_cells {
+        let key = (
+            custodian_cell.args.deposit_lock_args().as_bytes(),
+            custodian_cell.value.clone(),
+        );
+        if !deposit_cells.remove(&key) {
+            return Err(Error::InvalidCustodianCell);
+        }
+    }
+    if !deposit_cells.is_empty() {
+        return Err(Error::InvalidDepositCell);
+    }
+    Ok(())
+}
+
 fn check_input_custodian_cells(
-    config: &RollupConfig,
     context: &BlockContext,
-    output_withdrawal_cells: Vec<WithdrawalCell>,
+    cells: &TxCellsIndex,
 ) -> Result<BTreeMap<H256, u128>, Error> {
-    // collect input custodian cells
+    // input custodian cells
     let (finalized_custodian_cells, unfinalized_custodian_cells): (Vec<_>, Vec<_>) =
-        collect_custodian_locks(&context.rollup_type_hash, config, Source::Input)?
-            .into_iter()
-            .partition(|cell| {
-                let number: u64 = cell.args.deposit_block_number().unpack();
-                number <= context.finalized_number
-            });
+        cells.inputs.custodians.iter().partition(|cell| {
+            let number: u64 = cell.args.deposit_block_number().unpack();
+            number <= context.finalized_number
+        });
     // check unfinalized custodian cells == reverted deposit requests
-    let mut reverted_deposit_cells =
-        collect_deposit_locks(&context.rollup_type_hash, config, Source::Output)?;
-    for custodian_cell in unfinalized_custodian_cells {
-        let index = reverted_deposit_cells
-            .iter()
-            .position(|cell| {
-                custodian_cell.args.deposit_lock_args() == cell.args
-                    && custodian_cell.value == cell.value
-            })
-            .ok_or(Error::InvalidCustodianCell)?;
-        reverted_deposit_cells.remove(index);
-    }
-    if !reverted_deposit_cells.is_empty() {
-        return Err(Error::InvalidDepositCell);
-    }
+    check_deposit_cells_with_custodians(&cells.outputs.deposits, &unfinalized_custodian_cells)?;
     // check input finalized custodian cells >= withdrawal cells
     let withdrawal_assets =
-        build_assets_map_from_cells(output_withdrawal_cells.iter().map(|c| &c.value))?;
+        build_assets_map_from_cells(cells.outputs.withdrawals.iter().map(|c| &c.value))?;
     let mut input_finalized_assets =
         build_assets_map_from_cells(finalized_custodian_cells.iter().map(|c| &c.value))?;
     // calculate input finalized custodian assets - withdrawal assets
@@ -160,37 +280,21 @@ fn check_input_custodian_cells(
 fn check_output_custodian_cells(
     config: &RollupConfig,
     context: &BlockContext,
-    mut deposit_cells: Vec<DepositRequestCell>,
+    cells: &TxCellsIndex,
     input_finalized_assets: BTreeMap<H256, u128>,
 ) -> Result<(), Error> {
-    // collect output custodian cells
+    // output custodian cells
     let (finalized_custodian_cells, unfinalized_custodian_cells): (Vec<_>, Vec<_>) =
-        collect_custodian_locks(&context.rollup_type_hash, config, Source::Output)?
-            .into_iter()
-            .partition(|cell| {
-                let number: u64 = cell.args.deposit_block_number().unpack();
-                number <= context.finalized_number
-            });
+        cells.outputs.custodians.iter().partition(|cell| {
+            let number: u64 = cell.args.deposit_block_number().unpack();
+            number <= context.finalized_number
+        });
     // check deposits request cells == unfinalized custodian cells
-    for custodian_cell in unfinalized_custodian_cells {
-        let index = deposit_cells
-            .iter()
-            .position(|cell| {
-                custodian_cell.args.deposit_lock_args() == cell.args
-                    && custodian_cell.value == cell.value
-            })
-            .ok_or(Error::InvalidCustodianCell)?;
-        deposit_cells.remove(index);
-    }
-    if !deposit_cells.is_empty() {
-        return Err(Error::InvalidDepositCell);
-    }
+    check_deposit_cells_with_custodians(&cells.inputs.deposits, &unfinalized_custodian_cells)?;
     // check reverted withdrawals <= finalized custodian cells
     {
-        let reverted_withdrawals =
-            collect_withdrawal_locks(&context.rollup_type_hash, config, Source::Input)?;
         let reverted_withdrawal_assets =
-            build_assets_map_from_cells(reverted_withdrawals.iter().map(|c| &c.value))?;
+            build_assets_map_from_cells(cells.inputs.withdrawals.iter().map(|c| &c.value))?;
         let mut output_finalized_assets =
             build_assets_map_from_cells(finalized_custodian_cells.iter().map(|c| &c.value))?;
         // calculate output finalized assets - reverted withdrawal assets
@@ -214,6 +318,157 @@ fn check_output_custodian_cells(
             return Err(Error::InsufficientInputFinalizedAssets);
         }
     }
+    check_custodian_deposit_caps(config, cells)?;
+    Ok(())
+}
+
+/// check the custodian balance of a capped sUDT doesn't exceed the cap.
+///
+/// The balance is the sum of the output custodian cells of the block,
+/// custodian cells not consumed by the block aren't counted.
+/// A block that doesn't raise the balance is accepted, so the custodian cells
+/// of a sUDT bridged before the cap is set can still be withdrawn.
+fn check_custodian_deposit_caps(config: &RollupConfig, cells: &TxCellsIndex) -> Result<(), Error> {
+    let input_assets =
+        build_assets_map_from_cells(cells.inputs.custodians.iter().map(|c| &c.value))?;
+    let output_assets =
+        build_assets_map_from_cells(cells.outputs.custodians.iter().map(|c| &c.value))?;
+    for (sudt_script_hash, output_amount) in output_assets {
+        if sudt_script_hash.as_slice() == CKB_SUDT_SCRIPT_ARGS {
+            continue;
+        }
+        let cap = match config.sudt_deposit_cap(&sudt_script_hash)? {
+            Some(cap) => cap,
+            None => continue,
+        };
+        let input_amount = input_assets.get(&sudt_script_hash).copied().unwrap_or(0);
+        if output_amount > input_amount && output_amount > cap {
+            debug!(
+                "custodian amount {} exceeded deposit cap {} of sudt {:?}",
+                output_amount, cap, sudt_script_hash
+            );
+            return Err(Error::ExceededDepositCap);
+        }
+    }
+    Ok(())
+}
+
+/// check NFT cells are moved as they are,
+/// every output NFT cell must have the same type hash and data with an input NFT cell
+fn check_nft_cells(config: &RollupConfig, cells: &TxCellsIndex) -> Result<(), Error> {
+    let collect_nft_cells = |rollup_cells: &RollupCells, source: Source| {
+        let deposits = rollup_cells.deposits.iter().map(|c| c.index);
+        let custodians = rollup_cells.custodians.iter().map(|c| c.index);
+        let withdrawals = rollup_cells.withdrawals.iter().map(|c| c.index);
+        deposits
+            .chain(custodians)
+            .chain(withdrawals)
+            .filter_map(|index| fetch_nft_type_and_data_hash(config, index, source).transpose())
+            .collect::<Result<Vec<_>, Error>>()
+    };
+    let mut input_nft_cells: MultiSet<(H256, H256)> = MultiSet::new();
+    for nft in collect_nft_cells(&cells.inputs, Source::Input)? {
+        input_nft_cells.insert(nft);
+    }
+    for nft in collect_nft_cells(&cells.outputs, Source::Output)? {
+        if !input_nft_cells.remove(&nft) {
+            debug!("output NFT cell {:?} mismatch input NFT cells", nft.0);
+            return Err(Error::InvalidNFTCell);
+        }
+    }
+    if !input_nft_cells.is_empty() {
+        debug!(
+            "input NFT cells has no corresponded output cells: {}",
+            input_nft_cells.len()
+        );
+        return Err(Error::InvalidNFTCell);
+    }
+    Ok(())
+}
+
+/// script hash of the layer2 NFT account of a L1 NFT,
+/// return None if the rollup has no layer2 NFT validator
+fn l2_nft_script_hash(
+    rollup_type_hash: &H256,
+    config: &RollupConfig,
+    l1_nft_type_hash: &H256,
+) -> Result<Option<H256>, Error> {
+    let l2_nft_validator_script_type_hash = match config.l2_nft_validator_script_type_hash()? {
+        Some(type_hash) => type_hash,
+        None => return Ok(None),
+    };
+    let l2_nft_script = build_l2_nft_script(
+        rollup_type_hash,
+        &l2_nft_validator_script_type_hash,
+        l1_nft_type_hash,
+    );
+    Ok(Some(l2_nft_script.hash().into()))
+}
+
+/// Move a deposited NFT to its layer2 NFT account, owned by the depositor
+fn deposit_nft(
+    rollup_type_hash: &H256,
+    config: &RollupConfig,
+    kv_state: &mut KVState,
+    request: &DepositRequestCell,
+) -> Result<(), Error> {
+    let l2_nft_script_hash =
+        l2_nft_script_hash(rollup_type_hash, config, &request.value.sudt_script_hash)?
+            .ok_or(Error::InvalidNFTCell)?;
+    let nft_id = match kv_state.get_account_id_by_script_hash(&l2_nft_script_hash)? {
+        Some(id) => id,
+        None => kv_state.create_account(l2_nft_script_hash)?,
+    };
+    let nft_owner_key = build_nft_owner_key();
+    // the type hash of a NFT is unique, refuse another cell of a NFT already on layer2
+    if !kv_state.get_value(nft_id, &nft_owner_key)?.is_zero() {
+        debug!(
+            "NFT {:?} is already deposited",
+            request.value.sudt_script_hash
+        );
+        return Err(Error::InvalidNFTCell);
+    }
+    kv_state.update_value(nft_id, &nft_owner_key, request.account_script_hash)?;
+    Ok(())
+}
+
+/// Take a NFT out of its layer2 NFT account, only the owner can withdraw it
+fn withdraw_nft(
+    kv_state: &mut KVState,
+    nft_id: u32,
+    account_script_hash: &H256,
+    amount: u128,
+) -> Result<(), Error> {
+    if amount != 1 {
+        debug!("NFT withdrawal amount must be 1, got {}", amount);
+        return Err(Error::InvalidWithdrawalRequest);
+    }
+    let nft_owner_key = build_nft_owner_key();
+    let owner = kv_state.get_value(nft_id, &nft_owner_key)?;
+    if &owner != account_script_hash {
+        debug!(
+            "withdraw NFT of account {:?} by {:?}",
+            owner, account_script_hash
+        );
+        return Err(Error::InvalidWithdrawalRequest);
+    }
+    kv_state.update_value(nft_id, &nft_owner_key, H256::zero())?;
+    Ok(())
+}
+
+/// Pay fee to block producer, skip if the fee is zero
+fn pay_fee(
+    kv_state: &mut KVState,
+    payer_short_address: &[u8],
+    block_producer_short_address: &[u8],
+    sudt_id: u32,
+    amount: u128,
+) -> Result<(), Error> {
+    if amount == 0 {
+        return Ok(());
+    }
+    kv_state.burn_sudt(sudt_id, payer_short_address, amount)?;
+    kv_state.mint_sudt(sudt_id, block_producer_short_address, amount)?;
     Ok(())
 }
 
@@ -221,9 +476,44 @@ fn check_layer2_deposit(
     rollup_type_hash: &H256,
     config: &RollupConfig,
     kv_state: &mut KVState,
+    block: &L2BlockReader,
     deposit_cells: &[DepositRequestCell],
 ) -> Result<(), Error> {
+    // return ok if no deposits
+    if deposit_cells.is_empty() {
+        return Ok(());
+    }
+
+    let block_producer_script_hash = {
+        let block_producer_id = block.raw().block_producer_id().unpack();
+        kv_state.get_script_hash(block_producer_id)?
+    };
+    let block_producer_short_address = to_short_address(&block_producer_script_hash);
+
+    let min_deposit_capacity = config.min_deposit_capacity()?.unwrap_or(0);
+    let min_cancel_timeout = config.min_deposit_cancel_timeout()?.map(Since::new);
     for request in deposit_cells {
+        // reject dust deposits
+        if request.value.capacity < min_deposit_capacity {
+            debug!(
+                "deposit capacity {} is less than the minimal {}",
+                request.value.capacity, min_deposit_capacity
+            );
+            return Err(Error::InvalidDepositCell);
+        }
+        // reject deposits with malformed or too short cancel timeout
+        let cancel_timeout = Since::new(request.args.cancel_timeout().unpack());
+        parse_since(&cancel_timeout).map_err(|_| Error::InvalidDepositCell)?;
+        if let Some(min_cancel_timeout) = &min_cancel_timeout {
+            if check_since_timeout(&cancel_timeout, min_cancel_timeout).is_err() {
+                debug!(
+                    "deposit cancel timeout {:#x} is shorter than the minimal {:#x}",
+                    cancel_timeout.as_u64(),
+                    min_cancel_timeout.as_u64()
+                );
+                return Err(Error::InvalidDepositCell);
+            }
+        }
         // check that account's script is a valid EOA script
         if request.account_script.hash_type() != ScriptHashType::Type.into() {
             return Err(Error::UnknownEOAScript);
@@ -242,6 +532,15 @@ fn check_layer2_deposit(
         {
             let _new_id = kv_state.create_account(request.account_script_hash)?;
         }
+        // the fee can't exceed the deposited assets
+        let fee = request.args.fee()?;
+        if fee.ckb > request.value.capacity || fee.sudt > request.value.amount {
+            debug!(
+                "deposit fee {:?} exceeds the deposit, capacity: {} amount: {}",
+                fee, request.value.capacity, request.value.amount
+            );
+            return Err(Error::InvalidDepositCell);
+        }
         let short_address = to_short_address(&request.account_script_hash);
         // mint CKB
         kv_state.mint_sudt(
@@ -249,6 +548,14 @@ fn check_layer2_deposit(
             short_address,
             request.value.capacity.into(),
         )?;
+        // pay CKB fee
+        pay_fee(
+            kv_state,
+            short_address,
+            block_producer_short_address,
+            CKB_SUDT_ACCOUNT_ID,
+            fee.ckb.into(),
+        )?;
         if request.value.sudt_script_hash.as_slice() == CKB_SUDT_SCRIPT_ARGS {
             if request.value.amount != 0 {
                 // SUDT amount must equals to zero if sudt script hash is equals to CKB_SUDT_SCRIPT_ARGS
@@ -256,6 +563,24 @@ fn check_layer2_deposit(
             }
             continue;
         }
+        // refuse tokens not allowed to bridge
+        let is_nft = is_nft_cell(config, request.index, Source::Input)?;
+        if !config.is_l1_token_allowed(&request.value.sudt_script_hash, is_nft)? {
+            debug!(
+                "token {:?} is not allowed to deposit",
+                request.value.sudt_script_hash
+            );
+            return Err(Error::InvalidDepositCell);
+        }
+        if is_nft {
+            // a NFT can't be split to pay fee
+            if fee.sudt != 0 {
+                debug!("NFT deposit with sudt fee {}", fee.sudt);
+                return Err(Error::InvalidDepositCell);
+            }
+            deposit_nft(rollup_type_hash, config, kv_state, request)?;
+            continue;
+        }
         // find or create Simple UDT account
         let l2_sudt_script =
             build_l2_sudt_script(rollup_type_hash, config, &request.value.sudt_script_hash);
@@ -270,6 +595,14 @@ fn check_layer2_deposit(
         }
         // mint SUDT
         kv_state.mint_sudt(sudt_id, short_address, request.value.amount)?;
+        // pay SUDT fee
+        pay_fee(
+            kv_state,
+            short_address,
+            block_producer_short_address,
+            sudt_id,
+            fee.sudt,
+        )?;
     }
 
     Ok(())
@@ -278,22 +611,10 @@ fn check_layer2_deposit(
 fn check_layer2_withdrawal(
     rollup_type_hash: &H256,
     config: &RollupConfig,
+    context: &BlockContext,
     kv_state: &mut KVState,
     block: &L2BlockReader,
 ) -> Result<(), Error> {
-    /// Pay fee to block producer
-    fn pay_fee(
-        kv_state: &mut KVState,
-        payer_short_address: &[u8],
-        block_producer_short_address: &[u8],
-        sudt_id: u32,
-        amount: u128,
-    ) -> Result<(), Error> {
-        kv_state.burn_sudt(sudt_id, payer_short_address, amount)?;
-        kv_state.mint_sudt(sudt_id, block_producer_short_address, amount)?;
-        Ok(())
-    }
-
     let withdrawals = block.withdrawals();
     // return ok if no withdrawals
     if withdrawals.is_empty() {
@@ -307,9 +628,11 @@ fn check_layer2_withdrawal(
     let block_producer_short_address = to_short_address(&block_producer_script_hash);
 
     for request in withdrawals.iter() {
+        // refuse expired withdrawals
+        if let Some(valid_until) = request.valid_until()? {
+            check_withdrawal_valid_until(&valid_until, context.number, context.timestamp)?;
+        }
         let raw = request.raw();
-        let l2_sudt_script_hash: [u8; 32] =
-            build_l2_sudt_script(rollup_type_hash, config, &raw.sudt_script_hash().unpack()).hash();
         // find EOA
         let account_script_hash: H256 = raw.account_script_hash().unpack();
         let id = kv_state
@@ -335,12 +658,28 @@ fn check_layer2_withdrawal(
             short_address,
             raw.capacity().unpack() as u128,
         )?;
-        // find Simple UDT account
-        let sudt_id = kv_state
-            .get_account_id_by_script_hash(&l2_sudt_script_hash.into())?
-            .ok_or(StateError::MissingKey)?;
-        // burn sudt
-        kv_state.burn_sudt(sudt_id, short_address, raw.amount().unpack())?;
+        let sudt_script_hash: H256 = raw.sudt_script_hash().unpack();
+        let amount: u128 = raw.amount().unpack();
+        let nft_id = if sudt_script_hash.as_slice() == CKB_SUDT_SCRIPT_ARGS {
+            None
+        } else {
+            match l2_nft_script_hash(rollup_type_hash, config, &sudt_script_hash)? {
+                Some(script_hash) => kv_state.get_account_id_by_script_hash(&script_hash)?,
+                None => None,
+            }
+        };
+        if let Some(nft_id) = nft_id {
+            withdraw_nft(kv_state, nft_id, &account_script_hash, amount)?;
+        } else {
+            // find Simple UDT account
+            let l2_sudt_script_hash: [u8; 32] =
+                build_l2_sudt_script(rollup_type_hash, config, &sudt_script_hash).hash();
+            let sudt_id = kv_state
+                .get_account_id_by_script_hash(&l2_sudt_script_hash.into())?
+                .ok_or(StateError::MissingKey)?;
+            // burn sudt
+            kv_state.burn_sudt(sudt_id, short_address, amount)?;
+        }
         // update nonce
         let nonce = kv_state.get_nonce(id)?;
         let withdrawal_nonce: u32 = raw.nonce().unpack();
@@ -356,7 +695,6 @@ fn check_layer2_withdrawal(
 fn load_block_context_and_state<'a>(
     rollup_type_hash: H256,
     config: &RollupConfig,
-    tree_buffer: &'a mut [Pair],
     kv_state_proof: &'a Bytes,
     l2block: &L2BlockReader,
     prev_global_state: &GlobalState,
@@ -384,53 +722,31 @@ fn load_block_context_and_state<'a>(
         return Err(Error::InvalidBlock);
     }
 
-    // verify prev block merkle proof
-    let block_smt_key = RawL2Block::compute_smt_key(number);
-    let block_proof: Bytes = l2block.block_proof().unpack();
-    {
-        let prev_block_root: [u8; 32] = prev_global_state.block().merkle_root().unpack();
-
-        let mut buf = [Pair::default(); 256];
-        let mut block_tree = Tree::new(&mut buf);
-        block_tree
-            .update(&block_smt_key, &H256::zero().into())
-            .map_err(|err| {
-                debug!("[verify block exist] update kv error: {}", err);
-                Error::MerkleProof
-            })?;
-        block_tree
-            .verify(&prev_block_root, &block_proof)
-            .map_err(|err| {
-                debug!("[verify block exist] merkle verify error: {}", err);
-                Error::MerkleProof
-            })?;
-    }
-
     // Check post block merkle proof
     if number + 1 != post_global_state.block().count().unpack() {
         debug!("[check block context] post global state block count error");
         return Err(Error::InvalidBlock);
     }
 
+    // verify the block is appended to the block merkle tree,
+    // from an empty leaf under the prev root to the block hash under the post root
+    let block_smt_key = RawL2Block::compute_smt_key(number);
+    let block_proof: Bytes = l2block.block_proof().unpack();
+    let prev_block_root: [u8; 32] = prev_global_state.block().merkle_root().unpack();
     let post_block_root: [u8; 32] = post_global_state.block().merkle_root().unpack();
     let block_hash: H256 = raw_block.hash().into();
-    // verify prev block merkle proof
-    {
-        let mut buf = [Pair::default(); 256];
-        let mut block_tree = Tree::new(&mut buf);
-        block_tree
-            .update(&block_smt_key, &block_hash.into())
-            .map_err(|err| {
-                debug!("[check block context] update kv error: {}", err);
-                Error::MerkleProof
-            })?;
-        block_tree
-            .verify(&post_block_root, &block_proof)
-            .map_err(|err| {
-                debug!("[check block context] merkle verify error: {}", err);
-                Error::MerkleProof
-            })?;
-    }
+    verify_update(
+        &block_smt_key,
+        &H256::zero().into(),
+        &block_hash.into(),
+        &prev_block_root,
+        &post_block_root,
+        &block_proof,
+    )
+    .map_err(|err| {
+        debug!("[check block context] block merkle verify error: {}", err);
+        Error::MerkleProof
+    })?;
 
     // Check prev account state
     if raw_block.prev_account().as_slice() != prev_global_state.account().as_slice() {
@@ -450,8 +766,7 @@ fn load_block_context_and_state<'a>(
     let finalized_number = number.saturating_sub(config.finality_blocks().unpack());
 
     // Check pre account merkle proof
-    let kv_state = KVState::build(
-        tree_buffer,
+    let kv_state = KVState::build_growable(
         l2block.kv_state(),
         kv_state_proof,
         account_count,
@@ -476,19 +791,16 @@ fn load_block_context_and_state<'a>(
 
 fn verify_block_producer(
     config: &RollupConfig,
-    context: &BlockContext,
+    cells: &TxCellsIndex,
     block: &L2BlockReader,
 ) -> Result<(), Error> {
     let raw_block = block.raw();
     let owner_lock_hash = raw_block.stake_cell_owner_lock_hash();
     // make sure we have one stake cell in the output
-    let output_stake_cell = find_block_producer_stake_cell(
-        &context.rollup_type_hash,
-        config,
-        Source::Output,
-        &owner_lock_hash,
-    )?
-    .ok_or(Error::InvalidStakeCell)?;
+    let output_stake_cell = cells
+        .outputs
+        .block_producer_stake_cell(&owner_lock_hash)?
+        .ok_or(Error::InvalidStakeCell)?;
     // check stake cell capacity
     let required_staking_capacity: u64 = config.required_staking_capacity().unpack();
     if output_stake_cell.capacity < required_staking_capacity {
@@ -499,12 +811,7 @@ fn verify_block_producer(
         return Err(Error::InvalidStakeCell);
     }
     // make sure input stake cell is identical to the output stake cell if we have one
-    if let Some(input_stake_cell) = find_block_producer_stake_cell(
-        &context.rollup_type_hash,
-        config,
-        Source::Input,
-        &owner_lock_hash,
-    )? {
+    if let Some(input_stake_cell) = cells.inputs.block_producer_stake_cell(&owner_lock_hash)? {
         let expected_stake_lock_args = input_stake_cell
             .args
             .as_builder()
@@ -521,6 +828,45 @@ fn verify_block_producer(
     Ok(())
 }
 
+/// Check block limits of the rollup config,
+/// to ensure every block can be challenged within the cycles limit
+fn check_block_limits(
+    config: &RollupConfig,
+    block: &L2BlockReader,
+    cells: &TxCellsIndex,
+) -> Result<(), Error> {
+    let limits = [
+        (
+            "txs",
+            block.transactions().len(),
+            config.max_txs_per_block()?,
+        ),
+        (
+            "withdrawals",
+            block.withdrawals().len(),
+            config.max_withdrawals_per_block()?,
+        ),
+        (
+            "deposits",
+            cells.inputs.deposits.len(),
+            config.max_deposits_per_block()?,
+        ),
+        ("kv pairs", block.kv_state().len(), config.max_kv_pairs()?),
+    ];
+    for (name, count, limit) in limits.iter() {
+        if let Some(limit) = limit {
+            if *count > *limit as usize {
+                debug!(
+                    "[check block limits] exceeded max {}: {}, limit: {}",
+                    name, count, limit
+                );
+                return Err(Error::ExceededBlockLimit);
+            }
+        }
+    }
+    Ok(())
+}
+
 fn check_state_checkpoints(block: &L2BlockReader) -> Result<(), Error> {
     let raw_block = block.raw();
     let checkpoint_list = raw_block.state_checkpoint_list();
@@ -662,6 +1008,168 @@ fn check_block_withdrawals(block: &L2BlockReader) -> Result<(), Error> {
     Ok(())
 }
 
+/// Pending messages consumed by the block, the delivered messages are restored by reverts
+fn pending_input_messages(cells: &TxCellsIndex) -> Vec<&MessageCell> {
+    cells
+        .inputs
+        .messages
+        .iter()
+        .filter(|cell| cell.args.delivered_block.is_none())
+        .collect()
+}
+
+/// Check the message root commits the pending message cells consumed by the block
+fn check_block_messages(block: &L2BlockReader, cells: &TxCellsIndex) -> Result<(), Error> {
+    let message_cells = pending_input_messages(cells);
+    let message_root = block.raw().message_root()?;
+    match message_root {
+        None if message_cells.is_empty() => Ok(()),
+        Some(message_root) if !message_cells.is_empty() => {
+            if message_root != calculate_message_root(message_cells.iter().copied())? {
+                debug!("failed to check block message_root");
+                return Err(Error::MerkleProof);
+            }
+            Ok(())
+        }
+        _ => {
+            debug!(
+                "mismatch message root {:?}, messages: {}",
+                message_root,
+                message_cells.len()
+            );
+            Err(Error::InvalidBlock)
+        }
+    }
+}
+
+/// Check the message cells of the block
+///
+/// 1. every pending input message is delivered by the block, and moved to an output message
+///    cell marked with the block, which holds the capacity for the owner
+/// 2. every input message delivered by a reverted block is restored as a pending message
+fn check_message_cells(
+    context: &BlockContext,
+    cells: &TxCellsIndex,
+    reverted_block_hashes: &[H256],
+) -> Result<(), Error> {
+    let mut delivered_messages = MultiSet::new();
+    let mut restored_messages = MultiSet::new();
+    for cell in &cells.inputs.messages {
+        match &cell.args.delivered_block {
+            None => delivered_messages.insert(cell.content()),
+            Some(delivered_block) => {
+                if !reverted_block_hashes.contains(&delivered_block.hash) {
+                    debug!("message of block {:?} isn't reverted", delivered_block.hash);
+                    return Err(Error::InvalidMessageCell);
+                }
+                restored_messages.insert(cell.content())
+            }
+        }
+    }
+    for cell in &cells.outputs.messages {
+        let matched = match &cell.args.delivered_block {
+            None => restored_messages.remove(&cell.content()),
+            Some(delivered_block) => {
+                delivered_block.hash == context.block_hash
+                    && delivered_block.number == context.number
+                    && delivered_messages.remove(&cell.content())
+            }
+        };
+        if !matched {
+            debug!("output message cell {} mismatch input messages", cell.index);
+            return Err(Error::InvalidMessageCell);
+        }
+    }
+    if !delivered_messages.is_empty() || !restored_messages.is_empty() {
+        debug!("input message cells have no corresponded output cells");
+        return Err(Error::InvalidMessageCell);
+    }
+    Ok(())
+}
+
+/// Deliver the pending messages to the receivers, in the order of the message root
+fn deliver_messages(kv_state: &mut KVState, cells: &TxCellsIndex) -> Result<(), Error> {
+    let account_count = kv_state.get_account_count()?;
+    for cell in pending_input_messages(cells) {
+        let to_id = cell.args.to_id;
+        if to_id >= account_count {
+            debug!("message receiver {} isn't exist", to_id);
+            return Err(Error::InvalidMessageCell);
+        }
+        let count = {
+            let value = kv_state.get_value(to_id, &MESSAGE_COUNT_KEY.into())?;
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&value.as_slice()[..8]);
+            u64::from_le_bytes(buf)
+        };
+        kv_state.update_value(to_id, &build_message_key(count), cell.hash())?;
+        let mut value = [0u8; 32];
+        value[..8].copy_from_slice(&(count + 1).to_le_bytes());
+        kv_state.update_value(to_id, &MESSAGE_COUNT_KEY.into(), value.into())?;
+    }
+    Ok(())
+}
+
+/// Check the leading transactions of the block are the calls of the deposit cells,
+/// in the order of the deposits. The calls are executed as layer2 transactions,
+/// so the execution can be challenged, see `gw_utils::deposit`
+/// check the leading transactions of the block are the calls of the deposits.
+///
+/// A call is authorized by the deposit cell instead of a layer2 signature,
+/// so the sender account must be created by the deposit of the call,
+/// and no other deposit of the block goes to the account.
+/// Otherwise anyone could send a call from an existing account.
+fn check_block_deposit_calls(
+    block: &L2BlockReader,
+    kv_state: &KVState,
+    deposit_cells: &[DepositRequestCell],
+    prev_account_count: u32,
+) -> Result<(), Error> {
+    let transactions = block.transactions();
+    let mut call_count = 0;
+    for cell in deposit_cells {
+        let call = match cell.args.call()? {
+            Some(call) => call,
+            None => continue,
+        };
+        let from_id = kv_state
+            .get_account_id_by_script_hash(&cell.account_script_hash)?
+            .ok_or(Error::AccountNotFound)?;
+        let deposit_count = deposit_cells
+            .iter()
+            .filter(|c| c.account_script_hash == cell.account_script_hash)
+            .count();
+        if from_id < prev_account_count || deposit_count != 1 {
+            debug!(
+                "deposit {} calls from account {} which isn't created by the deposit",
+                cell.index, from_id
+            );
+            return Err(Error::InvalidDepositCell);
+        }
+        let is_call = transactions
+            .get(call_count)
+            .map(|tx| call.is_sent_by(&tx.raw(), from_id))
+            .unwrap_or(false);
+        if !is_call {
+            debug!(
+                "transaction {} isn't the call of deposit {}",
+                call_count, cell.index
+            );
+            return Err(Error::InvalidBlock);
+        }
+        call_count += 1;
+    }
+    let deposit_call_count = block.raw().deposit_call_count()?;
+    if deposit_call_count as usize != call_count {
+        debug!(
+            "mismatch deposit call count {}, expected: {}",
+            deposit_call_count, call_count
+        );
+        return Err(Error::InvalidBlock);
+    }
+    Ok(())
+}
+
 fn check_block_timestamp(
     prev_global_state: &GlobalState,
     post_global_state: &GlobalState,
@@ -715,6 +1223,36 @@ fn check_block_timestamp(
     Ok(())
 }
 
+/// A v0 global state skips `check_block_timestamp`, the block timestamp is
+/// chosen by the block producer, refuse the checks that rely on it
+fn check_unverified_block_timestamp(
+    config: &RollupConfig,
+    block: &L2BlockReader,
+    post_global_state: &GlobalState,
+) -> Result<(), Error> {
+    let post_version: u8 = post_global_state.version().into();
+    if post_version > 0 {
+        return Ok(());
+    }
+
+    // the pause expires by the block timestamp
+    if config.guardian_lock_hash()?.is_some() {
+        debug!("v0 global state can't be used with a guardian");
+        return Err(Error::InvalidPostGlobalState);
+    }
+
+    for request in block.withdrawals().iter() {
+        if let Some(valid_until) = request.valid_until()? {
+            if let Some(LockValue::Timestamp(_)) = valid_until.extract_lock_value() {
+                debug!("v0 block can't include a withdrawal valid until a timestamp");
+                return Err(Error::InvalidWithdrawalRequest);
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Verify Deposit & Withdrawal
 pub fn verify(
     rollup_type_hash: H256,
@@ -722,56 +1260,89 @@ pub fn verify(
     block: &L2BlockReader,
     prev_global_state: &GlobalState,
     post_global_state: &GlobalState,
+    prev_paused_at: Option<u64>,
+    post_paused_at: Option<u64>,
+    reverted_block_hashes: &[H256],
 ) -> Result<(), Error> {
     check_status(prev_global_state, Status::Running)?;
 
+    // collect rollup lock cells of the transaction
+    let cells = TxCellsIndex::build(&rollup_type_hash, config)?;
+
+    // check block limits before the expensive verifications
+    check_block_limits(config, block, &cells)?;
+
     // check checkpoints
     check_state_checkpoints(block)?;
 
     // Check withdrawals root
     check_block_withdrawals(block)?;
 
-    let mut tree_buffer = [Pair::default(); GW_MAX_KV_PAIRS];
+    // Check L1 -> L2 messages root
+    check_block_messages(block, &cells)?;
+
     let kv_state_proof: Bytes = block.kv_state_proof().unpack();
 
     let (context, mut kv_state) = load_block_context_and_state(
         rollup_type_hash,
         config,
-        &mut tree_buffer,
         &kv_state_proof,
         block,
         prev_global_state,
         post_global_state,
     )?;
+    check_unverified_block_timestamp(config, block, post_global_state)?;
+    // refuse withdrawals and deposits while paused, L2 transactions are still allowed
+    if is_paused(config, prev_paused_at, context.timestamp)?
+        && (!block.withdrawals().is_empty() || !cells.inputs.deposits.is_empty())
+    {
+        debug!("rollup is paused, refuse withdrawals and deposits");
+        return Err(Error::RollupPaused);
+    }
+    // clear the paused_at after the cooldown
+    if post_paused_at != clear_expired_pause(config, prev_paused_at, context.timestamp)? {
+        debug!("invalid post paused at {:?}", post_paused_at);
+        return Err(Error::InvalidPostGlobalState);
+    }
     // Verify block producer
-    verify_block_producer(config, &context, block)?;
-    // collect withdrawal cells
-    let withdrawal_cells: Vec<_> =
-        collect_withdrawal_locks(&context.rollup_type_hash, config, Source::Output)?;
-    // collect deposit cells
-    let deposit_cells = collect_deposit_locks(&context.rollup_type_hash, config, Source::Input)?;
+    verify_block_producer(config, &cells, block)?;
     // Check new cells and reverted cells: deposit / withdrawal / custodian
     let withdrawal_requests_vec = block.withdrawals();
     let withdrawal_requests = withdrawal_requests_vec.iter().collect();
-    check_withdrawal_cells(&context, withdrawal_requests, &withdrawal_cells)?;
-    let input_finalized_assets = check_input_custodian_cells(config, &context, withdrawal_cells)?;
-    check_output_custodian_cells(
+    check_withdrawal_cells(
         config,
         &context,
-        deposit_cells.clone(),
-        input_finalized_assets,
+        withdrawal_requests,
+        &cells.outputs.withdrawals,
     )?;
+    let input_finalized_assets = check_input_custodian_cells(&context, &cells)?;
+    check_output_custodian_cells(config, &context, &cells, input_finalized_assets)?;
+    check_nft_cells(config, &cells)?;
+    check_message_cells(&context, &cells, reverted_block_hashes)?;
     // Ensure no challenge cells in submitting block transaction
-    if find_challenge_cell(&rollup_type_hash, config, Source::Input)?.is_some()
-        || find_challenge_cell(&rollup_type_hash, config, Source::Output)?.is_some()
-    {
+    if cells.inputs.challenge_cell()?.is_some() || cells.outputs.challenge_cell()?.is_some() {
         return Err(Error::InvalidChallengeCell);
     }
 
     // Withdrawal token: Layer2 SUDT -> withdrawals
-    check_layer2_withdrawal(&rollup_type_hash, config, &mut kv_state, block)?;
+    check_layer2_withdrawal(&rollup_type_hash, config, &context, &mut kv_state, block)?;
     // Mint token: deposit requests -> layer2 SUDT
-    check_layer2_deposit(&rollup_type_hash, config, &mut kv_state, &deposit_cells)?;
+    check_layer2_deposit(
+        &rollup_type_hash,
+        config,
+        &mut kv_state,
+        block,
+        &cells.inputs.deposits,
+    )?;
+    // Deliver L1 -> L2 messages
+    deliver_messages(&mut kv_state, &cells)?;
+    // Check deposit calls: the leading transactions
+    check_block_deposit_calls(
+        block,
+        &kv_state,
+        &cells.inputs.deposits,
+        prev_global_state.account().count().unpack(),
+    )?;
     // Check transactions
     check_block_transactions(block, &kv_state)?;
 
@@ -1,45 +1,96 @@
-//! Secp256k1 Eth implementation
+//! Secp256k1 Tron implementation
 
-use gw_utils::{ckb_std::debug, error::Error, gw_common::H256, gw_types::bytes::Bytes};
+use gw_utils::{
+    account_lock::{AccountLock, SIGNATURE_SIZE},
+    ckb_std::debug,
+    error::Error,
+    gw_common::H256,
+    gw_types::{bytes::Bytes, prelude::*},
+    signature::TypedTransaction,
+};
 use secp256k1_utils::recover_uncompressed_key;
 use sha3::{Digest, Keccak256};
 
 pub type TronAddress = [u8; 20];
 
-pub fn extract_lock_args(lock_args: Bytes) -> Result<(H256, TronAddress), Error> {
-    if lock_args.len() != 52 {
-        debug!("Invalid lock args len: {}", lock_args.len());
-        return Err(Error::InvalidArgs);
-    }
-    let rollup_script_hash = {
-        let mut buf = [0u8; 32];
-        buf.copy_from_slice(&lock_args[..32]);
-        buf.into()
-    };
-    let address = {
-        let mut buf = [0u8; 20];
-        buf.copy_from_slice(&lock_args[32..]);
-        buf
-    };
-    Ok((rollup_script_hash, address))
+/// TIP-712 domain, the rollup type hash is used as salt
+const DOMAIN_TYPE: &str = "EIP712Domain(string name,string version,bytes32 salt)";
+const DOMAIN_NAME: &str = "Godwoken";
+const DOMAIN_VERSION: &str = "1";
+const TRANSACTION_TYPE: &str = "Transaction(bytes32 sender,bytes32 receiver,uint32 fromId,uint32 toId,uint32 nonce,bytes args)";
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(data);
+    let buf = hasher.finalize();
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&buf[..]);
+    hash
+}
+
+/// ABI encode uint32 to a 32 bytes word
+fn encode_u32(n: u32) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[28..].copy_from_slice(&n.to_be_bytes());
+    word
 }
 
 #[derive(Default)]
 pub struct Secp256k1Tron;
 
-impl Secp256k1Tron {
-    pub fn verify_alone(
-        &self,
-        address: TronAddress,
-        mut signature: [u8; 65],
-        message: H256,
-    ) -> Result<bool, Error> {
-        // rewrite rec_id
-        signature[64] = match signature[64] {
-            28 => 1,
-            _ => 0,
+impl AccountLock for Secp256k1Tron {
+    type Address = TronAddress;
+
+    fn prefixed_message(&self, message: &H256) -> H256 {
+        let mut hasher = Keccak256::new();
+        hasher.update("\x19TRON Signed Message:\n32");
+        hasher.update(message.as_slice());
+        let buf = hasher.finalize();
+        let mut signing_message = [0u8; 32];
+        signing_message.copy_from_slice(&buf[..]);
+        H256::from(signing_message)
+    }
+
+    /// TIP-712 typed data hash
+    /// keccak256("\x19\x01" | domain_separator | hash_struct(transaction))
+    fn typed_data_message(&self, typed_tx: &TypedTransaction) -> Result<H256, Error> {
+        let domain_separator = {
+            let mut hasher = Keccak256::new();
+            hasher.update(&keccak256(DOMAIN_TYPE.as_bytes()));
+            hasher.update(&keccak256(DOMAIN_NAME.as_bytes()));
+            hasher.update(&keccak256(DOMAIN_VERSION.as_bytes()));
+            hasher.update(&typed_tx.rollup_type_hash);
+            hasher.finalize()
+        };
+        let struct_hash = {
+            let raw_tx = &typed_tx.raw_tx;
+            let args: Bytes = raw_tx.args().unpack();
+            let mut hasher = Keccak256::new();
+            hasher.update(&keccak256(TRANSACTION_TYPE.as_bytes()));
+            hasher.update(&typed_tx.sender_script_hash);
+            hasher.update(&typed_tx.receiver_script_hash);
+            hasher.update(&encode_u32(raw_tx.from_id().unpack()));
+            hasher.update(&encode_u32(raw_tx.to_id().unpack()));
+            hasher.update(&encode_u32(raw_tx.nonce().unpack()));
+            hasher.update(&keccak256(&args));
+            hasher.finalize()
         };
-        let pubkey = recover_uncompressed_key(message.into(), signature).map_err(|err| {
+        let mut hasher = Keccak256::new();
+        hasher.update(b"\x19\x01");
+        hasher.update(&domain_separator);
+        hasher.update(&struct_hash);
+        let buf = hasher.finalize();
+        let mut signing_message = [0u8; 32];
+        signing_message.copy_from_slice(&buf[..]);
+        Ok(H256::from(signing_message))
+    }
+
+    fn recover_address(
+        &self,
+        message: &H256,
+        signature: &[u8; SIGNATURE_SIZE],
+    ) -> Result<TronAddress, Error> {
+        let pubkey = recover_uncompressed_key((*message).into(), *signature).map_err(|err| {
             debug!("failed to recover secp256k1 pubkey, error number: {}", err);
             Error::WrongSignature
         })?;
@@ -51,26 +102,6 @@ impl Secp256k1Tron {
             pubkey_hash.copy_from_slice(&buf[12..]);
             pubkey_hash
         };
-        if pubkey_hash != address {
-            return Ok(false);
-        }
-        Ok(true)
-    }
-
-    pub fn verify_message(
-        &self,
-        address: TronAddress,
-        signature: [u8; 65],
-        message: H256,
-    ) -> Result<bool, Error> {
-        let mut hasher = Keccak256::new();
-        hasher.update("\x19TRON Signed Message:\n32");
-        hasher.update(message.as_slice());
-        let buf = hasher.finalize();
-        let mut signing_message = [0u8; 32];
-        signing_message.copy_from_slice(&buf[..]);
-        let signing_message = H256::from(signing_message);
-
-        self.verify_alone(address, signature, signing_message)
+        Ok(pubkey_hash)
     }
 }
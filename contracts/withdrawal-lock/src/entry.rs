@@ -1,37 +1,46 @@
+use alloc::vec::Vec;
 // Import from `core` instead of from `std` since we are in no-std mode
 use core::result::Result;
 
-use gw_utils::{
-    cells::rollup::MAX_ROLLUP_WITNESS_SIZE,
-    gw_types::{
-        self,
-        core::ScriptHashType,
-        packed::{
-            CustodianLockArgs, CustodianLockArgsReader, RollupActionUnionReader,
-            UnlockWithdrawalWitnessUnion, WithdrawalLockArgs, WithdrawalLockArgsReader,
-        },
+use gw_utils::gw_types::{
+    self,
+    core::ScriptHashType,
+    packed::{
+        CustodianLockArgs, CustodianLockArgsReader, GlobalState, UnlockWithdrawalWitnessUnion,
+        WithdrawalLockArgs,
     },
 };
 use gw_utils::{
     cells::{
         rollup::{
             load_rollup_config, parse_rollup_action, search_rollup_cell, search_rollup_state,
+            search_rollup_state_with_pause,
         },
         token::fetch_token_amount_by_lock_hash,
         token::TokenType,
         utils::search_lock_hash,
     },
     ckb_std::high_level::load_cell_lock,
+    pause::check_not_paused,
+    withdrawal::parse_withdrawal_lock_args,
 };
 
 // Import CKB syscalls and structures
 // https://nervosnetwork.github.io/ckb-std/riscv64imac-unknown-none-elf/doc/ckb_std/index.html
 use crate::ckb_std::{
     ckb_constants::Source,
-    ckb_types::{self, bytes::Bytes, prelude::Unpack as CKBUnpack},
+    ckb_types::{
+        self,
+        bytes::Bytes,
+        prelude::{
+            Builder as CKBBuilder, Entity as CKBEntity, Pack as CKBPack, Unpack as CKBUnpack,
+        },
+    },
     high_level::{
-        load_cell_capacity, load_cell_data, load_cell_type_hash, load_script, load_witness_args,
+        load_cell_capacity, load_cell_data, load_cell_type_hash, load_input_since, load_script,
+        load_witness_args, QueryIter,
     },
+    since::{LockValue, Since},
 };
 
 use crate::error::Error;
@@ -43,28 +52,39 @@ use gw_types::{
 const FINALIZED_BLOCK_NUMBER: u64 = 0;
 const FINALIZED_BLOCK_HASH: [u8; 32] = [0u8; 32];
 
-/// args: rollup_type_hash | withdrawal lock args
+/// args: rollup_type_hash | withdrawal lock args | extra finality blocks (optional)
 fn parse_lock_args(
     script: &ckb_types::packed::Script,
-) -> Result<([u8; 32], WithdrawalLockArgs), Error> {
+) -> Result<([u8; 32], WithdrawalLockArgs, u64), Error> {
     let mut rollup_type_hash = [0u8; 32];
     let args: Bytes = script.args().unpack();
     if args.len() < rollup_type_hash.len() {
         return Err(Error::InvalidArgs);
     }
     rollup_type_hash.copy_from_slice(&args[..32]);
-    match WithdrawalLockArgsReader::verify(&args.slice(32..), false) {
-        Ok(()) => Ok((
-            rollup_type_hash,
-            WithdrawalLockArgs::new_unchecked(args.slice(32..)),
-        )),
+    match parse_withdrawal_lock_args(&args[32..]) {
+        Ok((lock_args, extra_finality_blocks)) => {
+            Ok((rollup_type_hash, lock_args, extra_finality_blocks))
+        }
         Err(_) => Err(Error::InvalidArgs),
     }
 }
 
+/// The timestamp to check the pause, the later one of the tip block timestamp
+/// and the absolute timestamp of the input since, see `gw_utils::pause`
+fn load_pause_check_timestamp(global_state: &GlobalState) -> Result<u64, Error> {
+    let tip_block_timestamp: u64 = global_state.tip_block_timestamp().unpack();
+    let since = Since::new(load_input_since(0, Source::GroupInput)?);
+    let since_timestamp = match since.extract_lock_value() {
+        Some(LockValue::Timestamp(time)) if since.is_absolute() => time,
+        _ => 0,
+    };
+    Ok(tip_block_timestamp.max(since_timestamp))
+}
+
 pub fn main() -> Result<(), Error> {
     let script = load_script()?;
-    let (rollup_type_hash, lock_args) = parse_lock_args(&script)?;
+    let (rollup_type_hash, lock_args, extra_finality_blocks) = parse_lock_args(&script)?;
 
     // load unlock arguments from witness
     let witness_args = load_witness_args(0, Source::GroupInput)?;
@@ -83,27 +103,22 @@ pub fn main() -> Result<(), Error> {
     // execute verification
     match unlock_args.to_enum() {
         UnlockWithdrawalWitnessUnion::UnlockWithdrawalViaRevert(unlock_args) => {
-            let mut rollup_action_witness = [0u8; MAX_ROLLUP_WITNESS_SIZE];
             let withdrawal_block_hash = lock_args.withdrawal_block_hash();
             // prove the block is reverted
             let rollup_action = {
                 let index = search_rollup_cell(&rollup_type_hash, Source::Output)
                     .ok_or(Error::RollupCellNotFound)?;
-                parse_rollup_action(&mut rollup_action_witness, index, Source::Output)?
+                parse_rollup_action(index, Source::Output)?
             };
-            match rollup_action.to_enum() {
-                RollupActionUnionReader::RollupSubmitBlock(args) => {
-                    if !args
-                        .reverted_block_hashes()
-                        .iter()
-                        .any(|hash| hash.as_slice() == withdrawal_block_hash.as_slice())
-                    {
-                        return Err(Error::InvalidRevertedBlocks);
-                    }
-                }
-                _ => {
-                    return Err(Error::InvalidRevertedBlocks);
-                }
+            let reverted_block_hashes = rollup_action
+                .load_reverted_block_hashes()?
+                .ok_or(Error::InvalidRevertedBlocks)?;
+            if !reverted_block_hashes
+                .as_reader()
+                .iter()
+                .any(|hash| hash.as_slice() == withdrawal_block_hash.as_slice())
+            {
+                return Err(Error::InvalidRevertedBlocks);
             }
             let custodian_lock_hash: [u8; 32] = unlock_args.custodian_lock_hash().unpack();
             // check there are a reverted custodian lock in the output
@@ -124,7 +139,7 @@ pub fn main() -> Result<(), Error> {
                     return Err(Error::InvalidArgs);
                 }
 
-                match CustodianLockArgsReader::verify(&args.slice(32..), false) {
+                match CustodianLockArgsReader::verify(&args.slice(32..), true) {
                     Ok(_) => CustodianLockArgs::new_unchecked(args.slice(32..)),
                     Err(_) => return Err(Error::InvalidOutput),
                 }
@@ -151,20 +166,29 @@ pub fn main() -> Result<(), Error> {
         }
         UnlockWithdrawalWitnessUnion::UnlockWithdrawalViaFinalize(_unlock_args) => {
             // try search rollup state from deps
-            let global_state = match search_rollup_state(&rollup_type_hash, Source::CellDep)? {
-                Some(state) => state,
-                None => {
-                    // then try search rollup state from inputs
-                    search_rollup_state(&rollup_type_hash, Source::Input)?
-                        .ok_or(Error::RollupCellNotFound)?
-                }
-            };
-            // check finality
+            let (global_state, paused_at) =
+                match search_rollup_state_with_pause(&rollup_type_hash, Source::CellDep)? {
+                    Some(state) => state,
+                    None => {
+                        // then try search rollup state from inputs
+                        search_rollup_state_with_pause(&rollup_type_hash, Source::Input)?
+                            .ok_or(Error::RollupCellNotFound)?
+                    }
+                };
+            // withdrawals can't be finalized while the rollup is paused
+            if paused_at.is_some() {
+                let config = load_rollup_config(&global_state.rollup_config_hash().unpack())?;
+                let timestamp = load_pause_check_timestamp(&global_state)?;
+                check_not_paused(&config, paused_at, timestamp)?;
+            }
+            // check finality, large withdrawals wait extra blocks by the finality tier
             let withdrawal_block_number: u64 = lock_args.withdrawal_block_number().unpack();
+            let finalized_block_number =
+                withdrawal_block_number.saturating_add(extra_finality_blocks);
             let last_finalized_block_number: u64 =
                 global_state.last_finalized_block_number().unpack();
 
-            if withdrawal_block_number > last_finalized_block_number {
+            if finalized_block_number > last_finalized_block_number {
                 // not yet finalized
                 return Err(Error::InvalidArgs);
             }
@@ -207,10 +231,13 @@ pub fn main() -> Result<(), Error> {
                 return Err(Error::InsufficientAmount);
             }
 
-            // make sure the output should only change owner_lock_hash field
-            let new_lock_hash = unlock_args.owner_lock().hash();
-
-            let index = match search_lock_hash(&new_lock_hash, Source::Output) {
+            // the output keeps the withdrawal lock, only the owner is changed,
+            // the finality and the pause still apply to the new owner
+            let new_owner_lock_hash = unlock_args.owner_lock().hash();
+            let new_lock = build_traded_withdrawal_lock(&script, &lock_args, &new_owner_lock_hash)?;
+            let index = match QueryIter::new(load_cell_lock, Source::Output)
+                .position(|lock| lock.as_slice() == new_lock.as_slice())
+            {
                 Some(i) => i,
                 None => return Err(Error::InvalidOutput),
             };
@@ -221,6 +248,37 @@ pub fn main() -> Result<(), Error> {
     }
 }
 
+/// The withdrawal lock of a traded withdrawal cell, the owner and the payment lock
+/// are the buyer, the cell isn't for sell, the extra finality blocks are kept
+fn build_traded_withdrawal_lock(
+    script: &ckb_types::packed::Script,
+    lock_args: &WithdrawalLockArgs,
+    new_owner_lock_hash: &[u8; 32],
+) -> Result<ckb_types::packed::Script, Error> {
+    let args: Bytes = script.args().unpack();
+    let extra_finality_blocks_offset = 32 + lock_args.as_slice().len();
+    if args.len() < extra_finality_blocks_offset {
+        return Err(Error::InvalidArgs);
+    }
+    let new_lock_args = lock_args
+        .clone()
+        .as_builder()
+        .owner_lock_hash(Pack::pack(new_owner_lock_hash))
+        .payment_lock_hash(Pack::pack(new_owner_lock_hash))
+        .sell_amount(Pack::pack(&0u128))
+        .sell_capacity(Pack::pack(&0u64))
+        .build();
+    let mut new_args = Vec::with_capacity(args.len());
+    new_args.extend_from_slice(&args[..32]);
+    new_args.extend_from_slice(new_lock_args.as_slice());
+    new_args.extend_from_slice(&args[extra_finality_blocks_offset..]);
+    Ok(script
+        .clone()
+        .as_builder()
+        .args(CKBPack::pack(&Bytes::from(new_args)))
+        .build())
+}
+
 fn check_output_cell_has_same_content(output_index: usize) -> Result<(), Error> {
     if load_cell_capacity(0, Source::GroupInput)?
         != load_cell_capacity(output_index, Source::Output)?
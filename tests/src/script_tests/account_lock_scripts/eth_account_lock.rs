@@ -5,10 +5,10 @@ use crate::testing_tool::programs::{
 };
 use ckb_chain_spec::consensus::ConsensusBuilder;
 use ckb_crypto::secp::{Generator, Privkey, Pubkey};
-use ckb_error::assert_error_eq;
+use ckb_error::{assert_error_eq, Error};
 use ckb_script::{ScriptError, TransactionScriptsVerifier, TxVerifyEnv};
 use ckb_types::core::hardfork::HardForkSwitch;
-use ckb_types::core::HeaderView;
+use ckb_types::core::{Cycle, HeaderView};
 use ckb_types::{
     bytes::Bytes,
     core::{Capacity, DepType, ScriptHashType, TransactionBuilder, TransactionView},
@@ -16,6 +16,7 @@ use ckb_types::{
     prelude::*,
 };
 use gw_ckb_hardfork::{GLOBAL_CURRENT_EPOCH_NUMBER, GLOBAL_HARDFORK_SWITCH};
+use gw_common::merkle_utils::{calculate_ckb_merkle_root, ckb_merkle_leaf_hash};
 use gw_generator::account_lock_manage::{secp256k1::Secp256k1Eth, LockAlgorithm};
 use rand::{thread_rng, Rng};
 use sha3::{Digest, Keccak256};
@@ -193,6 +194,67 @@ pub fn sha3_pubkey_hash(pubkey: &Pubkey) -> Bytes {
     buf[12..].to_vec().into()
 }
 
+/// convert to EIP-2098 compact signature
+fn to_compact_signature(signature: &[u8]) -> Bytes {
+    let mut compact = signature[..64].to_vec();
+    compact[32] |= signature[64] << 7;
+    compact.into()
+}
+
+/// convert to the malleable high-s form: (r, n - s, !v)
+fn to_high_s_signature(signature: &[u8]) -> Bytes {
+    const SECP256K1_ORDER: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xfe, 0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36,
+        0x41, 0x41,
+    ];
+    let mut high_s = signature.to_vec();
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut v = SECP256K1_ORDER[i] as i16 - signature[32 + i] as i16 - borrow;
+        borrow = if v < 0 {
+            v += 256;
+            1
+        } else {
+            0
+        };
+        high_s[32 + i] = v as u8;
+    }
+    high_s[64] ^= 1;
+    high_s.into()
+}
+
+fn verify_tx(data_loader: &DummyDataLoader, tx: &TransactionView) -> Result<Cycle, Error> {
+    let hardfork_switch = smol::block_on(async {
+        let switch = &*GLOBAL_HARDFORK_SWITCH.lock().await;
+        HardForkSwitch::new_without_any_enabled()
+            .as_builder()
+            .rfc_0028(switch.rfc_0028())
+            .rfc_0029(switch.rfc_0029())
+            .rfc_0030(switch.rfc_0030())
+            .rfc_0031(switch.rfc_0031())
+            .rfc_0032(switch.rfc_0032())
+            .rfc_0036(switch.rfc_0036())
+            .rfc_0038(switch.rfc_0038())
+            .build()
+            .unwrap()
+    });
+    let consensus = ConsensusBuilder::default()
+        .hardfork_switch(hardfork_switch)
+        .build();
+    let current_epoch_number = smol::block_on(async { *GLOBAL_CURRENT_EPOCH_NUMBER.lock().await });
+    let tx_verify_env = TxVerifyEnv::new_submit(
+        &HeaderView::new_advanced_builder()
+            .epoch(current_epoch_number.pack())
+            .build(),
+    );
+    let resolved_tx = build_resolved_tx(data_loader, tx);
+    let mut verifier =
+        TransactionScriptsVerifier::new(&resolved_tx, &consensus, data_loader, &tx_verify_env);
+    verifier.set_debug_printer(|_script, msg| println!("[script debug] {}", msg));
+    verifier.verify(MAX_CYCLES)
+}
+
 #[test]
 fn test_sign_eth_message() {
     let mut data_loader = DummyDataLoader::default();
@@ -327,6 +389,84 @@ fn test_submit_signing_eth_message() {
     assert!(valid);
 }
 
+#[test]
+fn test_sign_batch_eth_message() {
+    let mut data_loader = DummyDataLoader::default();
+    let privkey = Generator::random_privkey();
+    let pubkey = privkey.pubkey().expect("pubkey");
+    let pubkey_hash = sha3_pubkey_hash(&pubkey);
+    let mut rng = thread_rng();
+    let messages: Vec<[u8; 32]> = (0..3)
+        .map(|_| {
+            let mut message = [0u8; 32];
+            rng.fill(&mut message);
+            message
+        })
+        .collect();
+    // sign the merkle root of the messages
+    let merkle_root: [u8; 32] = {
+        let leaves = messages
+            .iter()
+            .enumerate()
+            .map(|(idx, message)| ckb_merkle_leaf_hash(idx as u32, &(*message).into()))
+            .collect();
+        calculate_ckb_merkle_root(leaves).unwrap().into()
+    };
+    let signature = sign_message(&privkey, merkle_root);
+    let lock_args = {
+        let rollup_script_hash = [42u8; 32];
+        let mut args = rollup_script_hash.to_vec();
+        args.extend_from_slice(&pubkey_hash);
+        args.into()
+    };
+    // signing_type(with prefix) | messages
+    let data = {
+        let mut data = vec![0u8];
+        for message in &messages {
+            data.extend_from_slice(message);
+        }
+        data
+    };
+    let tx = gen_tx(&mut data_loader, lock_args, data.into());
+    let tx = tx
+        .as_advanced_builder()
+        .set_witnesses(vec![WitnessArgs::new_builder()
+            .lock(Some(signature).pack())
+            .build()
+            .as_bytes()
+            .pack()])
+        .build();
+    let hardfork_switch = smol::block_on(async {
+        let switch = &*GLOBAL_HARDFORK_SWITCH.lock().await;
+        HardForkSwitch::new_without_any_enabled()
+            .as_builder()
+            .rfc_0028(switch.rfc_0028())
+            .rfc_0029(switch.rfc_0029())
+            .rfc_0030(switch.rfc_0030())
+            .rfc_0031(switch.rfc_0031())
+            .rfc_0032(switch.rfc_0032())
+            .rfc_0036(switch.rfc_0036())
+            .rfc_0038(switch.rfc_0038())
+            .build()
+            .unwrap()
+    });
+    let consensus = ConsensusBuilder::default()
+        .hardfork_switch(hardfork_switch)
+        .build();
+    let current_epoch_number = smol::block_on(async { *GLOBAL_CURRENT_EPOCH_NUMBER.lock().await });
+    let tx_verify_env = TxVerifyEnv::new_submit(
+        &HeaderView::new_advanced_builder()
+            .epoch(current_epoch_number.pack())
+            .build(),
+    );
+    let resolved_tx = build_resolved_tx(&data_loader, &tx);
+    let mut verifier =
+        TransactionScriptsVerifier::new(&resolved_tx, &consensus, &data_loader, &tx_verify_env);
+    verifier.set_debug_printer(|_script, msg| println!("[script debug] {}", msg));
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
+
 #[test]
 fn test_wrong_signature() {
     let mut data_loader = DummyDataLoader::default();
@@ -403,3 +543,71 @@ fn test_wrong_signature() {
         .unwrap();
     assert!(!valid);
 }
+
+#[test]
+fn test_compact_signature() {
+    let mut data_loader = DummyDataLoader::default();
+    let privkey = Generator::random_privkey();
+    let pubkey = privkey.pubkey().expect("pubkey");
+    let pubkey_hash = sha3_pubkey_hash(&pubkey);
+    let mut rng = thread_rng();
+    let mut message = [0u8; 32];
+    rng.fill(&mut message);
+    let signature = to_compact_signature(&sign_message(&privkey, message));
+    let lock_args = {
+        let rollup_script_hash = [42u8; 32];
+        let mut args = rollup_script_hash.to_vec();
+        args.extend_from_slice(&pubkey_hash);
+        args.into()
+    };
+    let tx = gen_tx(&mut data_loader, lock_args, message.to_vec().into());
+    let tx = tx
+        .as_advanced_builder()
+        .set_witnesses(vec![WitnessArgs::new_builder()
+            .lock(Some(signature).pack())
+            .build()
+            .as_bytes()
+            .pack()])
+        .build();
+    verify_tx(&data_loader, &tx).expect("pass verification");
+}
+
+#[test]
+fn test_high_s_signature() {
+    let mut data_loader = DummyDataLoader::default();
+    let privkey = Generator::random_privkey();
+    let pubkey = privkey.pubkey().expect("pubkey");
+    let pubkey_hash = sha3_pubkey_hash(&pubkey);
+    let mut rng = thread_rng();
+    let mut message = [0u8; 32];
+    rng.fill(&mut message);
+    let signature = to_high_s_signature(&sign_message(&privkey, message));
+    let lock_args = {
+        let rollup_script_hash = [42u8; 32];
+        let mut args = rollup_script_hash.to_vec();
+        args.extend_from_slice(&pubkey_hash);
+        args.into()
+    };
+    let tx = gen_tx(&mut data_loader, lock_args, message.to_vec().into());
+    let tx = tx
+        .as_advanced_builder()
+        .set_witnesses(vec![WitnessArgs::new_builder()
+            .lock(Some(signature).pack())
+            .build()
+            .as_bytes()
+            .pack()])
+        .build();
+    let verify_result = verify_tx(&data_loader, &tx);
+    let script_cell_index = 0;
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::ValidationFailure(
+            format!(
+                "by-data-hash/{}",
+                ckb_types::H256(*ETH_ACCOUNT_LOCK_CODE_HASH)
+            ),
+            ERROR_WRONG_SIGNATURE
+        )
+        .input_lock_script(script_cell_index)
+    );
+}
@@ -1,2 +1,3 @@
 mod eth_account_lock;
+mod recovery_account_lock;
 mod tron_account_lock;
@@ -0,0 +1,360 @@
+use crate::script_tests::utils::layer1::*;
+use crate::testing_tool::programs::{
+    ALWAYS_SUCCESS_CODE_HASH, ALWAYS_SUCCESS_PROGRAM, RECOVERY_ACCOUNT_LOCK_CODE_HASH,
+    RECOVERY_ACCOUNT_LOCK_PROGRAM, SECP256K1_DATA,
+};
+use ckb_chain_spec::consensus::ConsensusBuilder;
+use ckb_crypto::secp::{Generator, Privkey, Pubkey};
+use ckb_error::{assert_error_eq, Error};
+use ckb_script::{ScriptError, TransactionScriptsVerifier, TxVerifyEnv};
+use ckb_types::core::hardfork::HardForkSwitch;
+use ckb_types::core::{Cycle, HeaderView};
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, DepType, ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script, WitnessArgs},
+    prelude::*,
+};
+use gw_ckb_hardfork::{GLOBAL_CURRENT_EPOCH_NUMBER, GLOBAL_HARDFORK_SWITCH};
+use rand::{thread_rng, Rng};
+use sha3::{Digest, Keccak256};
+
+const ERROR_WRONG_SIGNATURE: i8 = 43;
+const ERROR_INSUFFICIENT_GUARDIANS: i8 = 46;
+// relative block number since, 100 blocks
+const RECOVERY_DELAY: u64 = 0x8000_0000_0000_0000 | 100;
+const REGISTRY_TYPE_ID: [u8; 32] = [7u8; 32];
+
+#[derive(Clone)]
+struct RegistryData {
+    owner_address: Bytes,
+    pending_address: [u8; 20],
+    threshold: u8,
+    guardian_lock_hashes: Vec<[u8; 32]>,
+}
+
+impl RegistryData {
+    fn as_bytes(&self) -> Bytes {
+        let mut data = self.owner_address.to_vec();
+        data.extend_from_slice(&self.pending_address);
+        data.extend_from_slice(&RECOVERY_DELAY.to_le_bytes());
+        data.push(self.threshold);
+        data.push(self.guardian_lock_hashes.len() as u8);
+        for lock_hash in &self.guardian_lock_hashes {
+            data.extend_from_slice(lock_hash);
+        }
+        data.into()
+    }
+}
+
+fn recovery_script(args: Bytes) -> Script {
+    Script::new_builder()
+        .code_hash(RECOVERY_ACCOUNT_LOCK_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Data.into())
+        .args(args.pack())
+        .build()
+}
+
+fn registry_type_script() -> Script {
+    recovery_script(REGISTRY_TYPE_ID.to_vec().into())
+}
+
+fn guardian_lock(index: u8) -> Script {
+    always_success_script()
+        .as_builder()
+        .args(vec![index].pack())
+        .build()
+}
+
+fn insert_cell(dummy: &mut DummyDataLoader, cell: CellOutput, data: Bytes) -> OutPoint {
+    let out_point = random_out_point();
+    dummy.cells.insert(out_point.clone(), (cell, data));
+    out_point
+}
+
+fn code_dep(out_point: OutPoint) -> CellDep {
+    CellDep::new_builder()
+        .out_point(out_point)
+        .dep_type(DepType::Code.into())
+        .build()
+}
+
+/// setup recovery lock, always success lock and secp256k1 data deps
+fn gen_tx_builder(dummy: &mut DummyDataLoader) -> TransactionBuilder {
+    let script_out_point = insert_cell(
+        dummy,
+        CellOutput::new_builder()
+            .capacity(
+                Capacity::bytes(RECOVERY_ACCOUNT_LOCK_PROGRAM.len())
+                    .expect("script capacity")
+                    .pack(),
+            )
+            .build(),
+        RECOVERY_ACCOUNT_LOCK_PROGRAM.clone(),
+    );
+    let always_success_out_point = insert_cell(
+        dummy,
+        CellOutput::new_builder()
+            .capacity(
+                Capacity::bytes(ALWAYS_SUCCESS_PROGRAM.len())
+                    .expect("script capacity")
+                    .pack(),
+            )
+            .build(),
+        ALWAYS_SUCCESS_PROGRAM.clone(),
+    );
+    let secp256k1_data_out_point = insert_cell(
+        dummy,
+        CellOutput::new_builder()
+            .capacity(
+                Capacity::bytes(SECP256K1_DATA.len())
+                    .expect("data capacity")
+                    .pack(),
+            )
+            .build(),
+        SECP256K1_DATA.clone(),
+    );
+    TransactionBuilder::default()
+        .cell_dep(code_dep(script_out_point))
+        .cell_dep(code_dep(secp256k1_data_out_point))
+        .cell_dep(code_dep(always_success_out_point))
+}
+
+fn registry_cell() -> CellOutput {
+    CellOutput::new_builder()
+        .capacity(Capacity::shannons(42).pack())
+        .lock(always_success_script())
+        .type_(Some(registry_type_script()).pack())
+        .build()
+}
+
+/// unlock an account lock cell, the registry is in the cell deps
+fn gen_unlock_tx(
+    dummy: &mut DummyDataLoader,
+    registry: &RegistryData,
+    message: [u8; 32],
+) -> TransactionView {
+    let registry_out_point = insert_cell(dummy, registry_cell(), registry.as_bytes());
+    let owner_lock_cell = CellOutput::new_builder()
+        .lock(
+            Script::new_builder()
+                .code_hash((*ALWAYS_SUCCESS_CODE_HASH).pack())
+                .hash_type(ScriptHashType::Data.into())
+                .build(),
+        )
+        .build();
+    let owner_lock_hash: [u8; 32] = owner_lock_cell.lock().calc_script_hash().unpack();
+    let owner_lock_cell_out_point = insert_cell(dummy, owner_lock_cell, Bytes::default());
+    let lock_args = {
+        let rollup_script_hash = [42u8; 32];
+        let registry_type_hash: [u8; 32] = registry_type_script().calc_script_hash().unpack();
+        let mut args = rollup_script_hash.to_vec();
+        args.extend_from_slice(&registry_type_hash);
+        args.into()
+    };
+    let account_cell = CellOutput::new_builder()
+        .capacity(Capacity::shannons(42).pack())
+        .lock(recovery_script(lock_args))
+        .build();
+    let mut input_data = owner_lock_hash.to_vec();
+    input_data.extend_from_slice(&message);
+    let account_cell_out_point = insert_cell(dummy, account_cell, input_data.into());
+    gen_tx_builder(dummy)
+        .cell_dep(code_dep(registry_out_point))
+        .input(CellInput::new(account_cell_out_point, 0))
+        .input(CellInput::new(owner_lock_cell_out_point, 0))
+        .output(
+            CellOutput::new_builder()
+                .capacity(Capacity::shannons(42).pack())
+                .build(),
+        )
+        .output_data(Bytes::new().pack())
+        .build()
+}
+
+/// update the registry cell, unlocked by the guardian cells in the inputs
+fn gen_registry_tx(
+    dummy: &mut DummyDataLoader,
+    input_registry: &RegistryData,
+    output_registry: &RegistryData,
+    guardians: &[u8],
+) -> TransactionView {
+    let registry_out_point = insert_cell(dummy, registry_cell(), input_registry.as_bytes());
+    let mut tx_builder = gen_tx_builder(dummy)
+        .input(CellInput::new(registry_out_point, 0))
+        .output(registry_cell())
+        .output_data(output_registry.as_bytes().pack());
+    for index in guardians {
+        let guardian_cell = CellOutput::new_builder()
+            .capacity(Capacity::shannons(42).pack())
+            .lock(guardian_lock(*index))
+            .build();
+        let out_point = insert_cell(dummy, guardian_cell, Bytes::default());
+        tx_builder = tx_builder.input(CellInput::new(out_point, 0));
+    }
+    tx_builder.build()
+}
+
+fn verify_tx(dummy: &DummyDataLoader, tx: &TransactionView) -> Result<Cycle, Error> {
+    let hardfork_switch = smol::block_on(async {
+        let switch = &*GLOBAL_HARDFORK_SWITCH.lock().await;
+        HardForkSwitch::new_without_any_enabled()
+            .as_builder()
+            .rfc_0028(switch.rfc_0028())
+            .rfc_0029(switch.rfc_0029())
+            .rfc_0030(switch.rfc_0030())
+            .rfc_0031(switch.rfc_0031())
+            .rfc_0032(switch.rfc_0032())
+            .rfc_0036(switch.rfc_0036())
+            .rfc_0038(switch.rfc_0038())
+            .build()
+            .unwrap()
+    });
+    let consensus = ConsensusBuilder::default()
+        .hardfork_switch(hardfork_switch)
+        .build();
+    let current_epoch_number = smol::block_on(async { *GLOBAL_CURRENT_EPOCH_NUMBER.lock().await });
+    let tx_verify_env = TxVerifyEnv::new_submit(
+        &HeaderView::new_advanced_builder()
+            .epoch(current_epoch_number.pack())
+            .build(),
+    );
+    let resolved_tx = build_resolved_tx(dummy, tx);
+    let mut verifier =
+        TransactionScriptsVerifier::new(&resolved_tx, &consensus, dummy, &tx_verify_env);
+    verifier.set_debug_printer(|_script, msg| println!("[script debug] {}", msg));
+    verifier.verify(MAX_CYCLES)
+}
+
+fn sign_message(key: &Privkey, message: [u8; 32]) -> Bytes {
+    // calculate eth signing message
+    let message = {
+        let mut hasher = Keccak256::new();
+        hasher.update("\x19Ethereum Signed Message:\n32");
+        hasher.update(&message);
+        let buf = hasher.finalize();
+        let mut signing_message = [0u8; 32];
+        signing_message.copy_from_slice(&buf[..]);
+        ckb_types::H256::from(signing_message)
+    };
+    let sig = key.sign_recoverable(&message).expect("sign");
+    let mut signature = [0u8; 65];
+    signature.copy_from_slice(&sig.serialize());
+    signature.to_vec().into()
+}
+
+fn sha3_pubkey_hash(pubkey: &Pubkey) -> Bytes {
+    let mut hasher = Keccak256::new();
+    hasher.update(&pubkey.as_bytes());
+    let buf = hasher.finalize();
+    buf[12..].to_vec().into()
+}
+
+fn guardian_lock_hashes(count: u8) -> Vec<[u8; 32]> {
+    (0..count)
+        .map(|index| guardian_lock(index).calc_script_hash().unpack())
+        .collect()
+}
+
+#[test]
+fn test_unlock_by_registry_owner() {
+    let mut data_loader = DummyDataLoader::default();
+    let privkey = Generator::random_privkey();
+    let pubkey = privkey.pubkey().expect("pubkey");
+    let registry = RegistryData {
+        owner_address: sha3_pubkey_hash(&pubkey),
+        pending_address: [0u8; 20],
+        threshold: 2,
+        guardian_lock_hashes: guardian_lock_hashes(3),
+    };
+    let mut rng = thread_rng();
+    let mut message = [0u8; 32];
+    rng.fill(&mut message);
+    let signature = sign_message(&privkey, message);
+    let tx = gen_unlock_tx(&mut data_loader, &registry, message);
+    let tx = tx
+        .as_advanced_builder()
+        .set_witnesses(vec![WitnessArgs::new_builder()
+            .lock(Some(signature).pack())
+            .build()
+            .as_bytes()
+            .pack()])
+        .build();
+    verify_tx(&data_loader, &tx).expect("pass verification");
+}
+
+#[test]
+fn test_unlock_by_rotated_key() {
+    let mut data_loader = DummyDataLoader::default();
+    let old_privkey = Generator::random_privkey();
+    let new_privkey = Generator::random_privkey();
+    let new_pubkey = new_privkey.pubkey().expect("pubkey");
+    let registry = RegistryData {
+        owner_address: sha3_pubkey_hash(&new_pubkey),
+        pending_address: [0u8; 20],
+        threshold: 1,
+        guardian_lock_hashes: guardian_lock_hashes(1),
+    };
+    let mut rng = thread_rng();
+    let mut message = [0u8; 32];
+    rng.fill(&mut message);
+    let signature = sign_message(&old_privkey, message);
+    let tx = gen_unlock_tx(&mut data_loader, &registry, message);
+    let tx = tx
+        .as_advanced_builder()
+        .set_witnesses(vec![WitnessArgs::new_builder()
+            .lock(Some(signature).pack())
+            .build()
+            .as_bytes()
+            .pack()])
+        .build();
+    let verify_result = verify_tx(&data_loader, &tx);
+    let script_cell_index = 0;
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::ValidationFailure(
+            format!(
+                "by-data-hash/{}",
+                ckb_types::H256(*RECOVERY_ACCOUNT_LOCK_CODE_HASH)
+            ),
+            ERROR_WRONG_SIGNATURE
+        )
+        .input_lock_script(script_cell_index)
+    );
+}
+
+#[test]
+fn test_guardians_start_recovery() {
+    let privkey = Generator::random_privkey();
+    let pubkey = privkey.pubkey().expect("pubkey");
+    let input_registry = RegistryData {
+        owner_address: sha3_pubkey_hash(&pubkey),
+        pending_address: [0u8; 20],
+        threshold: 2,
+        guardian_lock_hashes: guardian_lock_hashes(3),
+    };
+    let mut output_registry = input_registry.clone();
+    output_registry.pending_address = [3u8; 20];
+
+    // 2 of 3 guardians
+    let mut data_loader = DummyDataLoader::default();
+    let tx = gen_registry_tx(&mut data_loader, &input_registry, &output_registry, &[0, 2]);
+    verify_tx(&data_loader, &tx).expect("pass verification");
+
+    // 1 of 3 guardians
+    let mut data_loader = DummyDataLoader::default();
+    let tx = gen_registry_tx(&mut data_loader, &input_registry, &output_registry, &[1]);
+    let verify_result = verify_tx(&data_loader, &tx);
+    let script_cell_index = 0;
+    assert_error_eq!(
+        verify_result.unwrap_err(),
+        ScriptError::ValidationFailure(
+            format!(
+                "by-data-hash/{}",
+                ckb_types::H256(*RECOVERY_ACCOUNT_LOCK_CODE_HASH)
+            ),
+            ERROR_INSUFFICIENT_GUARDIANS
+        )
+        .input_type_script(script_cell_index)
+    );
+}
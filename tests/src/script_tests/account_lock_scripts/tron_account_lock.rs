@@ -16,8 +16,13 @@ use ckb_types::{
     prelude::*,
 };
 use gw_ckb_hardfork::{GLOBAL_CURRENT_EPOCH_NUMBER, GLOBAL_HARDFORK_SWITCH};
+use gw_common::blake2b::new_blake2b;
 use gw_generator::account_lock_manage::secp256k1::Secp256k1Tron;
 use gw_generator::account_lock_manage::{secp256k1::Secp256k1Eth, LockAlgorithm};
+use gw_types::{
+    packed::RawL2Transaction,
+    prelude::{Builder as GWBuilder, Entity as GWEntity, Pack as GWPack, Unpack as GWUnpack},
+};
 use rand::{thread_rng, Rng};
 use sha3::{Digest, Keccak256};
 
@@ -196,6 +201,51 @@ pub fn sha3_pubkey_hash(pubkey: &Pubkey) -> Bytes {
     buf[12..].to_vec().into()
 }
 
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(data);
+    let buf = hasher.finalize();
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&buf[..]);
+    hash
+}
+
+fn encode_u32(n: u32) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[28..].copy_from_slice(&n.to_be_bytes());
+    word
+}
+
+/// TIP-712 typed data hash of a layer2 transaction
+fn typed_data_hash(
+    rollup_type_hash: &[u8; 32],
+    sender_script_hash: &[u8; 32],
+    receiver_script_hash: &[u8; 32],
+    raw_tx: &RawL2Transaction,
+) -> [u8; 32] {
+    let domain_separator = {
+        let mut buf = keccak256(b"EIP712Domain(string name,string version,bytes32 salt)").to_vec();
+        buf.extend_from_slice(&keccak256(b"Godwoken"));
+        buf.extend_from_slice(&keccak256(b"1"));
+        buf.extend_from_slice(rollup_type_hash);
+        keccak256(&buf)
+    };
+    let struct_hash = {
+        let mut buf = keccak256(b"Transaction(bytes32 sender,bytes32 receiver,uint32 fromId,uint32 toId,uint32 nonce,bytes args)").to_vec();
+        buf.extend_from_slice(sender_script_hash);
+        buf.extend_from_slice(receiver_script_hash);
+        buf.extend_from_slice(&encode_u32(GWUnpack::unpack(&raw_tx.from_id())));
+        buf.extend_from_slice(&encode_u32(GWUnpack::unpack(&raw_tx.to_id())));
+        buf.extend_from_slice(&encode_u32(GWUnpack::unpack(&raw_tx.nonce())));
+        buf.extend_from_slice(&keccak256(&raw_tx.args().raw_data()));
+        keccak256(&buf)
+    };
+    let mut buf = b"\x19\x01".to_vec();
+    buf.extend_from_slice(&domain_separator);
+    buf.extend_from_slice(&struct_hash);
+    keccak256(&buf)
+}
+
 #[test]
 fn test_sign_tron_message() {
     let mut data_loader = DummyDataLoader::default();
@@ -406,3 +456,97 @@ fn test_wrong_signature() {
         .unwrap_or(false);
     assert!(!valid);
 }
+
+#[test]
+fn test_sign_tron_typed_data() {
+    let mut data_loader = DummyDataLoader::default();
+    let privkey = Generator::random_privkey();
+    let pubkey = privkey.pubkey().expect("pubkey");
+    let pubkey_hash = sha3_pubkey_hash(&pubkey);
+    let rollup_type_hash = [42u8; 32];
+    let sender_script_hash = [1u8; 32];
+    let receiver_script_hash = [2u8; 32];
+    let raw_tx = RawL2Transaction::new_builder()
+        .from_id(GWPack::pack(&3u32))
+        .to_id(GWPack::pack(&4u32))
+        .nonce(GWPack::pack(&5u32))
+        .args(GWPack::pack(&gw_types::bytes::Bytes::from(vec![6u8; 42])))
+        .build();
+    let message = {
+        let mut hasher = new_blake2b();
+        hasher.update(&rollup_type_hash);
+        hasher.update(&sender_script_hash);
+        hasher.update(&receiver_script_hash);
+        hasher.update(raw_tx.as_slice());
+        let mut message = [0u8; 32];
+        hasher.finalize(&mut message);
+        message
+    };
+    let signature = {
+        let signing_message = typed_data_hash(
+            &rollup_type_hash,
+            &sender_script_hash,
+            &receiver_script_hash,
+            &raw_tx,
+        );
+        let sig = privkey
+            .sign_recoverable(&signing_message.into())
+            .expect("sign");
+        let mut signature = [0u8; 65];
+        signature.copy_from_slice(&sig.serialize());
+        Bytes::from(signature.to_vec())
+    };
+    let lock_args = {
+        let mut args = rollup_type_hash.to_vec();
+        args.extend_from_slice(&pubkey_hash);
+        args.into()
+    };
+    // signing_type(typed data) | message | typed transaction
+    let data = {
+        let mut data = vec![2u8];
+        data.extend_from_slice(&message);
+        data.extend_from_slice(&rollup_type_hash);
+        data.extend_from_slice(&sender_script_hash);
+        data.extend_from_slice(&receiver_script_hash);
+        data.extend_from_slice(raw_tx.as_slice());
+        data
+    };
+    let tx = gen_tx(&mut data_loader, lock_args, data.into());
+    let tx = tx
+        .as_advanced_builder()
+        .set_witnesses(vec![WitnessArgs::new_builder()
+            .lock(Some(signature).pack())
+            .build()
+            .as_bytes()
+            .pack()])
+        .build();
+    let hardfork_switch = smol::block_on(async {
+        let switch = &*GLOBAL_HARDFORK_SWITCH.lock().await;
+        HardForkSwitch::new_without_any_enabled()
+            .as_builder()
+            .rfc_0028(switch.rfc_0028())
+            .rfc_0029(switch.rfc_0029())
+            .rfc_0030(switch.rfc_0030())
+            .rfc_0031(switch.rfc_0031())
+            .rfc_0032(switch.rfc_0032())
+            .rfc_0036(switch.rfc_0036())
+            .rfc_0038(switch.rfc_0038())
+            .build()
+            .unwrap()
+    });
+    let consensus = ConsensusBuilder::default()
+        .hardfork_switch(hardfork_switch)
+        .build();
+    let current_epoch_number = smol::block_on(async { *GLOBAL_CURRENT_EPOCH_NUMBER.lock().await });
+    let tx_verify_env = TxVerifyEnv::new_submit(
+        &HeaderView::new_advanced_builder()
+            .epoch(current_epoch_number.pack())
+            .build(),
+    );
+    let resolved_tx = build_resolved_tx(&data_loader, &tx);
+    let mut verifier =
+        TransactionScriptsVerifier::new(&resolved_tx, &consensus, &data_loader, &tx_verify_env);
+    verifier.set_debug_printer(|_script, msg| println!("[script debug] {}", msg));
+    let verify_result = verifier.verify(MAX_CYCLES);
+    verify_result.expect("pass verification");
+}
@@ -1,4 +1,5 @@
 use super::{check_transfer_logs, new_block_info, run_contract, run_contract_get_result};
+use crate::script_tests::utils::rollup::build_rollup_config_with_extra_fields;
 use gw_common::state::{to_short_address, State};
 use gw_generator::dummy_state::DummyState;
 use gw_generator::syscalls::error_codes::{
@@ -8,6 +9,7 @@ use gw_generator::{error::TransactionError, traits::StateExt};
 use gw_traits::CodeStore;
 use gw_types::packed::BlockInfo;
 use gw_types::{
+    bytes::Bytes,
     core::ScriptHashType,
     packed::{RollupConfig, SUDTArgs, SUDTQuery, SUDTTransfer, Script},
     prelude::*,
@@ -902,3 +904,55 @@ fn check_balance<S: State + CodeStore>(
     };
     assert_eq!(balance, expected_balance);
 }
+
+#[test]
+fn test_sudt_with_extended_rollup_config() {
+    let mut tree = DummyState::default();
+    let init_a_balance: u128 = 10000;
+
+    // the generator must accept a config with extra fields
+    let rollup_config = {
+        let rollup_config = RollupConfig::new_builder()
+            .l2_sudt_validator_script_type_hash(DUMMY_SUDT_VALIDATOR_SCRIPT_TYPE_HASH.pack())
+            .build();
+        let max_txs_per_block = Bytes::from(100u32.to_le_bytes().to_vec());
+        build_rollup_config_with_extra_fields(&rollup_config, &[max_txs_per_block])
+    };
+
+    // init accounts
+    let sudt_id = tree
+        .create_account_from_script(
+            Script::new_builder()
+                .code_hash(DUMMY_SUDT_VALIDATOR_SCRIPT_TYPE_HASH.clone().pack())
+                .args([0u8; 32].to_vec().pack())
+                .hash_type(ScriptHashType::Type.into())
+                .build(),
+        )
+        .expect("create account");
+    let a_id = tree
+        .create_account_from_script(
+            Script::new_builder()
+                .code_hash([0u8; 32].pack())
+                .args([0u8; 20].to_vec().pack())
+                .hash_type(ScriptHashType::Type.into())
+                .build(),
+        )
+        .expect("create account");
+    let a_script_hash = tree.get_script_hash(a_id).expect("get script hash");
+    let block_info = new_block_info(0, 1, 0);
+
+    // init balance for a
+    tree.mint_sudt(sudt_id, to_short_address(&a_script_hash), init_a_balance)
+        .expect("init balance");
+
+    let a_address = to_short_address(&a_script_hash).to_vec();
+    check_balance(
+        &rollup_config,
+        &mut tree,
+        &block_info,
+        a_id,
+        sudt_id,
+        &a_address,
+        init_a_balance,
+    );
+}
@@ -0,0 +1,205 @@
+use crate::script_tests::utils::layer1::{
+    always_success_script, build_simple_tx, random_out_point,
+};
+use crate::script_tests::utils::layer2::extend_table;
+use crate::script_tests::utils::rollup::{
+    build_always_success_cell, build_type_id_script, CellContext, CellContextParam,
+};
+use crate::testing_tool::programs::{CUSTODIAN_LOCK_CODE_HASH, CUSTODIAN_LOCK_PROGRAM};
+use ckb_error::assert_error_eq;
+use ckb_script::ScriptError;
+use ckb_types::{
+    core::TransactionView,
+    packed::{CellDep, CellInput, CellOutput, WitnessArgs},
+    prelude::{Pack as CKBPack, Unpack as CKBUnpack},
+};
+use gw_common::blake2b::new_blake2b;
+use gw_types::prelude::*;
+use gw_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, Status},
+    packed::{
+        Byte32Vec, CustodianLockArgs, DepositLockArgs, GlobalState, L2Block, RollupAction,
+        RollupActionUnion, RollupConfig, RollupSubmitBlock, UnlockCustodianViaRevertWitness,
+    },
+};
+
+const ENCODING_ERROR: i8 = 4;
+const INVALID_REVERTED_BLOCKS_ERROR: i8 = 31;
+
+const DEPOSIT_BLOCK_HASH: [u8; 32] = [3u8; 32];
+
+/// Witness of the rollup output, the reverted block hashes are loaded by `WitnessCursor`
+fn build_submit_block_witness(reverted_block_hashes: Vec<[u8; 32]>) -> Bytes {
+    let reverted_block_hashes = reverted_block_hashes
+        .into_iter()
+        .fold(Byte32Vec::new_builder(), |builder, hash| {
+            builder.push(Pack::pack(&hash))
+        })
+        .build();
+    let rollup_action = RollupAction::new_builder()
+        .set(RollupActionUnion::RollupSubmitBlock(
+            RollupSubmitBlock::new_builder()
+                .reverted_block_hashes(reverted_block_hashes)
+                .build(),
+        ))
+        .build();
+    rollup_action.as_bytes()
+}
+
+/// Unlock a unfinalized custodian cell by reverting its deposit block
+fn build_revert_custodian_tx(rollup_action: Bytes) -> (CellContext, TransactionView) {
+    let deposit_script_type_hash: [u8; 32] = build_type_id_script(b"deposit_lock_type_id")
+        .calc_script_hash()
+        .unpack();
+    let rollup_config = RollupConfig::new_builder()
+        .deposit_script_type_hash(Pack::pack(&deposit_script_type_hash))
+        .finality_blocks(Pack::pack(&10u64))
+        .build();
+    let mut ctx = CellContext::new(&rollup_config, CellContextParam::default());
+
+    let rollup_type_script = build_type_id_script(b"rollup_type_id");
+    let rollup_type_hash: [u8; 32] = rollup_type_script.calc_script_hash().unpack();
+    let rollup_config_hash = {
+        let mut hash = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(rollup_config.as_slice());
+        hasher.finalize(&mut hash);
+        hash
+    };
+    let global_state = GlobalState::new_builder()
+        .rollup_config_hash(Pack::pack(&rollup_config_hash))
+        .last_finalized_block_number(Pack::pack(&0u64))
+        .status(Status::Running.into())
+        .version(1u8.into())
+        .build();
+    let rollup_cell = build_always_success_cell(1000, Some(rollup_type_script));
+
+    // the deposit block is not finalized
+    let deposit_lock_args = DepositLockArgs::new_builder()
+        .owner_lock_hash(Pack::pack(&[5u8; 32]))
+        .cancel_timeout(Pack::pack(&100u64))
+        .build();
+    let custodian_lock_args = CustodianLockArgs::new_builder()
+        .deposit_block_hash(Pack::pack(&DEPOSIT_BLOCK_HASH))
+        .deposit_block_number(Pack::pack(&1u64))
+        .deposit_lock_args(deposit_lock_args.clone())
+        .build();
+    let custodian_lock = {
+        let mut args = rollup_type_hash.to_vec();
+        args.extend_from_slice(custodian_lock_args.as_slice());
+        ckb_types::packed::Script::new_builder()
+            .code_hash(CKBPack::pack(&*CUSTODIAN_LOCK_CODE_HASH))
+            .hash_type(ScriptHashType::Data.into())
+            .args(CKBPack::pack(&Bytes::from(args)))
+            .build()
+    };
+    let deposit_lock = {
+        let mut args = rollup_type_hash.to_vec();
+        args.extend_from_slice(deposit_lock_args.as_slice());
+        ckb_types::packed::Script::new_builder()
+            .code_hash(CKBPack::pack(&deposit_script_type_hash))
+            .hash_type(ScriptHashType::Type.into())
+            .args(CKBPack::pack(&Bytes::from(args)))
+            .build()
+    };
+    let deposit_lock_hash: [u8; 32] = deposit_lock.calc_script_hash().unpack();
+
+    let custodian_cell = CellOutput::new_builder()
+        .lock(custodian_lock)
+        .capacity(CKBPack::pack(&500u64))
+        .build();
+    let custodian_input = CellInput::new_builder()
+        .previous_output(ctx.insert_cell(custodian_cell, Bytes::new()))
+        .build();
+    let deposit_cell = CellOutput::new_builder()
+        .lock(deposit_lock)
+        .capacity(CKBPack::pack(&500u64))
+        .build();
+    let custodian_lock_dep = {
+        let cell = CellOutput::new_builder()
+            .capacity(CKBPack::pack(&(CUSTODIAN_LOCK_PROGRAM.len() as u64)))
+            .lock(always_success_script())
+            .build();
+        CellDep::new_builder()
+            .out_point(ctx.insert_cell(cell, CUSTODIAN_LOCK_PROGRAM.clone()))
+            .build()
+    };
+
+    let rollup_witness = WitnessArgs::new_builder()
+        .output_type(CKBPack::pack(&Some(rollup_action)))
+        .build();
+    let unlock_witness = {
+        let unlock_args = UnlockCustodianViaRevertWitness::new_builder()
+            .deposit_lock_hash(Pack::pack(&deposit_lock_hash))
+            .build();
+        WitnessArgs::new_builder()
+            .lock(CKBPack::pack(&Some(unlock_args.as_bytes())))
+            .build()
+    };
+    let tx = build_simple_tx(
+        &mut ctx.inner,
+        (rollup_cell.clone(), global_state.as_bytes()),
+        Default::default(),
+        (rollup_cell, global_state.as_bytes()),
+    )
+    .as_advanced_builder()
+    .input(custodian_input)
+    .output(deposit_cell)
+    .output_data(CKBPack::pack(&Bytes::new()))
+    .cell_dep(ctx.always_success_dep.clone())
+    .cell_dep(ctx.rollup_config_dep.clone())
+    .cell_dep(custodian_lock_dep)
+    .witness(CKBPack::pack(&rollup_witness.as_bytes()))
+    .witness(CKBPack::pack(&unlock_witness.as_bytes()))
+    .build();
+    (ctx, tx)
+}
+
+fn assert_custodian_lock_error(err: ckb_error::Error, error_code: i8) {
+    let expected_err = ScriptError::ValidationFailure(
+        format!(
+            "by-data-hash/{}",
+            ckb_types::H256(*CUSTODIAN_LOCK_CODE_HASH)
+        ),
+        error_code,
+    )
+    .input_lock_script(1);
+    assert_error_eq!(err, expected_err);
+}
+
+#[test]
+fn test_unlock_custodian_via_revert() {
+    let rollup_action = build_submit_block_witness(vec![[1u8; 32], DEPOSIT_BLOCK_HASH]);
+    let (ctx, tx) = build_revert_custodian_tx(rollup_action);
+    ctx.verify_tx(tx).expect("unlock custodian cell");
+}
+
+#[test]
+fn test_unlock_custodian_via_revert_with_unreverted_block() {
+    let rollup_action = build_submit_block_witness(vec![[1u8; 32], [2u8; 32]]);
+    let (ctx, tx) = build_revert_custodian_tx(rollup_action);
+    let err = ctx.verify_tx(tx).unwrap_err();
+    assert_custodian_lock_error(err, INVALID_REVERTED_BLOCKS_ERROR);
+}
+
+#[test]
+fn test_unlock_custodian_via_revert_with_malformed_reverted_block_hashes() {
+    // the reverted_block_hashes declares one hash but has no item
+    let submit_block = extend_table(
+        &4u32.to_le_bytes(),
+        &[
+            L2Block::default().as_bytes(),
+            Bytes::from(1u32.to_le_bytes().to_vec()),
+            Pack::pack(&Bytes::default()).as_bytes(),
+        ],
+    );
+    let rollup_action = {
+        let mut buf = 0u32.to_le_bytes().to_vec();
+        buf.extend_from_slice(&submit_block);
+        Bytes::from(buf)
+    };
+    let (ctx, tx) = build_revert_custodian_tx(rollup_action);
+    let err = ctx.verify_tx(tx).unwrap_err();
+    assert_custodian_lock_error(err, ENCODING_ERROR);
+}
@@ -0,0 +1,366 @@
+use crate::script_tests::utils::layer1::always_success_script;
+use crate::script_tests::utils::layer2::extend_table;
+use crate::script_tests::utils::rollup::{CellContext, CellContextParam};
+use crate::testing_tool::programs::{DEPOSIT_LOCK_CODE_HASH, DEPOSIT_LOCK_PROGRAM, SECP256K1_DATA};
+use ckb_crypto::secp::{Generator, Privkey};
+use ckb_error::assert_error_eq;
+use ckb_script::ScriptError;
+use ckb_types::{
+    core::{TransactionBuilder, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, WitnessArgs},
+    prelude::{Pack as CKBPack, Unpack as CKBUnpack},
+};
+use gw_common::blake2b::new_blake2b;
+use gw_types::prelude::*;
+use gw_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{DepositLockArgs, RollupConfig},
+};
+
+const INDEX_OUT_OF_BOUND_ERROR: i8 = 1;
+const INVALID_SINCE_ERROR: i8 = 6;
+const INVALID_OUTPUT_ERROR: i8 = 7;
+const WRONG_SIGNATURE_ERROR: i8 = 43;
+
+const DEPOSIT_CAPACITY: u64 = 1000_00000000u64;
+const RELAYER_FEE: u64 = 1_00000000u64;
+// relative 100 blocks
+const CANCEL_TIMEOUT: u64 = 0x8000_0000_0000_0000u64 | 100;
+
+const RELATIVE_BLOCK_NUMBER_FLAG: u64 = 0x8000_0000_0000_0000;
+const RELATIVE_EPOCH_FLAG: u64 = 0xa000_0000_0000_0000;
+const RELATIVE_TIMESTAMP_FLAG: u64 = 0xc000_0000_0000_0000;
+
+/// Epoch with fraction: number | index | length
+fn epoch(number: u64, index: u64, length: u64) -> u64 {
+    number | (index << 24) | (length << 40)
+}
+
+/// A deposit cell which can be cancelled by the signature of the key
+struct SignedDeposit {
+    input: CellInput,
+    /// signature | refund_lock_hash
+    witness_lock: Bytes,
+}
+
+struct DepositLockTest {
+    ctx: CellContext,
+    deposit_lock_dep: CellDep,
+    secp256k1_data_dep: CellDep,
+}
+
+impl DepositLockTest {
+    fn new() -> Self {
+        let mut ctx = CellContext::new(&RollupConfig::default(), CellContextParam::default());
+        let mut deploy = |program: &Bytes| {
+            let cell = CellOutput::new_builder()
+                .capacity(CKBPack::pack(&(program.len() as u64)))
+                .lock(always_success_script())
+                .build();
+            CellDep::new_builder()
+                .out_point(ctx.insert_cell(cell, program.clone()))
+                .build()
+        };
+        let deposit_lock_dep = deploy(&DEPOSIT_LOCK_PROGRAM);
+        let secp256k1_data_dep = deploy(&SECP256K1_DATA);
+        DepositLockTest {
+            ctx,
+            deposit_lock_dep,
+            secp256k1_data_dep,
+        }
+    }
+
+    fn insert_deposit_cell(&mut self, lock_args: &[u8]) -> OutPoint {
+        let deposit_lock = {
+            let mut args = [1u8; 32].to_vec();
+            args.extend_from_slice(lock_args);
+            ckb_types::packed::Script::new_builder()
+                .code_hash(CKBPack::pack(&*DEPOSIT_LOCK_CODE_HASH))
+                .hash_type(ScriptHashType::Data.into())
+                .args(CKBPack::pack(&Bytes::from(args)))
+                .build()
+        };
+        let deposit_cell = CellOutput::new_builder()
+            .lock(deposit_lock)
+            .capacity(CKBPack::pack(&DEPOSIT_CAPACITY))
+            .build();
+        self.ctx.insert_cell(deposit_cell, Bytes::new())
+    }
+
+    /// Cancel the deposit by the owner cell, the owner is the always success script
+    fn build_owner_cancel_tx(&mut self, cancel_timeout: u64, since: u64) -> TransactionView {
+        let owner_lock_hash: [u8; 32] = always_success_script().calc_script_hash().unpack();
+        let lock_args = DepositLockArgs::new_builder()
+            .owner_lock_hash(Pack::pack(&owner_lock_hash))
+            .cancel_timeout(Pack::pack(&cancel_timeout))
+            .build();
+        let deposit_input = CellInput::new(self.insert_deposit_cell(lock_args.as_slice()), since);
+        let owner_cell = CellOutput::new_builder()
+            .lock(always_success_script())
+            .capacity(CKBPack::pack(&DEPOSIT_CAPACITY))
+            .build();
+        let owner_input = CellInput::new(self.ctx.insert_cell(owner_cell.clone(), Bytes::new()), 0);
+        TransactionBuilder::default()
+            .input(deposit_input)
+            .input(owner_input)
+            .output(owner_cell)
+            .output_data(CKBPack::pack(&Bytes::new()))
+            .cell_dep(self.deposit_lock_dep.clone())
+            .cell_dep(self.ctx.always_success_dep.clone())
+            .build()
+    }
+
+    /// Deposit cell cancelled by the key, `owner` makes the lock args unique,
+    /// the key signs the cancel by `signer`
+    fn build_signed_deposit(
+        &mut self,
+        owner: u8,
+        key: &Privkey,
+        signer: &Privkey,
+        refund_lock_hash: [u8; 32],
+    ) -> SignedDeposit {
+        let cancel_pubkey_hash = {
+            let mut hash = [0u8; 32];
+            let mut hasher = new_blake2b();
+            hasher.update(&key.pubkey().expect("pubkey").serialize());
+            hasher.finalize(&mut hash);
+            Bytes::from(hash[..20].to_vec())
+        };
+        // extra fields: call | cancel_pubkey_hash | relayer_fee, see `gw_utils::deposit`
+        let lock_args = DepositLockArgs::new_builder()
+            .owner_lock_hash(Pack::pack(&[owner; 32]))
+            .cancel_timeout(Pack::pack(&CANCEL_TIMEOUT))
+            .build();
+        let lock_args = extend_table(
+            lock_args.as_slice(),
+            &[
+                Bytes::new(),
+                cancel_pubkey_hash,
+                Bytes::from(RELAYER_FEE.to_le_bytes().to_vec()),
+            ],
+        );
+        let out_point = self.insert_deposit_cell(&lock_args);
+
+        // signing message: hash(deposit out point | refund_lock_hash | relayer_fee)
+        let mut message = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(out_point.as_slice());
+        hasher.update(&refund_lock_hash);
+        hasher.update(&RELAYER_FEE.to_le_bytes());
+        hasher.finalize(&mut message);
+        let signature = signer
+            .sign_recoverable(&message.into())
+            .expect("sign")
+            .serialize();
+        let mut witness_lock = signature;
+        witness_lock.extend_from_slice(&refund_lock_hash);
+        SignedDeposit {
+            input: CellInput::new(out_point, CANCEL_TIMEOUT),
+            witness_lock: witness_lock.into(),
+        }
+    }
+
+    fn build_cancel_tx(
+        &self,
+        deposits: &[SignedDeposit],
+        refund_cells: Vec<CellOutput>,
+    ) -> TransactionView {
+        let outputs_data = vec![CKBPack::pack(&Bytes::new()); refund_cells.len()];
+        TransactionBuilder::default()
+            .inputs(deposits.iter().map(|deposit| deposit.input.clone()))
+            .outputs(refund_cells)
+            .outputs_data(outputs_data)
+            .witnesses(deposits.iter().map(|deposit| {
+                let witness = WitnessArgs::new_builder()
+                    .lock(CKBPack::pack(&Some(deposit.witness_lock.clone())))
+                    .build();
+                CKBPack::pack(&witness.as_bytes())
+            }))
+            .cell_dep(self.deposit_lock_dep.clone())
+            .cell_dep(self.secp256k1_data_dep.clone())
+            .build()
+    }
+}
+
+fn build_refund_lock() -> ckb_types::packed::Script {
+    always_success_script()
+        .as_builder()
+        .args(CKBPack::pack(&Bytes::from_static(b"refund")))
+        .build()
+}
+
+fn build_refund_cell(capacity: u64) -> CellOutput {
+    CellOutput::new_builder()
+        .lock(build_refund_lock())
+        .capacity(CKBPack::pack(&capacity))
+        .build()
+}
+
+fn assert_deposit_lock_error(err: ckb_error::Error, input_index: usize, error_code: i8) {
+    let expected_err = ScriptError::ValidationFailure(
+        format!("by-data-hash/{}", ckb_types::H256(*DEPOSIT_LOCK_CODE_HASH)),
+        error_code,
+    )
+    .input_lock_script(input_index);
+    assert_error_eq!(err, expected_err);
+}
+
+#[test]
+fn test_cancel_deposit_by_signature() {
+    let key = Generator::random_privkey();
+    let refund_lock_hash: [u8; 32] = build_refund_lock().calc_script_hash().unpack();
+    let refund_capacity = DEPOSIT_CAPACITY - RELAYER_FEE;
+
+    let mut test = DepositLockTest::new();
+    let deposit = test.build_signed_deposit(1, &key, &key, refund_lock_hash);
+    let tx = test.build_cancel_tx(&[deposit], vec![build_refund_cell(refund_capacity)]);
+    test.ctx.verify_tx(tx).expect("signed cancel");
+
+    // signed by another key
+    let mut test = DepositLockTest::new();
+    let other_key = Generator::random_privkey();
+    let deposit = test.build_signed_deposit(1, &key, &other_key, refund_lock_hash);
+    let tx = test.build_cancel_tx(&[deposit], vec![build_refund_cell(refund_capacity)]);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_deposit_lock_error(err, 0, WRONG_SIGNATURE_ERROR);
+
+    // the relayer takes more than the relayer fee
+    let mut test = DepositLockTest::new();
+    let deposit = test.build_signed_deposit(1, &key, &key, refund_lock_hash);
+    let tx = test.build_cancel_tx(&[deposit], vec![build_refund_cell(refund_capacity - 1)]);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_deposit_lock_error(err, 0, INVALID_OUTPUT_ERROR);
+
+    // the refund cell isn't at the index of the deposit
+    let mut test = DepositLockTest::new();
+    let deposit = test.build_signed_deposit(1, &key, &key, refund_lock_hash);
+    let tx = test.build_cancel_tx(
+        &[deposit],
+        vec![build_refund_cell(0), build_refund_cell(refund_capacity)],
+    );
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_deposit_lock_error(err, 0, INVALID_OUTPUT_ERROR);
+}
+
+#[test]
+fn test_cancel_two_deposits_by_signature() {
+    let key = Generator::random_privkey();
+    let refund_lock_hash: [u8; 32] = build_refund_lock().calc_script_hash().unpack();
+    let refund_capacity = DEPOSIT_CAPACITY - RELAYER_FEE;
+
+    // every deposit has its own refund cell
+    let mut test = DepositLockTest::new();
+    let deposits = [
+        test.build_signed_deposit(1, &key, &key, refund_lock_hash),
+        test.build_signed_deposit(2, &key, &key, refund_lock_hash),
+    ];
+    let tx = test.build_cancel_tx(
+        &deposits,
+        vec![
+            build_refund_cell(refund_capacity),
+            build_refund_cell(refund_capacity),
+        ],
+    );
+    test.ctx.verify_tx(tx).expect("signed cancel");
+
+    // one refund cell can't refund both deposits
+    let mut test = DepositLockTest::new();
+    let deposits = [
+        test.build_signed_deposit(1, &key, &key, refund_lock_hash),
+        test.build_signed_deposit(2, &key, &key, refund_lock_hash),
+    ];
+    let tx = test.build_cancel_tx(&deposits, vec![build_refund_cell(refund_capacity)]);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_deposit_lock_error(err, 1, INDEX_OUT_OF_BOUND_ERROR);
+}
+
+#[test]
+fn test_cancel_deposit_by_owner_since() {
+    let block_timeout = RELATIVE_BLOCK_NUMBER_FLAG | 100;
+    let epoch_timeout = RELATIVE_EPOCH_FLAG | epoch(1, 1, 2);
+    let timestamp_timeout = RELATIVE_TIMESTAMP_FLAG | 3600;
+    for (cancel_timeout, since, expected_err) in vec![
+        // block number
+        (block_timeout, RELATIVE_BLOCK_NUMBER_FLAG | 100, None),
+        (block_timeout, RELATIVE_BLOCK_NUMBER_FLAG | 101, None),
+        (
+            block_timeout,
+            RELATIVE_BLOCK_NUMBER_FLAG | 99,
+            Some(INVALID_SINCE_ERROR),
+        ),
+        // absolute since of the relative timeout
+        (block_timeout, 100, Some(INVALID_SINCE_ERROR)),
+        // mismatch metric
+        (
+            block_timeout,
+            RELATIVE_TIMESTAMP_FLAG | 100,
+            Some(INVALID_SINCE_ERROR),
+        ),
+        (
+            block_timeout,
+            RELATIVE_EPOCH_FLAG | epoch(100, 0, 1),
+            Some(INVALID_SINCE_ERROR),
+        ),
+        // epoch is compared by number, then by fraction
+        (epoch_timeout, RELATIVE_EPOCH_FLAG | epoch(1, 2, 4), None),
+        (epoch_timeout, RELATIVE_EPOCH_FLAG | epoch(1, 3, 4), None),
+        (epoch_timeout, RELATIVE_EPOCH_FLAG | epoch(2, 0, 0), None),
+        (
+            epoch_timeout,
+            RELATIVE_EPOCH_FLAG | epoch(1, 1, 4),
+            Some(INVALID_SINCE_ERROR),
+        ),
+        (
+            epoch_timeout,
+            RELATIVE_EPOCH_FLAG | epoch(0, 9, 10),
+            Some(INVALID_SINCE_ERROR),
+        ),
+        // malformed fraction
+        (
+            epoch_timeout,
+            RELATIVE_EPOCH_FLAG | epoch(1, 4, 4),
+            Some(INVALID_SINCE_ERROR),
+        ),
+        // timestamp
+        (timestamp_timeout, RELATIVE_TIMESTAMP_FLAG | 3600, None),
+        (
+            timestamp_timeout,
+            RELATIVE_TIMESTAMP_FLAG | 3599,
+            Some(INVALID_SINCE_ERROR),
+        ),
+        // invalid flags of the since
+        (
+            block_timeout,
+            0x8100_0000_0000_0000u64 | 100,
+            Some(INVALID_SINCE_ERROR),
+        ),
+    ] {
+        let mut test = DepositLockTest::new();
+        let tx = test.build_owner_cancel_tx(cancel_timeout, since);
+        let result = test.ctx.verify_tx(tx);
+        match expected_err {
+            None => {
+                result.expect("cancel deposit");
+            }
+            Some(code) => assert_deposit_lock_error(result.unwrap_err(), 0, code),
+        }
+    }
+}
+
+#[test]
+fn test_cancel_deposit_with_malformed_cancel_timeout() {
+    // the rollup rejects the deposit, the owner can cancel it at any time
+    for cancel_timeout in vec![
+        // invalid flags
+        0x8100_0000_0000_0000u64 | 100,
+        // invalid metric
+        0xe000_0000_0000_0000u64 | 100,
+        // malformed fraction
+        RELATIVE_EPOCH_FLAG | epoch(1, 2, 2),
+    ] {
+        let mut test = DepositLockTest::new();
+        let tx = test.build_owner_cancel_tx(cancel_timeout, 0);
+        test.ctx.verify_tx(tx).expect("cancel deposit");
+    }
+}
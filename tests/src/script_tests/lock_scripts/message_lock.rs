@@ -0,0 +1,311 @@
+use crate::script_tests::utils::layer1::{always_success_script, build_simple_tx};
+use crate::script_tests::utils::rollup::{
+    build_always_success_cell, build_type_id_script, CellContext, CellContextParam,
+};
+use crate::testing_tool::programs::{MESSAGE_LOCK_CODE_HASH, MESSAGE_LOCK_PROGRAM};
+use ckb_error::assert_error_eq;
+use ckb_script::ScriptError;
+use ckb_types::{
+    core::TransactionView,
+    packed::{CellDep, CellInput, CellOutput, WitnessArgs},
+    prelude::{Pack as CKBPack, Unpack as CKBUnpack},
+};
+use gw_common::{smt::SMT, sparse_merkle_tree::default_store::DefaultStore, H256};
+use gw_types::prelude::*;
+use gw_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, Status},
+    packed::{
+        BlockMerkleState, Byte32Vec, GlobalState, RawL2Block, RollupAction, RollupActionUnion,
+        RollupConfig, RollupSubmitBlock,
+    },
+};
+
+const INVALID_SINCE_ERROR: i8 = 6;
+const OWNER_CELL_NOT_FOUND_ERROR: i8 = 8;
+const INVALID_REVERTED_BLOCKS_ERROR: i8 = 31;
+
+const DELIVERED_BLOCK_HASH: [u8; 32] = [3u8; 32];
+const DELIVERED_BLOCK_NUMBER: u64 = 5;
+/// Cancel timeout of the message, an absolute block number since
+const CANCEL_TIMEOUT: u64 = 100;
+
+/// A delivered message cell and the rollup cell
+struct DeliveredMessageTest {
+    ctx: CellContext,
+    rollup_cell: CellOutput,
+    message_cell: CellOutput,
+    message_lock_dep: CellDep,
+    /// Block merkle tree of the rollup, number -> block hash
+    block_tree: SMT<DefaultStore<H256>>,
+}
+
+impl DeliveredMessageTest {
+    /// The message is delivered by the block `DELIVERED_BLOCK_NUMBER`,
+    /// which is in the canonical chain
+    fn new() -> Self {
+        Self::with_canonical_block_hash(DELIVERED_BLOCK_HASH)
+    }
+
+    /// The canonical block of `DELIVERED_BLOCK_NUMBER` is `canonical_block_hash`,
+    /// the owner is the always success script
+    fn with_canonical_block_hash(canonical_block_hash: [u8; 32]) -> Self {
+        let mut ctx = CellContext::new(&RollupConfig::default(), CellContextParam::default());
+        let rollup_type_script = build_type_id_script(b"rollup_type_id");
+        let rollup_type_hash: [u8; 32] = rollup_type_script.calc_script_hash().unpack();
+        let rollup_cell = build_always_success_cell(1000, Some(rollup_type_script));
+
+        let owner_lock_hash: [u8; 32] = always_success_script().calc_script_hash().unpack();
+        let message_lock = {
+            // rollup_type_hash | owner_lock_hash | to_id | cancel_timeout | delivered block
+            let mut args = rollup_type_hash.to_vec();
+            args.extend_from_slice(&owner_lock_hash);
+            args.extend_from_slice(&1u32.to_le_bytes());
+            args.extend_from_slice(&CANCEL_TIMEOUT.to_le_bytes());
+            args.extend_from_slice(&DELIVERED_BLOCK_HASH);
+            args.extend_from_slice(&DELIVERED_BLOCK_NUMBER.to_le_bytes());
+            ckb_types::packed::Script::new_builder()
+                .code_hash(CKBPack::pack(&*MESSAGE_LOCK_CODE_HASH))
+                .hash_type(ScriptHashType::Data.into())
+                .args(CKBPack::pack(&Bytes::from(args)))
+                .build()
+        };
+        let message_cell = CellOutput::new_builder()
+            .lock(message_lock)
+            .capacity(CKBPack::pack(&500u64))
+            .build();
+        let message_lock_dep = {
+            let cell = CellOutput::new_builder()
+                .capacity(CKBPack::pack(&(MESSAGE_LOCK_PROGRAM.len() as u64)))
+                .lock(always_success_script())
+                .build();
+            CellDep::new_builder()
+                .out_point(ctx.insert_cell(cell, MESSAGE_LOCK_PROGRAM.clone()))
+                .build()
+        };
+        let mut block_tree: SMT<DefaultStore<H256>> = Default::default();
+        for number in 0..=DELIVERED_BLOCK_NUMBER {
+            let hash = if number == DELIVERED_BLOCK_NUMBER {
+                canonical_block_hash
+            } else {
+                [number as u8 + 100; 32]
+            };
+            block_tree
+                .update(RawL2Block::compute_smt_key(number).into(), hash.into())
+                .unwrap();
+        }
+        DeliveredMessageTest {
+            ctx,
+            rollup_cell,
+            message_cell,
+            message_lock_dep,
+            block_tree,
+        }
+    }
+
+    /// Message input with the since
+    fn message_input(&mut self, since: u64) -> CellInput {
+        CellInput::new_builder()
+            .previous_output(
+                self.ctx
+                    .insert_cell(self.message_cell.clone(), Bytes::from_static(b"hello")),
+            )
+            .since(CKBPack::pack(&since))
+            .build()
+    }
+
+    /// Block merkle proof of the delivered block
+    fn delivered_block_proof(&self) -> Bytes {
+        let key: H256 = RawL2Block::compute_smt_key(DELIVERED_BLOCK_NUMBER).into();
+        self.block_tree
+            .merkle_proof(vec![key])
+            .unwrap()
+            .compile(vec![(key, DELIVERED_BLOCK_HASH.into())])
+            .unwrap()
+            .0
+            .into()
+    }
+
+    fn build_global_state(&self, last_finalized_block_number: u64) -> GlobalState {
+        let block = BlockMerkleState::new_builder()
+            .merkle_root(Pack::pack(self.block_tree.root()))
+            .count(Pack::pack(&(DELIVERED_BLOCK_NUMBER + 1)))
+            .build();
+        build_global_state(last_finalized_block_number)
+            .as_builder()
+            .block(block)
+            .build()
+    }
+
+    /// Reclaim the message by the owner cell, the rollup cell is a cell dep,
+    /// the witness lock of the message is `block_proof`
+    fn build_reclaim_tx(
+        &mut self,
+        last_finalized_block_number: u64,
+        owner_cell: CellOutput,
+        block_proof: Option<Bytes>,
+        since: u64,
+    ) -> TransactionView {
+        let global_state = self.build_global_state(last_finalized_block_number);
+        let message_input = self.message_input(since);
+        let message_witness = WitnessArgs::new_builder()
+            .lock(CKBPack::pack(&block_proof))
+            .build();
+        let rollup_dep = CellDep::new_builder()
+            .out_point(
+                self.ctx
+                    .insert_cell(self.rollup_cell.clone(), global_state.as_bytes()),
+            )
+            .build();
+        build_simple_tx(
+            &mut self.ctx.inner,
+            (owner_cell.clone(), Bytes::new()),
+            Default::default(),
+            (owner_cell, Bytes::new()),
+        )
+        .as_advanced_builder()
+        .input(message_input)
+        .witness(CKBPack::pack(&Bytes::new()))
+        .witness(CKBPack::pack(&message_witness.as_bytes()))
+        .cell_dep(self.ctx.always_success_dep.clone())
+        .cell_dep(self.message_lock_dep.clone())
+        .cell_dep(rollup_dep)
+        .build()
+    }
+
+    /// Unlock the message by the rollup cell submitting a block
+    fn build_revert_tx(&mut self, reverted_block_hashes: Vec<[u8; 32]>) -> TransactionView {
+        let global_state = self.build_global_state(0);
+        let message_input = self.message_input(0);
+        let reverted_block_hashes = reverted_block_hashes
+            .into_iter()
+            .fold(Byte32Vec::new_builder(), |builder, hash| {
+                builder.push(Pack::pack(&hash))
+            })
+            .build();
+        let rollup_action = RollupAction::new_builder()
+            .set(RollupActionUnion::RollupSubmitBlock(
+                RollupSubmitBlock::new_builder()
+                    .reverted_block_hashes(reverted_block_hashes)
+                    .build(),
+            ))
+            .build();
+        let rollup_witness = WitnessArgs::new_builder()
+            .output_type(CKBPack::pack(&Some(rollup_action.as_bytes())))
+            .build();
+        build_simple_tx(
+            &mut self.ctx.inner,
+            (self.rollup_cell.clone(), global_state.as_bytes()),
+            Default::default(),
+            (self.rollup_cell.clone(), global_state.as_bytes()),
+        )
+        .as_advanced_builder()
+        .input(message_input)
+        .cell_dep(self.ctx.always_success_dep.clone())
+        .cell_dep(self.message_lock_dep.clone())
+        .witness(CKBPack::pack(&rollup_witness.as_bytes()))
+        .build()
+    }
+}
+
+fn build_global_state(last_finalized_block_number: u64) -> GlobalState {
+    GlobalState::new_builder()
+        .last_finalized_block_number(Pack::pack(&last_finalized_block_number))
+        .status(Status::Running.into())
+        .version(1u8.into())
+        .build()
+}
+
+fn assert_message_lock_error(err: ckb_error::Error, error_code: i8) {
+    let expected_err = ScriptError::ValidationFailure(
+        format!("by-data-hash/{}", ckb_types::H256(*MESSAGE_LOCK_CODE_HASH)),
+        error_code,
+    )
+    .input_lock_script(1);
+    assert_error_eq!(err, expected_err);
+}
+
+#[test]
+fn test_reclaim_delivered_message() {
+    let owner_cell = build_always_success_cell(1000, None);
+    let mut test = DeliveredMessageTest::new();
+    let proof = test.delivered_block_proof();
+    let tx = test.build_reclaim_tx(DELIVERED_BLOCK_NUMBER, owner_cell, Some(proof), 0);
+    test.ctx.verify_tx(tx).expect("reclaim message");
+}
+
+#[test]
+fn test_reclaim_delivered_message_before_finality() {
+    let owner_cell = build_always_success_cell(1000, None);
+    let mut test = DeliveredMessageTest::new();
+    let proof = test.delivered_block_proof();
+    let tx = test.build_reclaim_tx(DELIVERED_BLOCK_NUMBER - 1, owner_cell, Some(proof), 0);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_message_lock_error(err, INVALID_REVERTED_BLOCKS_ERROR);
+}
+
+#[test]
+fn test_reclaim_delivered_message_without_block_proof() {
+    // the finalized delivered block must be proved in the canonical chain
+    let owner_cell = build_always_success_cell(1000, None);
+    let mut test = DeliveredMessageTest::new();
+    let tx = test.build_reclaim_tx(DELIVERED_BLOCK_NUMBER, owner_cell, None, 0);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_message_lock_error(err, INVALID_SINCE_ERROR);
+}
+
+#[test]
+fn test_reclaim_message_of_reverted_block() {
+    // the delivered block is reverted, another block is finalized at its number
+    let owner_cell = build_always_success_cell(1000, None);
+    let mut test = DeliveredMessageTest::with_canonical_block_hash([4u8; 32]);
+    let proof = test.delivered_block_proof();
+    let tx = test.build_reclaim_tx(
+        DELIVERED_BLOCK_NUMBER,
+        owner_cell.clone(),
+        Some(proof.clone()),
+        0,
+    );
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_message_lock_error(err, INVALID_SINCE_ERROR);
+
+    // refund after the cancel timeout
+    let mut test = DeliveredMessageTest::with_canonical_block_hash([4u8; 32]);
+    let tx = test.build_reclaim_tx(
+        DELIVERED_BLOCK_NUMBER,
+        owner_cell,
+        Some(proof),
+        CANCEL_TIMEOUT,
+    );
+    test.ctx.verify_tx(tx).expect("refund message");
+}
+
+#[test]
+fn test_reclaim_delivered_message_without_owner_cell() {
+    let other_lock = always_success_script()
+        .as_builder()
+        .args(CKBPack::pack(&Bytes::from_static(b"other")))
+        .build();
+    let other_cell = build_always_success_cell(1000, None)
+        .as_builder()
+        .lock(other_lock)
+        .build();
+    let mut test = DeliveredMessageTest::new();
+    let proof = test.delivered_block_proof();
+    let tx = test.build_reclaim_tx(DELIVERED_BLOCK_NUMBER, other_cell, Some(proof), 0);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_message_lock_error(err, OWNER_CELL_NOT_FOUND_ERROR);
+}
+
+#[test]
+fn test_unlock_delivered_message_via_revert() {
+    let mut test = DeliveredMessageTest::new();
+    let tx = test.build_revert_tx(vec![[1u8; 32], DELIVERED_BLOCK_HASH]);
+    test.ctx.verify_tx(tx).expect("unlock message");
+
+    // the delivered block isn't reverted nor proved, and the cancel timeout isn't reached
+    let mut test = DeliveredMessageTest::new();
+    let tx = test.build_revert_tx(vec![[1u8; 32]]);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_message_lock_error(err, INVALID_SINCE_ERROR);
+}
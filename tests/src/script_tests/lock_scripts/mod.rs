@@ -0,0 +1,5 @@
+mod custodian_lock;
+mod deposit_lock;
+mod message_lock;
+mod rollup_actions;
+mod withdrawal_lock;
@@ -0,0 +1,219 @@
+//! Deposit, message and stake cells unlocked by the rollup cell,
+//! the locks only accept the rollup actions which verify the cells
+
+use crate::script_tests::utils::layer1::{always_success_script, build_simple_tx};
+use crate::script_tests::utils::rollup::{
+    build_always_success_cell, build_type_id_script, CellContext, CellContextParam,
+};
+use crate::testing_tool::programs::{
+    DEPOSIT_LOCK_CODE_HASH, DEPOSIT_LOCK_PROGRAM, MESSAGE_LOCK_CODE_HASH, MESSAGE_LOCK_PROGRAM,
+    STAKE_LOCK_CODE_HASH, STAKE_LOCK_PROGRAM,
+};
+use ckb_error::assert_error_eq;
+use ckb_script::ScriptError;
+use ckb_types::{
+    core::{Cycle, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, WitnessArgs},
+    prelude::{Pack as CKBPack, Unpack as CKBUnpack},
+};
+use gw_types::prelude::*;
+use gw_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    packed::{
+        DepositLockArgs, RollupAction, RollupActionUnion, RollupCancelChallenge, RollupConfig,
+        RollupEnterChallenge, RollupRevert, RollupSubmitBlock, StakeLockArgs,
+    },
+};
+
+const INVALID_SINCE_ERROR: i8 = 6;
+const INVALID_STAKE_CELL_UNLOCK_ERROR: i8 = 24;
+
+// relative 100 blocks
+const CANCEL_TIMEOUT: u64 = 0x8000_0000_0000_0000u64 | 100;
+
+enum Action {
+    SubmitBlock,
+    EnterChallenge,
+    CancelChallenge,
+    Revert,
+    Pause,
+}
+
+const ALL_ACTIONS: [Action; 5] = [
+    Action::SubmitBlock,
+    Action::EnterChallenge,
+    Action::CancelChallenge,
+    Action::Revert,
+    Action::Pause,
+];
+
+/// Witness of the rollup action, the locks only read the action type
+fn build_rollup_action(action: &Action) -> Bytes {
+    let item = match action {
+        Action::SubmitBlock => RollupActionUnion::RollupSubmitBlock(RollupSubmitBlock::default()),
+        Action::EnterChallenge => {
+            RollupActionUnion::RollupEnterChallenge(RollupEnterChallenge::default())
+        }
+        Action::CancelChallenge => {
+            RollupActionUnion::RollupCancelChallenge(RollupCancelChallenge::default())
+        }
+        Action::Revert => RollupActionUnion::RollupRevert(RollupRevert::default()),
+        // not in the upstream union, the item is empty, see `gw_utils::cells::rollup`
+        Action::Pause => return Bytes::from(4u32.to_le_bytes().to_vec()),
+    };
+    RollupAction::new_builder().set(item).build().as_bytes()
+}
+
+/// Unlock the cell of the lock program by the rollup cell, the input since is 0
+fn build_unlock_by_rollup_tx(
+    program: &Bytes,
+    code_hash: &[u8; 32],
+    lock_args: &[u8],
+    action: &Action,
+) -> (CellContext, TransactionView) {
+    let mut ctx = CellContext::new(&RollupConfig::default(), CellContextParam::default());
+    let rollup_type_script = build_type_id_script(b"rollup_type_id");
+    let rollup_type_hash: [u8; 32] = rollup_type_script.calc_script_hash().unpack();
+    let rollup_cell = build_always_success_cell(1000, Some(rollup_type_script));
+
+    let lock = {
+        let mut args = rollup_type_hash.to_vec();
+        args.extend_from_slice(lock_args);
+        ckb_types::packed::Script::new_builder()
+            .code_hash(CKBPack::pack(code_hash))
+            .hash_type(ScriptHashType::Data.into())
+            .args(CKBPack::pack(&Bytes::from(args)))
+            .build()
+    };
+    let cell = CellOutput::new_builder()
+        .lock(lock)
+        .capacity(CKBPack::pack(&500u64))
+        .build();
+    let input = CellInput::new_builder()
+        .previous_output(ctx.insert_cell(cell, Bytes::new()))
+        .build();
+    let lock_dep = {
+        let cell = CellOutput::new_builder()
+            .capacity(CKBPack::pack(&(program.len() as u64)))
+            .lock(always_success_script())
+            .build();
+        CellDep::new_builder()
+            .out_point(ctx.insert_cell(cell, program.clone()))
+            .build()
+    };
+    let rollup_witness = WitnessArgs::new_builder()
+        .output_type(CKBPack::pack(&Some(build_rollup_action(action))))
+        .build();
+    let tx = build_simple_tx(
+        &mut ctx.inner,
+        (rollup_cell.clone(), Bytes::new()),
+        Default::default(),
+        (rollup_cell, Bytes::new()),
+    )
+    .as_advanced_builder()
+    .input(input)
+    .cell_dep(ctx.always_success_dep.clone())
+    .cell_dep(lock_dep)
+    .witness(CKBPack::pack(&rollup_witness.as_bytes()))
+    .build();
+    (ctx, tx)
+}
+
+fn verify_unlock_by_rollup(
+    program: &Bytes,
+    code_hash: &[u8; 32],
+    lock_args: &[u8],
+    action: &Action,
+) -> Result<Cycle, ckb_error::Error> {
+    let (ctx, tx) = build_unlock_by_rollup_tx(program, code_hash, lock_args, action);
+    ctx.verify_tx(tx)
+}
+
+fn assert_lock_error(err: ckb_error::Error, code_hash: &[u8; 32], error_code: i8) {
+    let expected_err = ScriptError::ValidationFailure(
+        format!("by-data-hash/{}", ckb_types::H256(*code_hash)),
+        error_code,
+    )
+    .input_lock_script(1);
+    assert_error_eq!(err, expected_err);
+}
+
+#[test]
+fn test_unlock_deposit_by_rollup_actions() {
+    let lock_args = DepositLockArgs::new_builder()
+        .owner_lock_hash(Pack::pack(&[5u8; 32]))
+        .cancel_timeout(Pack::pack(&CANCEL_TIMEOUT))
+        .build();
+    for action in ALL_ACTIONS.iter() {
+        let result = verify_unlock_by_rollup(
+            &DEPOSIT_LOCK_PROGRAM,
+            &DEPOSIT_LOCK_CODE_HASH,
+            lock_args.as_slice(),
+            action,
+        );
+        match action {
+            Action::SubmitBlock => {
+                result.expect("unlock deposit by submit block");
+            }
+            // falls through to the user unlock, the cancel timeout isn't reached
+            _ => assert_lock_error(
+                result.unwrap_err(),
+                &DEPOSIT_LOCK_CODE_HASH,
+                INVALID_SINCE_ERROR,
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_unlock_message_by_rollup_actions() {
+    // owner_lock_hash | to_id | cancel_timeout, see `gw_utils::cells::message`
+    let mut lock_args = [5u8; 32].to_vec();
+    lock_args.extend_from_slice(&1u32.to_le_bytes());
+    lock_args.extend_from_slice(&CANCEL_TIMEOUT.to_le_bytes());
+    for action in ALL_ACTIONS.iter() {
+        let result = verify_unlock_by_rollup(
+            &MESSAGE_LOCK_PROGRAM,
+            &MESSAGE_LOCK_CODE_HASH,
+            &lock_args,
+            action,
+        );
+        match action {
+            Action::SubmitBlock => {
+                result.expect("unlock message by submit block");
+            }
+            // falls through to the user unlock, the cancel timeout isn't reached
+            _ => assert_lock_error(
+                result.unwrap_err(),
+                &MESSAGE_LOCK_CODE_HASH,
+                INVALID_SINCE_ERROR,
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_unlock_stake_by_rollup_actions() {
+    let lock_args = StakeLockArgs::new_builder()
+        .owner_lock_hash(Pack::pack(&[5u8; 32]))
+        .build();
+    for action in ALL_ACTIONS.iter() {
+        let result = verify_unlock_by_rollup(
+            &STAKE_LOCK_PROGRAM,
+            &STAKE_LOCK_CODE_HASH,
+            lock_args.as_slice(),
+            action,
+        );
+        match action {
+            Action::SubmitBlock | Action::Revert => {
+                result.expect("unlock stake by submit block or revert");
+            }
+            _ => assert_lock_error(
+                result.unwrap_err(),
+                &STAKE_LOCK_CODE_HASH,
+                INVALID_STAKE_CELL_UNLOCK_ERROR,
+            ),
+        }
+    }
+}
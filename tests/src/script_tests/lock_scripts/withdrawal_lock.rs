@@ -0,0 +1,385 @@
+use crate::script_tests::utils::layer1::{always_success_script, build_simple_tx};
+use crate::script_tests::utils::rollup::{
+    build_always_success_cell, build_rollup_config_with_extra_fields, build_type_id_script,
+    CellContext, CellContextParam,
+};
+use crate::testing_tool::programs::{WITHDRAWAL_LOCK_CODE_HASH, WITHDRAWAL_LOCK_PROGRAM};
+use ckb_error::assert_error_eq;
+use ckb_script::ScriptError;
+use ckb_types::{
+    core::TransactionView,
+    packed::{CellDep, CellInput, CellOutput, WitnessArgs},
+    prelude::{Pack as CKBPack, Unpack as CKBUnpack},
+};
+use gw_types::prelude::*;
+use gw_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, Status},
+    packed::{
+        GlobalState, RollupConfig, Script, UnlockWithdrawalViaFinalize, UnlockWithdrawalViaTrade,
+        UnlockWithdrawalWitness, UnlockWithdrawalWitnessUnion, WithdrawalLockArgs,
+    },
+};
+
+const INVALID_ARGS_ERROR: i8 = 5;
+const INVALID_OUTPUT_ERROR: i8 = 7;
+const ROLLUP_PAUSED_ERROR: i8 = 51;
+
+const MAX_PAUSE_DURATION: usize = 14;
+
+const ABSOLUTE_TIMESTAMP_FLAG: u64 = 0x4000_0000_0000_0000;
+const RELATIVE_TIMESTAMP_FLAG: u64 = 0xc000_0000_0000_0000;
+
+const WITHDRAWAL_BLOCK_NUMBER: u64 = 10;
+const MAX_PAUSE_DURATION_MS: u64 = 100_000;
+const PAUSED_AT: u64 = 1_000_000_000;
+const SELL_CAPACITY: u64 = 300;
+
+/// Pause of the rollup cell and the timestamps to check it, see `gw_utils::pause`
+#[derive(Default)]
+struct PauseParam {
+    paused_at: Option<u64>,
+    tip_block_timestamp: u64,
+    /// since of the withdrawal input
+    since: u64,
+}
+
+/// Finalize the withdrawal cell, the rollup cell is a cell dep,
+/// `extra_finality_blocks` is appended to the lock args if it isn't None
+fn build_finalize_tx(
+    extra_finality_blocks: Option<u64>,
+    last_finalized_block_number: u64,
+    pause: PauseParam,
+) -> (CellContext, TransactionView) {
+    let rollup_config = {
+        let mut extra_fields = vec![Bytes::new(); MAX_PAUSE_DURATION + 1];
+        extra_fields[MAX_PAUSE_DURATION] =
+            Bytes::from(MAX_PAUSE_DURATION_MS.to_le_bytes().to_vec());
+        build_rollup_config_with_extra_fields(&RollupConfig::default(), &extra_fields)
+    };
+    let mut ctx = CellContext::new(&rollup_config, CellContextParam::default());
+    let rollup_type_script = build_type_id_script(b"rollup_type_id");
+    let rollup_type_hash: [u8; 32] = rollup_type_script.calc_script_hash().unpack();
+    let rollup_cell = build_always_success_cell(1000, Some(rollup_type_script));
+
+    let owner_lock_hash: [u8; 32] = always_success_script().calc_script_hash().unpack();
+    let withdrawal_lock = {
+        // rollup_type_hash | WithdrawalLockArgs | extra_finality_blocks, see `gw_utils::withdrawal`
+        let lock_args = WithdrawalLockArgs::new_builder()
+            .withdrawal_block_number(Pack::pack(&WITHDRAWAL_BLOCK_NUMBER))
+            .owner_lock_hash(Pack::pack(&owner_lock_hash))
+            .build();
+        let mut args = rollup_type_hash.to_vec();
+        args.extend_from_slice(lock_args.as_slice());
+        if let Some(extra_finality_blocks) = extra_finality_blocks {
+            args.extend_from_slice(&extra_finality_blocks.to_le_bytes());
+        }
+        ckb_types::packed::Script::new_builder()
+            .code_hash(CKBPack::pack(&*WITHDRAWAL_LOCK_CODE_HASH))
+            .hash_type(ScriptHashType::Data.into())
+            .args(CKBPack::pack(&Bytes::from(args)))
+            .build()
+    };
+    let withdrawal_cell = CellOutput::new_builder()
+        .lock(withdrawal_lock)
+        .capacity(CKBPack::pack(&500u64))
+        .build();
+    let withdrawal_input = CellInput::new_builder()
+        .previous_output(ctx.insert_cell(withdrawal_cell, Bytes::new()))
+        .since(CKBPack::pack(&pause.since))
+        .build();
+    let withdrawal_lock_dep = {
+        let cell = CellOutput::new_builder()
+            .capacity(CKBPack::pack(&(WITHDRAWAL_LOCK_PROGRAM.len() as u64)))
+            .lock(always_success_script())
+            .build();
+        CellDep::new_builder()
+            .out_point(ctx.insert_cell(cell, WITHDRAWAL_LOCK_PROGRAM.clone()))
+            .build()
+    };
+    let global_state = GlobalState::new_builder()
+        .last_finalized_block_number(Pack::pack(&last_finalized_block_number))
+        .tip_block_timestamp(Pack::pack(&pause.tip_block_timestamp))
+        .rollup_config_hash(Pack::pack(&rollup_config.hash()))
+        .status(Status::Running.into())
+        .version(1u8.into())
+        .build();
+    // GlobalState | paused_at, see `gw_utils::pause`
+    let mut rollup_cell_data = global_state.as_slice().to_vec();
+    if let Some(paused_at) = pause.paused_at {
+        rollup_cell_data.extend_from_slice(&paused_at.to_le_bytes());
+    }
+    let rollup_dep = CellDep::new_builder()
+        .out_point(ctx.insert_cell(rollup_cell, Bytes::from(rollup_cell_data)))
+        .build();
+    let unlock_witness = {
+        let unlock_args = UnlockWithdrawalWitness::new_builder()
+            .set(UnlockWithdrawalWitnessUnion::UnlockWithdrawalViaFinalize(
+                UnlockWithdrawalViaFinalize::default(),
+            ))
+            .build();
+        WitnessArgs::new_builder()
+            .lock(CKBPack::pack(&Some(unlock_args.as_bytes())))
+            .build()
+    };
+
+    let owner_cell = build_always_success_cell(1000, None);
+    let tx = build_simple_tx(
+        &mut ctx.inner,
+        (owner_cell.clone(), Bytes::new()),
+        Default::default(),
+        (owner_cell, Bytes::new()),
+    )
+    .as_advanced_builder()
+    .input(withdrawal_input)
+    .witness(CKBPack::pack(&Bytes::new()))
+    .witness(CKBPack::pack(&unlock_witness.as_bytes()))
+    .cell_dep(ctx.always_success_dep.clone())
+    .cell_dep(withdrawal_lock_dep)
+    .cell_dep(rollup_dep)
+    .cell_dep(ctx.rollup_config_dep.clone())
+    .build();
+    (ctx, tx)
+}
+
+fn assert_withdrawal_lock_error(err: ckb_error::Error, error_code: i8) {
+    let expected_err = ScriptError::ValidationFailure(
+        format!(
+            "by-data-hash/{}",
+            ckb_types::H256(*WITHDRAWAL_LOCK_CODE_HASH)
+        ),
+        error_code,
+    )
+    .input_lock_script(1);
+    assert_error_eq!(err, expected_err);
+}
+
+#[test]
+fn test_finalize_withdrawal() {
+    // without the finality tier
+    let (ctx, tx) = build_finalize_tx(None, WITHDRAWAL_BLOCK_NUMBER, Default::default());
+    ctx.verify_tx(tx).expect("finalize withdrawal");
+
+    let (ctx, tx) = build_finalize_tx(None, WITHDRAWAL_BLOCK_NUMBER - 1, Default::default());
+    let err = ctx.verify_tx(tx).unwrap_err();
+    assert_withdrawal_lock_error(err, INVALID_ARGS_ERROR);
+}
+
+#[test]
+fn test_finalize_withdrawal_with_extra_finality_blocks() {
+    let extra_finality_blocks = 100u64;
+    let (ctx, tx) = build_finalize_tx(
+        Some(extra_finality_blocks),
+        WITHDRAWAL_BLOCK_NUMBER + extra_finality_blocks,
+        Default::default(),
+    );
+    ctx.verify_tx(tx).expect("finalize withdrawal");
+
+    // the withdrawal block is finalized, but the extra blocks aren't
+    for last_finalized_block_number in vec![
+        WITHDRAWAL_BLOCK_NUMBER,
+        WITHDRAWAL_BLOCK_NUMBER + extra_finality_blocks - 1,
+    ] {
+        let (ctx, tx) = build_finalize_tx(
+            Some(extra_finality_blocks),
+            last_finalized_block_number,
+            Default::default(),
+        );
+        let err = ctx.verify_tx(tx).unwrap_err();
+        assert_withdrawal_lock_error(err, INVALID_ARGS_ERROR);
+    }
+
+    // a zero suffix is the same as the global finality
+    let (ctx, tx) = build_finalize_tx(Some(0), WITHDRAWAL_BLOCK_NUMBER, Default::default());
+    ctx.verify_tx(tx).expect("finalize withdrawal");
+}
+
+#[test]
+fn test_finalize_withdrawal_paused() {
+    let pause_end = PAUSED_AT + MAX_PAUSE_DURATION_MS;
+    // the since is in seconds
+    let cases = vec![
+        // the tip block timestamp is frozen while paused
+        (PAUSED_AT, 0, Some(ROLLUP_PAUSED_ERROR)),
+        (pause_end - 1, 0, Some(ROLLUP_PAUSED_ERROR)),
+        (pause_end, 0, None),
+        // an absolute timestamp input since proves the pause has expired
+        (
+            PAUSED_AT,
+            ABSOLUTE_TIMESTAMP_FLAG | (pause_end / 1000),
+            None,
+        ),
+        (
+            PAUSED_AT,
+            ABSOLUTE_TIMESTAMP_FLAG | (pause_end / 1000 - 1),
+            Some(ROLLUP_PAUSED_ERROR),
+        ),
+        // the tip block timestamp is 0 in the v0 global state
+        (0, ABSOLUTE_TIMESTAMP_FLAG | (pause_end / 1000), None),
+        (
+            0,
+            RELATIVE_TIMESTAMP_FLAG | (pause_end / 1000),
+            Some(ROLLUP_PAUSED_ERROR),
+        ),
+    ];
+    for (tip_block_timestamp, since, expected_err) in cases {
+        let pause = PauseParam {
+            paused_at: Some(PAUSED_AT),
+            tip_block_timestamp,
+            since,
+        };
+        let (ctx, tx) = build_finalize_tx(None, WITHDRAWAL_BLOCK_NUMBER, pause);
+        let result = ctx.verify_tx(tx);
+        match expected_err {
+            None => {
+                result.expect("finalize withdrawal");
+            }
+            Some(code) => assert_withdrawal_lock_error(result.unwrap_err(), code),
+        }
+    }
+}
+
+/// Output withdrawal lock of a trade, see `build_traded_withdrawal_lock` of the withdrawal lock
+enum TradeOutput {
+    /// the withdrawal lock owned by the buyer, with the extra finality blocks
+    WithdrawalLock,
+    /// the withdrawal lock owned by the buyer, without the extra finality blocks
+    WithdrawalLockWithoutExtraFinalityBlocks,
+    /// the lock of the buyer
+    BuyerLock,
+}
+
+/// Buy the withdrawal cell for sell, the seller is paid by `SELL_CAPACITY`
+fn build_trade_tx(
+    extra_finality_blocks: u64,
+    trade_output: TradeOutput,
+) -> (CellContext, TransactionView) {
+    let mut ctx = CellContext::new(&RollupConfig::default(), CellContextParam::default());
+    let rollup_type_hash: [u8; 32] = build_type_id_script(b"rollup_type_id")
+        .calc_script_hash()
+        .unpack();
+    let build_lock = |args: &'static [u8]| {
+        always_success_script()
+            .as_builder()
+            .args(CKBPack::pack(&Bytes::from_static(args)))
+            .build()
+    };
+    let seller_lock = build_lock(b"seller");
+    let buyer_lock = build_lock(b"buyer");
+    let seller_lock_hash: [u8; 32] = seller_lock.calc_script_hash().unpack();
+    let buyer_lock_hash: [u8; 32] = buyer_lock.calc_script_hash().unpack();
+
+    // rollup_type_hash | WithdrawalLockArgs | extra_finality_blocks, see `gw_utils::withdrawal`
+    let build_withdrawal_lock = |lock_args: WithdrawalLockArgs, extra_finality_blocks: u64| {
+        let mut args = rollup_type_hash.to_vec();
+        args.extend_from_slice(lock_args.as_slice());
+        if extra_finality_blocks > 0 {
+            args.extend_from_slice(&extra_finality_blocks.to_le_bytes());
+        }
+        ckb_types::packed::Script::new_builder()
+            .code_hash(CKBPack::pack(&*WITHDRAWAL_LOCK_CODE_HASH))
+            .hash_type(ScriptHashType::Data.into())
+            .args(CKBPack::pack(&Bytes::from(args)))
+            .build()
+    };
+    let lock_args = WithdrawalLockArgs::new_builder()
+        .withdrawal_block_number(Pack::pack(&WITHDRAWAL_BLOCK_NUMBER))
+        .owner_lock_hash(Pack::pack(&seller_lock_hash))
+        .payment_lock_hash(Pack::pack(&seller_lock_hash))
+        .sell_capacity(Pack::pack(&SELL_CAPACITY))
+        .build();
+    let withdrawal_cell = CellOutput::new_builder()
+        .lock(build_withdrawal_lock(
+            lock_args.clone(),
+            extra_finality_blocks,
+        ))
+        .capacity(CKBPack::pack(&500u64))
+        .build();
+    let withdrawal_input = CellInput::new_builder()
+        .previous_output(ctx.insert_cell(withdrawal_cell.clone(), Bytes::new()))
+        .build();
+    let withdrawal_lock_dep = {
+        let cell = CellOutput::new_builder()
+            .capacity(CKBPack::pack(&(WITHDRAWAL_LOCK_PROGRAM.len() as u64)))
+            .lock(always_success_script())
+            .build();
+        CellDep::new_builder()
+            .out_point(ctx.insert_cell(cell, WITHDRAWAL_LOCK_PROGRAM.clone()))
+            .build()
+    };
+
+    let traded_lock_args = lock_args
+        .as_builder()
+        .owner_lock_hash(Pack::pack(&buyer_lock_hash))
+        .payment_lock_hash(Pack::pack(&buyer_lock_hash))
+        .sell_capacity(Pack::pack(&0u64))
+        .build();
+    let output_lock = match trade_output {
+        TradeOutput::WithdrawalLock => {
+            build_withdrawal_lock(traded_lock_args, extra_finality_blocks)
+        }
+        TradeOutput::WithdrawalLockWithoutExtraFinalityBlocks => {
+            build_withdrawal_lock(traded_lock_args, 0)
+        }
+        TradeOutput::BuyerLock => buyer_lock.clone(),
+    };
+    let traded_cell = withdrawal_cell.as_builder().lock(output_lock).build();
+    let payment_cell = CellOutput::new_builder()
+        .lock(seller_lock)
+        .capacity(CKBPack::pack(&SELL_CAPACITY))
+        .build();
+    let unlock_witness = {
+        let unlock_args = UnlockWithdrawalWitness::new_builder()
+            .set(UnlockWithdrawalWitnessUnion::UnlockWithdrawalViaTrade(
+                UnlockWithdrawalViaTrade::new_builder()
+                    .owner_lock(Script::new_unchecked(buyer_lock.as_bytes()))
+                    .build(),
+            ))
+            .build();
+        WitnessArgs::new_builder()
+            .lock(CKBPack::pack(&Some(unlock_args.as_bytes())))
+            .build()
+    };
+
+    let buyer_cell = build_always_success_cell(1000, None)
+        .as_builder()
+        .lock(buyer_lock)
+        .build();
+    let tx = build_simple_tx(
+        &mut ctx.inner,
+        (buyer_cell, Bytes::new()),
+        Default::default(),
+        (traded_cell, Bytes::new()),
+    )
+    .as_advanced_builder()
+    .input(withdrawal_input)
+    .output(payment_cell)
+    .output_data(CKBPack::pack(&Bytes::new()))
+    .witness(CKBPack::pack(&Bytes::new()))
+    .witness(CKBPack::pack(&unlock_witness.as_bytes()))
+    .cell_dep(ctx.always_success_dep.clone())
+    .cell_dep(withdrawal_lock_dep)
+    .build();
+    (ctx, tx)
+}
+
+#[test]
+fn test_trade_withdrawal() {
+    for extra_finality_blocks in vec![0, 100] {
+        let (ctx, tx) = build_trade_tx(extra_finality_blocks, TradeOutput::WithdrawalLock);
+        ctx.verify_tx(tx).expect("trade withdrawal");
+    }
+}
+
+#[test]
+fn test_trade_withdrawal_skipping_finality() {
+    // the buyer can't take the cell out of the withdrawal lock before the finality
+    let (ctx, tx) = build_trade_tx(0, TradeOutput::BuyerLock);
+    let err = ctx.verify_tx(tx).unwrap_err();
+    assert_withdrawal_lock_error(err, INVALID_OUTPUT_ERROR);
+
+    // the extra finality blocks are kept for the buyer
+    let (ctx, tx) = build_trade_tx(100, TradeOutput::WithdrawalLockWithoutExtraFinalityBlocks);
+    let err = ctx.verify_tx(tx).unwrap_err();
+    assert_withdrawal_lock_error(err, INVALID_OUTPUT_ERROR);
+}
@@ -1,5 +1,6 @@
 mod account_lock_scripts;
 mod l2_scripts;
 mod l2_scripts_validator;
+mod lock_scripts;
 mod state_validator;
 pub mod utils;
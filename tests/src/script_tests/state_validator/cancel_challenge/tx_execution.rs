@@ -4,8 +4,8 @@ use crate::script_tests::utils::init_env_log;
 use crate::script_tests::utils::layer1::build_simple_tx_with_out_point;
 use crate::script_tests::utils::layer1::random_out_point;
 use crate::script_tests::utils::rollup::{
-    build_always_success_cell, build_rollup_locked_cell, build_type_id_script,
-    calculate_state_validator_type_id, CellContext, CellContextParam,
+    build_always_success_cell, build_rollup_config_with_extra_fields, build_rollup_locked_cell,
+    build_type_id_script, calculate_state_validator_type_id, CellContext, CellContextParam,
 };
 use crate::testing_tool::chain::setup_chain;
 use crate::testing_tool::chain::{apply_block_result, construct_block};
@@ -28,14 +28,33 @@ use gw_types::{
     core::{ChallengeTargetType, ScriptHashType, Status},
     packed::{
         BlockHashEntry, BlockHashEntryVec, ChallengeLockArgs, ChallengeTarget, DepositRequest,
-        L2Transaction, RawL2Transaction, RollupAction, RollupActionUnion, RollupCancelChallenge,
-        RollupConfig, SUDTArgs, SUDTTransfer, Script, ScriptVec, VerifyTransactionContext,
-        VerifyTransactionWitness,
+        GlobalState, L2Transaction, RawL2Transaction, RollupAction, RollupActionUnion,
+        RollupCancelChallenge, RollupConfig, SUDTArgs, SUDTTransfer, Script, ScriptVec,
+        VerifyTransactionContext, VerifyTransactionWitness,
     },
 };
 
 #[test]
 fn test_cancel_tx_execute() {
+    verify_cancel_tx_execute(&[], None);
+}
+
+#[test]
+fn test_cancel_tx_execute_with_extended_rollup_config() {
+    // the validators must accept a config with extra fields
+    let max_txs_per_block = Bytes::from(100u32.to_le_bytes().to_vec());
+    verify_cancel_tx_execute(&[max_txs_per_block], None);
+}
+
+#[test]
+fn test_cancel_tx_execute_while_paused() {
+    // the validators must accept a rollup cell with the paused_at, see `gw_utils::pause`
+    verify_cancel_tx_execute(&[], Some(1000));
+}
+
+/// Cancel a tx execution challenge, `extra_fields` are appended to the rollup config,
+/// `paused_at` is appended to the rollup cell data
+fn verify_cancel_tx_execute(extra_fields: &[Bytes], paused_at: Option<u64>) {
     init_env_log();
     let input_out_point = random_out_point();
     let type_id = calculate_state_validator_type_id(input_out_point.clone());
@@ -62,6 +81,7 @@ fn test_cancel_tx_execute() {
         .finality_blocks(Pack::pack(&finality_blocks))
         .allowed_eoa_type_hashes(vec![*ALWAYS_SUCCESS_CODE_HASH].pack())
         .build();
+    let rollup_config = build_rollup_config_with_extra_fields(&rollup_config, extra_fields);
     // setup chain
     let mut chain = setup_chain(rollup_type_script.clone(), rollup_config.clone());
     // create a rollup cell
@@ -214,7 +234,14 @@ fn test_cancel_tx_execute() {
         .as_builder()
         .status(Status::Halting.into())
         .build();
-    let initial_rollup_cell_data = global_state.as_bytes();
+    let build_rollup_cell_data = |global_state: &GlobalState| {
+        let mut data = global_state.as_slice().to_vec();
+        if let Some(paused_at) = paused_at {
+            data.extend_from_slice(&paused_at.to_le_bytes());
+        }
+        Bytes::from(data)
+    };
+    let initial_rollup_cell_data = build_rollup_cell_data(&global_state);
     // verify enter challenge
     let witness = {
         let rollup_action = RollupAction::new_builder()
@@ -346,12 +373,13 @@ fn test_cancel_tx_execute() {
         let out_point = ctx.insert_cell(cell, Bytes::default());
         CellInput::new_builder().previous_output(out_point).build()
     };
-    let rollup_cell_data = global_state
-        .clone()
-        .as_builder()
-        .status(Status::Running.into())
-        .build()
-        .as_bytes();
+    let rollup_cell_data = build_rollup_cell_data(
+        &global_state
+            .clone()
+            .as_builder()
+            .status(Status::Running.into())
+            .build(),
+    );
     let tx = build_simple_tx_with_out_point(
         &mut ctx.inner,
         (rollup_cell.clone(), initial_rollup_cell_data),
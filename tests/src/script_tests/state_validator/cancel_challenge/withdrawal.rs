@@ -4,6 +4,7 @@ use crate::script_tests::state_validator::cancel_challenge::build_merkle_proof;
 use crate::script_tests::utils::init_env_log;
 use crate::script_tests::utils::layer1::build_simple_tx_with_out_point;
 use crate::script_tests::utils::layer1::random_out_point;
+use crate::script_tests::utils::layer2::{calculate_withdrawal_message, with_valid_until};
 use crate::script_tests::utils::rollup::{
     build_always_success_cell, build_rollup_locked_cell, build_type_id_script,
     calculate_state_validator_type_id, CellContext, CellContextParam,
@@ -12,23 +13,33 @@ use crate::testing_tool::chain::{
     apply_block_result, construct_block, setup_chain_with_account_lock_manage,
 };
 use crate::testing_tool::programs::STATE_VALIDATOR_CODE_HASH;
+use ckb_error::assert_error_eq;
+use ckb_script::ScriptError;
 use ckb_types::{
+    core::Cycle,
     packed::{CellInput, CellOutput},
     prelude::{Pack as CKBPack, Unpack},
 };
-use gw_common::H256;
+use gw_common::{
+    blake2b::new_blake2b,
+    merkle_utils::{calculate_ckb_merkle_root, ckb_merkle_leaf_hash},
+    H256,
+};
 use gw_generator::account_lock_manage::{always_success::AlwaysSuccess, AccountLockManage};
 use gw_types::prelude::*;
 use gw_types::{
     bytes::Bytes,
     core::{ChallengeTargetType, ScriptHashType, Status},
     packed::{
-        Byte32, ChallengeLockArgs, ChallengeTarget, DepositRequest, RawWithdrawalRequest,
-        RollupAction, RollupActionUnion, RollupCancelChallenge, RollupConfig, Script,
-        VerifyWithdrawalWitness, WithdrawalRequest,
+        Byte32, ChallengeLockArgs, ChallengeTarget, DepositRequest, GlobalState, RawL2Block,
+        RawWithdrawalRequest, RollupAction, RollupActionUnion, RollupCancelChallenge, RollupConfig,
+        Script, SubmitWithdrawals, VerifyWithdrawalWitness, WithdrawalRequest,
     },
 };
 
+const INVALID_WITHDRAWAL_REQUEST_ERROR: i8 = 35;
+const ACCOUNT_LOCK_CELL_NOT_FOUND_ERROR: i8 = 39;
+
 #[test]
 fn test_cancel_withdrawal() {
     init_env_log();
@@ -256,3 +267,276 @@ fn test_cancel_withdrawal() {
     .build();
     ctx.verify_tx(tx).expect("return success");
 }
+
+/// Cancel the challenge of the withdrawal in a block built by hand,
+/// the sender signs the message in the signature cell
+fn verify_cancel_withdrawal_challenge(
+    build_withdrawal: impl FnOnce(H256) -> WithdrawalRequest,
+    block_number: u64,
+    block_timestamp: u64,
+    build_message: impl FnOnce(&H256, &WithdrawalRequest) -> H256,
+) -> Result<Cycle, ckb_error::Error> {
+    let input_out_point = random_out_point();
+    let type_id = calculate_state_validator_type_id(input_out_point.clone());
+    let rollup_type_script = Script::new_builder()
+        .code_hash(Pack::pack(&*STATE_VALIDATOR_CODE_HASH))
+        .hash_type(ScriptHashType::Data.into())
+        .args(Pack::pack(&Bytes::from(type_id.to_vec())))
+        .build();
+    let rollup_type_hash: H256 = rollup_type_script.hash().into();
+    let stake_lock_type = build_type_id_script(b"stake_lock_type_id");
+    let challenge_lock_type = build_type_id_script(b"challenge_lock_type_id");
+    let eoa_lock_type = build_type_id_script(b"eoa_lock_type_id");
+    let challenge_script_type_hash: [u8; 32] = challenge_lock_type.calc_script_hash().unpack();
+    let eoa_lock_type_hash: [u8; 32] = eoa_lock_type.calc_script_hash().unpack();
+    let rollup_config = RollupConfig::new_builder()
+        .challenge_script_type_hash(Pack::pack(&challenge_script_type_hash))
+        .allowed_eoa_type_hashes(PackVec::pack(vec![Pack::pack(&eoa_lock_type_hash)]))
+        .finality_blocks(Pack::pack(&10u64))
+        .build();
+    let param = CellContextParam {
+        stake_lock_type,
+        challenge_lock_type,
+        eoa_lock_type,
+        ..Default::default()
+    };
+    let mut ctx = CellContext::new(&rollup_config, param);
+
+    // the challenged block with the withdrawal
+    let sender_script = {
+        let mut args = rollup_type_hash.as_slice().to_vec();
+        args.extend_from_slice(b"sender");
+        Script::new_builder()
+            .code_hash(Pack::pack(&eoa_lock_type_hash))
+            .hash_type(ScriptHashType::Type.into())
+            .args(Pack::pack(&Bytes::from(args)))
+            .build()
+    };
+    let withdrawal = build_withdrawal(sender_script.hash().into());
+    let withdrawal_hash: H256 = withdrawal.witness_hash().into();
+    let withdrawal_witness_root =
+        calculate_ckb_merkle_root(vec![ckb_merkle_leaf_hash(0, &withdrawal_hash)]).unwrap();
+    let raw_block = RawL2Block::new_builder()
+        .number(Pack::pack(&block_number))
+        .timestamp(Pack::pack(&block_timestamp))
+        .submit_withdrawals(
+            SubmitWithdrawals::new_builder()
+                .withdrawal_witness_root(Pack::pack(&withdrawal_witness_root))
+                .withdrawal_count(Pack::pack(&1u32))
+                .build(),
+        )
+        .build();
+
+    let rollup_cell = build_always_success_cell(
+        1000_00000000u64,
+        Some(ckb_types::packed::Script::new_unchecked(
+            rollup_type_script.as_bytes(),
+        )),
+    );
+    let rollup_config_hash = {
+        let mut hash = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(rollup_config.as_slice());
+        hasher.finalize(&mut hash);
+        hash
+    };
+    let global_state = GlobalState::new_builder()
+        .rollup_config_hash(Pack::pack(&rollup_config_hash))
+        .status(Status::Halting.into())
+        .build();
+    let input_challenge_cell = {
+        let lock_args = ChallengeLockArgs::new_builder()
+            .target(
+                ChallengeTarget::new_builder()
+                    .target_index(Pack::pack(&0u32))
+                    .target_type(ChallengeTargetType::Withdrawal.into())
+                    .block_hash(Pack::pack(&raw_block.hash()))
+                    .build(),
+            )
+            .build();
+        let cell = build_rollup_locked_cell(
+            &rollup_type_script.hash(),
+            &challenge_script_type_hash,
+            10000_00000000u64,
+            lock_args.as_bytes(),
+        );
+        let out_point = ctx.insert_cell(cell, Bytes::new());
+        CellInput::new_builder().previous_output(out_point).build()
+    };
+    let witness = {
+        let rollup_action = RollupAction::new_builder()
+            .set(RollupActionUnion::RollupCancelChallenge(
+                RollupCancelChallenge::default(),
+            ))
+            .build();
+        ckb_types::packed::WitnessArgs::new_builder()
+            .output_type(CKBPack::pack(&Some(rollup_action.as_bytes())))
+            .build()
+    };
+    let challenge_witness = {
+        let witness = VerifyWithdrawalWitness::new_builder()
+            .raw_l2block(raw_block)
+            .withdrawal_request(withdrawal.clone())
+            .withdrawal_proof(build_merkle_proof(&[withdrawal_hash], &[0]))
+            .build();
+        ckb_types::packed::WitnessArgs::new_builder()
+            .lock(CKBPack::pack(&Some(witness.as_bytes())))
+            .build()
+    };
+    // signature cell: owner_lock_hash | message
+    let input_unlock_cell = {
+        let cell = CellOutput::new_builder()
+            .lock(ckb_types::packed::Script::new_unchecked(
+                sender_script.as_bytes(),
+            ))
+            .capacity(CKBPack::pack(&42u64))
+            .build();
+        let mut data = vec![42u8; 32];
+        data.extend_from_slice(build_message(&rollup_type_hash, &withdrawal).as_slice());
+        let out_point = ctx.insert_cell(cell, Bytes::from(data));
+        CellInput::new_builder().previous_output(out_point).build()
+    };
+    let rollup_cell_data = global_state
+        .clone()
+        .as_builder()
+        .status(Status::Running.into())
+        .build()
+        .as_bytes();
+    let tx = build_simple_tx_with_out_point(
+        &mut ctx.inner,
+        (rollup_cell.clone(), global_state.as_bytes()),
+        input_out_point,
+        (rollup_cell, rollup_cell_data),
+    )
+    .as_advanced_builder()
+    .witness(CKBPack::pack(&witness.as_bytes()))
+    .input(input_challenge_cell)
+    .witness(CKBPack::pack(&challenge_witness.as_bytes()))
+    .input(input_unlock_cell)
+    .witness(Default::default())
+    .cell_dep(ctx.challenge_lock_dep.clone())
+    .cell_dep(ctx.stake_lock_dep.clone())
+    .cell_dep(ctx.always_success_dep.clone())
+    .cell_dep(ctx.state_validator_dep.clone())
+    .cell_dep(ctx.rollup_config_dep.clone())
+    .cell_dep(ctx.eoa_lock_dep.clone())
+    .build();
+    ctx.verify_tx(tx)
+}
+
+fn assert_challenge_lock_error(err: ckb_error::Error, error_code: i8) {
+    let challenge_script_type_hash: [u8; 32] = build_type_id_script(b"challenge_lock_type_id")
+        .calc_script_hash()
+        .unpack();
+    let expected_err = ScriptError::ValidationFailure(
+        format!(
+            "by-type-hash/{}",
+            ckb_types::H256(challenge_script_type_hash)
+        ),
+        error_code,
+    )
+    .input_lock_script(1);
+    assert_error_eq!(err, expected_err);
+}
+
+fn build_withdrawal_request(account_script_hash: H256) -> WithdrawalRequest {
+    let capacity = 300_00000000u64;
+    WithdrawalRequest::new_builder()
+        .raw(
+            RawWithdrawalRequest::new_builder()
+                .nonce(Pack::pack(&0u32))
+                .capacity(Pack::pack(&capacity))
+                .account_script_hash(Pack::pack(&account_script_hash))
+                .sell_capacity(Pack::pack(&capacity))
+                .build(),
+        )
+        .build()
+}
+
+#[test]
+fn test_cancel_withdrawal_with_valid_until() {
+    const ABSOLUTE_TIMESTAMP_FLAG: u64 = 0x4000_0000_0000_0000;
+    let block_number = 5u64;
+    let block_timestamp = 10000u64;
+    let signed_valid_until = |valid_until: u64| {
+        move |rollup_type_hash: &H256, withdrawal: &WithdrawalRequest| {
+            calculate_withdrawal_message(rollup_type_hash, withdrawal, valid_until)
+        }
+    };
+
+    // not expired, signed with the valid until
+    for valid_until in vec![
+        block_number,
+        block_number + 1,
+        ABSOLUTE_TIMESTAMP_FLAG | block_timestamp,
+    ] {
+        verify_cancel_withdrawal_challenge(
+            |sender| with_valid_until(build_withdrawal_request(sender), valid_until),
+            block_number,
+            block_timestamp,
+            signed_valid_until(valid_until),
+        )
+        .expect("cancel challenge");
+    }
+
+    // the challenge of an expired withdrawal can't be cancelled
+    for valid_until in vec![
+        block_number - 1,
+        ABSOLUTE_TIMESTAMP_FLAG | (block_timestamp - 1),
+    ] {
+        let err = verify_cancel_withdrawal_challenge(
+            |sender| with_valid_until(build_withdrawal_request(sender), valid_until),
+            block_number,
+            block_timestamp,
+            signed_valid_until(valid_until),
+        )
+        .unwrap_err();
+        assert_challenge_lock_error(err, INVALID_WITHDRAWAL_REQUEST_ERROR);
+    }
+}
+
+#[test]
+fn test_cancel_withdrawal_signing_message() {
+    let block_number = 5u64;
+    let valid_until = block_number + 1;
+    let old_message = |rollup_type_hash: &H256, withdrawal: &WithdrawalRequest| {
+        withdrawal.raw().calc_message(rollup_type_hash)
+    };
+
+    // a withdrawal without valid until signs hash(rollup_type_hash | raw)
+    verify_cancel_withdrawal_challenge(build_withdrawal_request, block_number, 0, old_message)
+        .expect("cancel challenge");
+
+    // a withdrawal with valid until signs hash(rollup_type_hash | raw | valid_until)
+    verify_cancel_withdrawal_challenge(
+        |sender| with_valid_until(build_withdrawal_request(sender), valid_until),
+        block_number,
+        0,
+        |rollup_type_hash, withdrawal| {
+            calculate_withdrawal_message(rollup_type_hash, withdrawal, valid_until)
+        },
+    )
+    .expect("cancel challenge");
+
+    // the signature without the valid until can't cancel the challenge
+    let err = verify_cancel_withdrawal_challenge(
+        |sender| with_valid_until(build_withdrawal_request(sender), valid_until),
+        block_number,
+        0,
+        old_message,
+    )
+    .unwrap_err();
+    assert_challenge_lock_error(err, ACCOUNT_LOCK_CELL_NOT_FOUND_ERROR);
+
+    // the message commits the valid until
+    let err = verify_cancel_withdrawal_challenge(
+        |sender| with_valid_until(build_withdrawal_request(sender), valid_until),
+        block_number,
+        0,
+        |rollup_type_hash, withdrawal| {
+            calculate_withdrawal_message(rollup_type_hash, withdrawal, valid_until + 1)
+        },
+    )
+    .unwrap_err();
+    assert_challenge_lock_error(err, ACCOUNT_LOCK_CELL_NOT_FOUND_ERROR);
+}
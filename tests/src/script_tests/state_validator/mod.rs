@@ -1,4 +1,5 @@
 mod cancel_challenge;
 mod enter_challenge;
+mod pause;
 mod revert;
 mod submit_block;
@@ -0,0 +1,222 @@
+use crate::script_tests::utils::layer1::{
+    always_success_script, build_simple_tx, random_out_point,
+};
+use crate::script_tests::utils::rollup::{
+    build_always_success_cell, build_rollup_config_with_extra_fields,
+    calculate_state_validator_type_id, CellContext, CellContextParam,
+};
+use crate::testing_tool::programs::STATE_VALIDATOR_CODE_HASH;
+use ckb_error::assert_error_eq;
+use ckb_script::ScriptError;
+use ckb_types::{
+    core::Cycle,
+    packed::WitnessArgs,
+    prelude::{Pack as CKBPack, Unpack as CKBUnpack},
+};
+use gw_types::prelude::*;
+use gw_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, Status},
+    packed::{GlobalState, RollupConfig, Script},
+};
+
+const INVALID_SINCE_ERROR: i8 = 6;
+const OWNER_CELL_NOT_FOUND_ERROR: i8 = 8;
+const INVALID_POST_GLOBAL_STATE_ERROR: i8 = 25;
+const ROLLUP_PAUSED_ERROR: i8 = 51;
+
+const GUARDIAN_LOCK_HASH: usize = 13;
+const MAX_PAUSE_DURATION: usize = 14;
+const PAUSE_COOLDOWN: usize = 16;
+
+const ABSOLUTE_TIMESTAMP_FLAG: u64 = 0x4000_0000_0000_0000;
+const RELATIVE_BLOCK_NUMBER_FLAG: u64 = 0x8000_0000_0000_0000;
+
+const MAX_PAUSE_DURATION_MS: u64 = 100_000;
+const PAUSE_COOLDOWN_MS: u64 = 50_000;
+/// Timestamp of the rollup input since, the since is in seconds
+const TIMESTAMP: u64 = 1_000_000_000;
+
+/// Verify the pause action, the rollup cell is locked by the always success script
+fn verify_pause(
+    guardian_lock_hash: [u8; 32],
+    prev_paused_at: Option<u64>,
+    post_paused_at: Option<u64>,
+    since: u64,
+) -> Result<Cycle, ckb_error::Error> {
+    let rollup_config = {
+        let mut extra_fields = vec![Bytes::new(); PAUSE_COOLDOWN + 1];
+        extra_fields[GUARDIAN_LOCK_HASH] = Bytes::from(guardian_lock_hash.to_vec());
+        extra_fields[MAX_PAUSE_DURATION] =
+            Bytes::from(MAX_PAUSE_DURATION_MS.to_le_bytes().to_vec());
+        extra_fields[PAUSE_COOLDOWN] = Bytes::from(PAUSE_COOLDOWN_MS.to_le_bytes().to_vec());
+        build_rollup_config_with_extra_fields(&RollupConfig::default(), &extra_fields)
+    };
+    let mut ctx = CellContext::new(&rollup_config, CellContextParam::default());
+    let rollup_type_script = {
+        let type_id = calculate_state_validator_type_id(random_out_point());
+        Script::new_builder()
+            .code_hash(Pack::pack(&*STATE_VALIDATOR_CODE_HASH))
+            .hash_type(ScriptHashType::Data.into())
+            .args(Pack::pack(&Bytes::from(type_id.to_vec())))
+            .build()
+    };
+    let rollup_cell = build_always_success_cell(
+        1000_00000000u64,
+        Some(ckb_types::packed::Script::new_unchecked(
+            rollup_type_script.as_bytes(),
+        )),
+    );
+    let global_state = GlobalState::new_builder()
+        .rollup_config_hash(Pack::pack(&rollup_config.hash()))
+        .status(Status::Running.into())
+        .version(1u8.into())
+        .build();
+    // GlobalState | paused_at, see `gw_utils::pause`
+    let build_rollup_cell_data = |paused_at: Option<u64>| {
+        let mut data = global_state.as_slice().to_vec();
+        if let Some(paused_at) = paused_at {
+            data.extend_from_slice(&paused_at.to_le_bytes());
+        }
+        Bytes::from(data)
+    };
+    // not in the upstream union, the item is empty, see `gw_utils::cells::rollup`
+    let rollup_action = Bytes::from(4u32.to_le_bytes().to_vec());
+    let witness = WitnessArgs::new_builder()
+        .output_type(CKBPack::pack(&Some(rollup_action)))
+        .build();
+    let tx = build_simple_tx(
+        &mut ctx.inner,
+        (rollup_cell.clone(), build_rollup_cell_data(prev_paused_at)),
+        CKBPack::pack(&since),
+        (rollup_cell, build_rollup_cell_data(post_paused_at)),
+    )
+    .as_advanced_builder()
+    .cell_dep(ctx.always_success_dep.clone())
+    .cell_dep(ctx.state_validator_dep.clone())
+    .cell_dep(ctx.rollup_config_dep.clone())
+    .witness(CKBPack::pack(&witness.as_bytes()))
+    .build();
+    ctx.verify_tx(tx)
+}
+
+fn guardian_lock_hash() -> [u8; 32] {
+    always_success_script().calc_script_hash().unpack()
+}
+
+fn since_timestamp() -> u64 {
+    ABSOLUTE_TIMESTAMP_FLAG | (TIMESTAMP / 1000)
+}
+
+fn assert_state_validator_error(err: ckb_error::Error, error_code: i8) {
+    let expected_err = ScriptError::ValidationFailure(
+        format!(
+            "by-data-hash/{}",
+            ckb_types::H256(*STATE_VALIDATOR_CODE_HASH)
+        ),
+        error_code,
+    )
+    .input_type_script(0);
+    assert_error_eq!(err, expected_err);
+}
+
+#[test]
+fn test_pause() {
+    verify_pause(
+        guardian_lock_hash(),
+        None,
+        Some(TIMESTAMP),
+        since_timestamp(),
+    )
+    .expect("pause");
+
+    // paused_at must be the rollup input since
+    let err = verify_pause(
+        guardian_lock_hash(),
+        None,
+        Some(TIMESTAMP - 1),
+        since_timestamp(),
+    )
+    .unwrap_err();
+    assert_state_validator_error(err, INVALID_POST_GLOBAL_STATE_ERROR);
+
+    let err = verify_pause(
+        guardian_lock_hash(),
+        None,
+        Some(TIMESTAMP),
+        RELATIVE_BLOCK_NUMBER_FLAG | 1,
+    )
+    .unwrap_err();
+    assert_state_validator_error(err, INVALID_SINCE_ERROR);
+
+    // only the guardian can pause
+    let err = verify_pause([9u8; 32], None, Some(TIMESTAMP), since_timestamp()).unwrap_err();
+    assert_state_validator_error(err, OWNER_CELL_NOT_FOUND_ERROR);
+}
+
+#[test]
+fn test_pause_while_paused() {
+    let paused_at = TIMESTAMP - 1000;
+    // a pause can't be extended by pausing again
+    let err = verify_pause(
+        guardian_lock_hash(),
+        Some(paused_at),
+        Some(TIMESTAMP),
+        since_timestamp(),
+    )
+    .unwrap_err();
+    assert_state_validator_error(err, INVALID_POST_GLOBAL_STATE_ERROR);
+
+    // unpause, the pause expires at the rollup input since
+    verify_pause(
+        guardian_lock_hash(),
+        Some(paused_at),
+        Some(TIMESTAMP - MAX_PAUSE_DURATION_MS),
+        since_timestamp(),
+    )
+    .expect("unpause");
+
+    // the paused_at is cleared by submitting blocks
+    let err = verify_pause(
+        guardian_lock_hash(),
+        Some(paused_at),
+        None,
+        since_timestamp(),
+    )
+    .unwrap_err();
+    assert_state_validator_error(err, INVALID_POST_GLOBAL_STATE_ERROR);
+}
+
+#[test]
+fn test_pause_cooldown() {
+    // expired but in the cooldown
+    let paused_at = TIMESTAMP - MAX_PAUSE_DURATION_MS;
+    let err = verify_pause(
+        guardian_lock_hash(),
+        Some(paused_at),
+        Some(TIMESTAMP),
+        since_timestamp(),
+    )
+    .unwrap_err();
+    assert_state_validator_error(err, ROLLUP_PAUSED_ERROR);
+
+    let paused_at = TIMESTAMP - MAX_PAUSE_DURATION_MS - PAUSE_COOLDOWN_MS + 1;
+    let err = verify_pause(
+        guardian_lock_hash(),
+        Some(paused_at),
+        Some(TIMESTAMP),
+        since_timestamp(),
+    )
+    .unwrap_err();
+    assert_state_validator_error(err, ROLLUP_PAUSED_ERROR);
+
+    // after the cooldown
+    let paused_at = TIMESTAMP - MAX_PAUSE_DURATION_MS - PAUSE_COOLDOWN_MS;
+    verify_pause(
+        guardian_lock_hash(),
+        Some(paused_at),
+        Some(TIMESTAMP),
+        since_timestamp(),
+    )
+    .expect("pause after the cooldown");
+}
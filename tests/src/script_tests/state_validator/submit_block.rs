@@ -3,12 +3,18 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use crate::script_tests::utils::layer1::{
     build_simple_tx_with_out_point_and_since, random_out_point, since_timestamp,
 };
+use crate::script_tests::utils::layer2::{
+    build_message_key, extend_table, get_or_create_sudt_id, with_valid_until, BlockParam,
+    L2Deposit, L2Message, ProducedBlock, TestChain,
+};
 use crate::script_tests::utils::rollup::{
-    build_always_success_cell, build_rollup_locked_cell, build_type_id_script,
-    calculate_state_validator_type_id, CellContext, CellContextParam,
+    build_always_success_cell, build_rollup_config_with_extra_fields, build_rollup_locked_cell,
+    build_type_id_script, calculate_state_validator_type_id, CellContext, CellContextParam,
 };
 use crate::testing_tool::chain::construct_block_from_timestamp;
-use crate::testing_tool::programs::{ALWAYS_SUCCESS_CODE_HASH, STATE_VALIDATOR_CODE_HASH};
+use crate::testing_tool::programs::{
+    ALWAYS_SUCCESS_CODE_HASH, ALWAYS_SUCCESS_PROGRAM, STATE_VALIDATOR_CODE_HASH,
+};
 use crate::{script_tests::utils::layer1::build_simple_tx, testing_tool::chain::construct_block};
 use crate::{
     script_tests::utils::layer1::build_simple_tx_with_out_point, testing_tool::chain::setup_chain,
@@ -16,21 +22,668 @@ use crate::{
 use ckb_error::assert_error_eq;
 use ckb_script::ScriptError;
 use ckb_types::{
-    packed::CellInput,
+    core::TransactionView,
+    packed::{CellDep, CellInput},
     prelude::{Pack as CKBPack, Unpack},
 };
+use gw_common::{
+    blake2b::new_blake2b,
+    builtins::CKB_SUDT_ACCOUNT_ID,
+    state::{to_short_address, State},
+    H256,
+};
 use gw_types::prelude::{Pack as GWPack, Unpack as GWUnpack, *};
 use gw_types::{
     bytes::Bytes,
-    core::ScriptHashType,
+    core::{ChallengeTargetType, ScriptHashType, Status},
     packed::{
-        CustodianLockArgs, DepositLockArgs, RollupAction, RollupActionUnion, RollupConfig,
-        RollupSubmitBlock, Script, StakeLockArgs, WithdrawalLockArgs,
+        ChallengeLockArgs, ChallengeTarget, ChallengeWitness, CustodianLockArgs, DepositLockArgs,
+        DepositRequest, Fee, GlobalState, KVPair, KVPairVec, L2Block, L2Transaction,
+        RawL2Transaction, RawWithdrawalRequest, RollupAction, RollupActionUnion, RollupConfig,
+        RollupEnterChallenge, RollupSubmitBlock, Script, StakeLockArgs, WithdrawalLockArgs,
+        WithdrawalRequest,
     },
 };
 
+const ENCODING_ERROR: i8 = 4;
+const MERKLE_PROOF_ERROR: i8 = 13;
+const INVALID_STATE_CHECKPOINT_ERROR: i8 = 21;
 const INVALID_BLOCK_ERROR: i8 = 22;
 const INVALID_POST_GLOBAL_STATE: i8 = 25;
+const INVALID_STAKE_CELL_ERROR: i8 = 27;
+const INVALID_DEPOSIT_CELL_ERROR: i8 = 28;
+const INVALID_WITHDRAWAL_CELL_ERROR: i8 = 29;
+const INVALID_SUDT_CELL_ERROR: i8 = 33;
+const INVALID_CHALLENGE_TARGET_ERROR: i8 = 34;
+const INVALID_WITHDRAWAL_REQUEST_ERROR: i8 = 35;
+const EXCEEDED_BLOCK_LIMIT_ERROR: i8 = 47;
+const EXCEEDED_DEPOSIT_CAP_ERROR: i8 = 48;
+const INVALID_NFT_CELL_ERROR: i8 = 49;
+const INVALID_MESSAGE_CELL_ERROR: i8 = 50;
+const ROLLUP_PAUSED_ERROR: i8 = 51;
+
+const L1_SUDT_TYPE_ID: &[u8] = b"l1_sudt_type_id";
+const MESSAGE_LOCK_TYPE_ID: &[u8] = b"message_lock_type_id";
+const CHALLENGE_LOCK_TYPE_ID: &[u8] = b"challenge_lock_type_id";
+// extra fields of the rollup config, see `gw_utils::rollup_config`
+const MIN_DEPOSIT_CAPACITY: usize = 4;
+const SUDT_DEPOSIT_CAPS: usize = 5;
+const L1_SUDT_ALLOWLIST: usize = 6;
+const L1_SUDT_BLOCKLIST: usize = 7;
+const L1_XUDT_SCRIPT_TYPE_HASH: usize = 8;
+const L1_NFT_SCRIPT_CODE_HASHES: usize = 9;
+const MESSAGE_SCRIPT_TYPE_HASH: usize = 10;
+const MIN_DEPOSIT_CANCEL_TIMEOUT: usize = 11;
+const WITHDRAWAL_FINALITY_TIERS: usize = 12;
+const GUARDIAN_LOCK_HASH: usize = 13;
+const MAX_PAUSE_DURATION: usize = 14;
+const L2_NFT_VALIDATOR_SCRIPT_TYPE_HASH: usize = 15;
+const PAUSE_COOLDOWN: usize = 16;
+
+/// Rollup and lock cells of a submit block test, the blocks are built by `TestChain`
+struct SubmitBlockTest {
+    ctx: CellContext,
+    chain: TestChain,
+    rollup_cell: ckb_types::packed::CellOutput,
+    stake_script_type_hash: [u8; 32],
+    deposit_script_type_hash: [u8; 32],
+    custodian_script_type_hash: [u8; 32],
+    withdrawal_script_type_hash: [u8; 32],
+    /// token scripts deployed by the test
+    extra_deps: Vec<CellDep>,
+    /// blocks reverted by the next submitted block, see `revert_blocks`
+    reverted_block_hashes: Vec<H256>,
+    /// paused_at of the input and the output rollup cells, see `gw_utils::pause`
+    prev_paused_at: Option<u64>,
+    post_paused_at: Option<u64>,
+    /// submit the blocks by the v0 global state, which skips the block timestamp check
+    v0_global_state: bool,
+}
+
+/// A deposit cell and its request in the block
+struct TestDeposit {
+    request: DepositRequest,
+    lock_args: DepositLockArgs,
+    /// type script and data of the token
+    token: Option<(ckb_types::packed::Script, Bytes)>,
+    nft: bool,
+    /// fee paid to the block producer, see `with_deposit_fee`
+    ckb_fee: u64,
+    sudt_fee: u128,
+}
+
+impl SubmitBlockTest {
+    /// `build_config` can change the config or append extra fields to it
+    fn new<F: FnOnce(RollupConfig) -> RollupConfig>(build_config: F) -> Self {
+        let type_id = calculate_state_validator_type_id(random_out_point());
+        let rollup_type_script = Script::new_builder()
+            .code_hash(Pack::pack(&*STATE_VALIDATOR_CODE_HASH))
+            .hash_type(ScriptHashType::Data.into())
+            .args(Pack::pack(&Bytes::from(type_id.to_vec())))
+            .build();
+        // rollup locks & config
+        let stake_lock_type = build_type_id_script(b"stake_lock_type_id");
+        let stake_script_type_hash: [u8; 32] = stake_lock_type.calc_script_hash().unpack();
+        let deposit_lock_type = build_type_id_script(b"deposit_lock_type_id");
+        let deposit_script_type_hash: [u8; 32] = deposit_lock_type.calc_script_hash().unpack();
+        let custodian_lock_type = build_type_id_script(b"custodian_lock_type_id");
+        let custodian_script_type_hash: [u8; 32] = custodian_lock_type.calc_script_hash().unpack();
+        let withdrawal_lock_type = build_type_id_script(b"withdrawal_lock_type_id");
+        let withdrawal_script_type_hash: [u8; 32] =
+            withdrawal_lock_type.calc_script_hash().unpack();
+        let l2_sudt_type = build_type_id_script(b"l2_sudt_type_id");
+        let l2_sudt_type_hash: [u8; 32] = l2_sudt_type.calc_script_hash().unpack();
+        let l1_sudt_type = build_type_id_script(L1_SUDT_TYPE_ID);
+        let l1_sudt_type_hash: [u8; 32] = l1_sudt_type.calc_script_hash().unpack();
+        let rollup_config = build_config(
+            RollupConfig::new_builder()
+                .stake_script_type_hash(Pack::pack(&stake_script_type_hash))
+                .deposit_script_type_hash(Pack::pack(&deposit_script_type_hash))
+                .custodian_script_type_hash(Pack::pack(&custodian_script_type_hash))
+                .withdrawal_script_type_hash(Pack::pack(&withdrawal_script_type_hash))
+                .l2_sudt_validator_script_type_hash(Pack::pack(&l2_sudt_type_hash))
+                .l1_sudt_script_type_hash(Pack::pack(&l1_sudt_type_hash))
+                .allowed_eoa_type_hashes(GWPack::pack(&vec![*ALWAYS_SUCCESS_CODE_HASH]))
+                .finality_blocks(Pack::pack(&10u64))
+                .build(),
+        );
+        let param = CellContextParam {
+            stake_lock_type,
+            deposit_lock_type,
+            custodian_lock_type,
+            withdrawal_lock_type,
+            l2_sudt_type,
+            ..Default::default()
+        };
+        let ctx = CellContext::new(&rollup_config, param);
+        let chain = TestChain::new(rollup_type_script.hash().into(), rollup_config);
+        let rollup_cell = build_always_success_cell(
+            1000_00000000u64,
+            Some(ckb_types::packed::Script::new_unchecked(
+                rollup_type_script.as_bytes(),
+            )),
+        );
+        let mut test = SubmitBlockTest {
+            ctx,
+            chain,
+            rollup_cell,
+            stake_script_type_hash,
+            deposit_script_type_hash,
+            custodian_script_type_hash,
+            withdrawal_script_type_hash,
+            extra_deps: Vec::new(),
+            reverted_block_hashes: Vec::new(),
+            prev_paused_at: None,
+            post_paused_at: None,
+            v0_global_state: false,
+        };
+        test.deploy_type_script(l1_sudt_type);
+        test
+    }
+
+    /// Deploy the always success program as the code of a type script,
+    /// scripts can refer it by the type hash
+    fn deploy_type_script(&mut self, type_script: ckb_types::packed::Script) {
+        let cell = ckb_types::packed::CellOutput::new_builder()
+            .capacity(CKBPack::pack(&(ALWAYS_SUCCESS_PROGRAM.len() as u64)))
+            .type_(CKBPack::pack(&Some(type_script)))
+            .build();
+        let out_point = self.ctx.insert_cell(cell, ALWAYS_SUCCESS_PROGRAM.clone());
+        self.extra_deps
+            .push(CellDep::new_builder().out_point(out_point).build());
+    }
+
+    /// Revert the blocks before the next block,
+    /// the next submitted block carries the reverted block hashes
+    fn revert_blocks(&mut self, block_hashes: Vec<H256>) {
+        self.chain.revert_block_hashes(&block_hashes);
+        self.reverted_block_hashes = block_hashes;
+    }
+
+    fn rollup_type_hash(&self) -> [u8; 32] {
+        self.chain.rollup_type_hash.into()
+    }
+
+    /// Timestamp of the next block
+    fn next_timestamp(&self) -> u64 {
+        let tip_block_timestamp: u64 = self.chain.global_state().tip_block_timestamp().unpack();
+        tip_block_timestamp + 1000
+    }
+
+    /// Create a layer2 account with CKB, return the account script hash
+    fn create_account(&mut self, args: Bytes, capacity: u64) -> H256 {
+        let script_hash: H256 = build_account_script(args).hash().into();
+        let state = &mut self.chain.state;
+        state.create_account(script_hash).unwrap();
+        state
+            .mint_sudt(
+                CKB_SUDT_ACCOUNT_ID,
+                to_short_address(&script_hash),
+                capacity.into(),
+            )
+            .unwrap();
+        script_hash
+    }
+
+    fn insert_input(&mut self, cell: ckb_types::packed::CellOutput, data: Bytes) -> CellInput {
+        let out_point = self.ctx.insert_cell(cell, data);
+        CellInput::new_builder().previous_output(out_point).build()
+    }
+
+    fn build_finalized_custodian_cell(&self, capacity: u64) -> ckb_types::packed::CellOutput {
+        let args = CustodianLockArgs::new_builder()
+            .deposit_block_number(Pack::pack(&0u64))
+            .build();
+        build_rollup_locked_cell(
+            &self.rollup_type_hash(),
+            &self.custodian_script_type_hash,
+            capacity,
+            args.as_bytes(),
+        )
+    }
+
+    /// Deposit of CKB and an optional L1 sUDT to the account
+    fn build_deposit(
+        &self,
+        account_script: Script,
+        capacity: u64,
+        sudt: Option<(ckb_types::packed::Script, u128)>,
+    ) -> TestDeposit {
+        match sudt {
+            Some((sudt_script, amount)) => {
+                let data = Bytes::from(amount.to_le_bytes().to_vec());
+                self.build_token_deposit(
+                    account_script,
+                    capacity,
+                    Some((sudt_script, data)),
+                    amount,
+                )
+            }
+            None => self.build_token_deposit(account_script, capacity, None, 0),
+        }
+    }
+
+    /// Deposit of a NFT cell, valued as 1 token of the type hash
+    fn build_nft_deposit(
+        &self,
+        account_script: Script,
+        capacity: u64,
+        nft_script: ckb_types::packed::Script,
+        data: Bytes,
+    ) -> TestDeposit {
+        let deposit =
+            self.build_token_deposit(account_script, capacity, Some((nft_script, data)), 1);
+        TestDeposit {
+            nft: true,
+            ..deposit
+        }
+    }
+
+    fn build_token_deposit(
+        &self,
+        account_script: Script,
+        capacity: u64,
+        token: Option<(ckb_types::packed::Script, Bytes)>,
+        amount: u128,
+    ) -> TestDeposit {
+        let sudt_script_hash: [u8; 32] = match &token {
+            Some((type_script, _data)) => type_script.calc_script_hash().unpack(),
+            None => [0u8; 32],
+        };
+        let request = DepositRequest::new_builder()
+            .capacity(Pack::pack(&capacity))
+            .amount(Pack::pack(&amount))
+            .sudt_script_hash(Pack::pack(&sudt_script_hash))
+            .script(account_script.clone())
+            .build();
+        // relative 100 blocks
+        let cancel_timeout = 0x8000_0000_0000_0000u64 | 100;
+        let lock_args = DepositLockArgs::new_builder()
+            .owner_lock_hash(Pack::pack(&[8u8; 32]))
+            .layer2_lock(account_script)
+            .cancel_timeout(Pack::pack(&cancel_timeout))
+            .build();
+        TestDeposit {
+            request,
+            lock_args,
+            token,
+            nft: false,
+            ckb_fee: 0,
+            sudt_fee: 0,
+        }
+    }
+
+    /// The deposit cell in the inputs
+    fn build_deposit_cell(&self, deposit: &TestDeposit) -> (ckb_types::packed::CellOutput, Bytes) {
+        let cell = build_rollup_locked_cell(
+            &self.rollup_type_hash(),
+            &self.deposit_script_type_hash,
+            GWUnpack::unpack(&deposit.request.capacity()),
+            deposit.lock_args.as_bytes(),
+        );
+        with_deposit_value(cell, deposit)
+    }
+
+    /// The unfinalized custodian cell of a deposit in the produced block
+    fn build_deposit_custodian_cell(
+        &self,
+        produced: &ProducedBlock,
+        deposit: &TestDeposit,
+    ) -> (ckb_types::packed::CellOutput, Bytes) {
+        let args = CustodianLockArgs::new_builder()
+            .deposit_block_hash(Pack::pack(&produced.block.raw().hash()))
+            .deposit_block_number(produced.block.raw().number())
+            .deposit_lock_args(deposit.lock_args.clone())
+            .build();
+        let cell = build_rollup_locked_cell(
+            &self.rollup_type_hash(),
+            &self.custodian_script_type_hash,
+            GWUnpack::unpack(&deposit.request.capacity()),
+            args.as_bytes(),
+        );
+        with_deposit_value(cell, deposit)
+    }
+
+    /// Produce and submit a block of the deposits,
+    /// the deposit cells are moved to unfinalized custodian cells
+    fn submit_deposits(&mut self, deposits: &[TestDeposit]) -> Result<u64, ckb_error::Error> {
+        self.submit_deposits_with(deposits, BlockParam::default())
+            .map(|(_produced, cycles)| cycles)
+    }
+
+    /// Like `submit_deposits`, `param` sets the transactions and the extra fields of the block
+    fn submit_deposits_with(
+        &mut self,
+        deposits: &[TestDeposit],
+        param: BlockParam,
+    ) -> Result<(ProducedBlock, u64), ckb_error::Error> {
+        let produced = self.chain.produce_block(BlockParam {
+            timestamp: self.next_timestamp(),
+            deposits: deposits
+                .iter()
+                .map(|deposit| L2Deposit {
+                    request: deposit.request.clone(),
+                    ckb_fee: deposit.ckb_fee,
+                    sudt_fee: deposit.sudt_fee,
+                    nft: deposit.nft,
+                })
+                .collect(),
+            ..param
+        });
+        let deposit_cells = deposits
+            .iter()
+            .map(|deposit| self.build_deposit_cell(deposit))
+            .collect();
+        let custodian_cells = deposits
+            .iter()
+            .map(|deposit| self.build_deposit_custodian_cell(&produced, deposit))
+            .collect();
+        let tx = self.build_submit_tx_with_cells(&produced, deposit_cells, custodian_cells);
+        let cycles = self.ctx.verify_tx(tx)?;
+        Ok((produced, cycles))
+    }
+
+    /// Produce and submit a block of the withdrawals, paid by a finalized custodian cell,
+    /// every withdrawal cell has the extra finality blocks paired with the request
+    fn submit_withdrawals(
+        &mut self,
+        withdrawals: &[(WithdrawalRequest, u64)],
+    ) -> Result<u64, ckb_error::Error> {
+        let produced = self.chain.produce_block(BlockParam {
+            timestamp: self.next_timestamp(),
+            withdrawals: withdrawals
+                .iter()
+                .map(|(request, _)| request.clone())
+                .collect(),
+            ..Default::default()
+        });
+        let total_capacity: u64 = withdrawals
+            .iter()
+            .map(|(request, _)| GWUnpack::<u64>::unpack(&request.raw().capacity()))
+            .sum();
+        let custodian_cell = self.build_finalized_custodian_cell(total_capacity);
+        let withdrawal_cells = withdrawals
+            .iter()
+            .map(|(request, extra_finality_blocks)| {
+                let cell = self.build_withdrawal_cell(&produced, request, *extra_finality_blocks);
+                (cell, Bytes::new())
+            })
+            .collect();
+        let tx = self.build_submit_tx_with_cells(
+            &produced,
+            vec![(custodian_cell, Bytes::new())],
+            withdrawal_cells,
+        );
+        self.ctx.verify_tx(tx)
+    }
+
+    /// Withdrawal cell of a request in the produced block,
+    /// `extra_finality_blocks` is appended to the lock args if it isn't 0
+    fn build_withdrawal_cell(
+        &self,
+        produced: &ProducedBlock,
+        request: &WithdrawalRequest,
+        extra_finality_blocks: u64,
+    ) -> ckb_types::packed::CellOutput {
+        let raw = request.raw();
+        let args = WithdrawalLockArgs::new_builder()
+            .account_script_hash(raw.account_script_hash())
+            .withdrawal_block_hash(Pack::pack(&produced.block.raw().hash()))
+            .withdrawal_block_number(produced.block.raw().number())
+            .sudt_script_hash(raw.sudt_script_hash())
+            .owner_lock_hash(raw.owner_lock_hash())
+            .build();
+        let mut args = args.as_bytes().to_vec();
+        if extra_finality_blocks > 0 {
+            args.extend_from_slice(&extra_finality_blocks.to_le_bytes());
+        }
+        build_rollup_locked_cell(
+            &self.rollup_type_hash(),
+            &self.withdrawal_script_type_hash,
+            GWUnpack::unpack(&raw.capacity()),
+            args.into(),
+        )
+    }
+
+    /// Submit the produced block, the first input and output are the rollup cell,
+    /// the second are the block producer's stake cell
+    fn build_submit_tx(&mut self, produced: &ProducedBlock) -> TransactionView {
+        let rollup_type_hash = self.rollup_type_hash();
+        let input_stake_cell = {
+            let cell = build_rollup_locked_cell(
+                &rollup_type_hash,
+                &self.stake_script_type_hash,
+                10000_00000000u64,
+                StakeLockArgs::default().as_bytes(),
+            );
+            self.insert_input(cell, Bytes::new())
+        };
+        let output_stake_cell = build_rollup_locked_cell(
+            &rollup_type_hash,
+            &self.stake_script_type_hash,
+            10000_00000000u64,
+            StakeLockArgs::new_builder()
+                .stake_block_number(produced.block.raw().number())
+                .build()
+                .as_bytes(),
+        );
+        let reverted_block_hashes = std::mem::take(&mut self.reverted_block_hashes);
+        let reverted_block_proof = self.chain.reverted_block_proof(&reverted_block_hashes);
+        let witness = {
+            let rollup_action = RollupAction::new_builder()
+                .set(RollupActionUnion::RollupSubmitBlock(
+                    RollupSubmitBlock::new_builder()
+                        .block(produced.block.clone())
+                        .reverted_block_hashes(GWPack::pack(&reverted_block_hashes))
+                        .reverted_block_proof(GWPack::pack(&reverted_block_proof))
+                        .build(),
+                ))
+                .build();
+            ckb_types::packed::WitnessArgs::new_builder()
+                .output_type(CKBPack::pack(&Some(rollup_action.as_bytes())))
+                .build()
+        };
+        let timestamp = produced.block.raw().timestamp().unpack();
+        let (prev_global_state, post_global_state) = if self.v0_global_state {
+            (
+                as_v0_global_state(&produced.prev_global_state),
+                as_v0_global_state(&produced.global_state),
+            )
+        } else {
+            (
+                produced.prev_global_state.clone(),
+                produced.global_state.clone(),
+            )
+        };
+        build_simple_tx(
+            &mut self.ctx.inner,
+            (
+                self.rollup_cell.clone(),
+                build_rollup_cell_data(&prev_global_state, self.prev_paused_at),
+            ),
+            since_timestamp(timestamp),
+            (
+                self.rollup_cell.clone(),
+                build_rollup_cell_data(&post_global_state, self.post_paused_at),
+            ),
+        )
+        .as_advanced_builder()
+        .input(input_stake_cell)
+        .output(output_stake_cell)
+        .output_data(CKBPack::pack(&Bytes::new()))
+        .cell_dep(self.ctx.stake_lock_dep.clone())
+        .cell_dep(self.ctx.deposit_lock_dep.clone())
+        .cell_dep(self.ctx.custodian_lock_dep.clone())
+        .cell_dep(self.ctx.withdrawal_lock_dep.clone())
+        .cell_dep(self.ctx.always_success_dep.clone())
+        .cell_dep(self.ctx.state_validator_dep.clone())
+        .cell_dep(self.ctx.rollup_config_dep.clone())
+        .cell_deps(self.extra_deps.clone())
+        .witness(CKBPack::pack(&witness.as_bytes()))
+        .build()
+    }
+
+    /// Submit the produced block with extra input and output cells
+    fn build_submit_tx_with_cells(
+        &mut self,
+        produced: &ProducedBlock,
+        inputs: Vec<(ckb_types::packed::CellOutput, Bytes)>,
+        outputs: Vec<(ckb_types::packed::CellOutput, Bytes)>,
+    ) -> TransactionView {
+        let inputs: Vec<CellInput> = inputs
+            .into_iter()
+            .map(|(cell, data)| self.insert_input(cell, data))
+            .collect();
+        let (outputs, outputs_data): (Vec<_>, Vec<_>) = outputs
+            .into_iter()
+            .map(|(cell, data)| (cell, CKBPack::pack(&data)))
+            .unzip();
+        self.build_submit_tx(produced)
+            .as_advanced_builder()
+            .inputs(inputs)
+            .outputs(outputs)
+            .outputs_data(outputs_data)
+            .build()
+    }
+}
+
+/// The v0 global state has no tip block timestamp
+fn as_v0_global_state(global_state: &GlobalState) -> GlobalState {
+    global_state
+        .clone()
+        .as_builder()
+        .version(0u8.into())
+        .tip_block_timestamp(GWPack::pack(&0u64))
+        .build()
+}
+
+/// Rollup cell data, the paused_at is appended to the global state, see `gw_utils::pause`
+fn build_rollup_cell_data(global_state: &GlobalState, paused_at: Option<u64>) -> Bytes {
+    let mut data = global_state.as_slice().to_vec();
+    if let Some(paused_at) = paused_at {
+        data.extend_from_slice(&paused_at.to_le_bytes());
+    }
+    Bytes::from(data)
+}
+
+/// Layer2 account script, the always success program is an allowed EOA type hash
+fn build_account_script(args: Bytes) -> Script {
+    Script::new_builder()
+        .code_hash(Pack::pack(&*ALWAYS_SUCCESS_CODE_HASH))
+        .hash_type(ScriptHashType::Type.into())
+        .args(Pack::pack(&args))
+        .build()
+}
+
+/// L1 sUDT type script, the sUDT script hash is the type hash
+fn build_l1_sudt_script(args: &[u8]) -> ckb_types::packed::Script {
+    let code_hash: [u8; 32] = build_type_id_script(L1_SUDT_TYPE_ID)
+        .calc_script_hash()
+        .unpack();
+    ckb_types::packed::Script::new_builder()
+        .code_hash(CKBPack::pack(&code_hash))
+        .hash_type(ScriptHashType::Type.into())
+        .args(CKBPack::pack(&Bytes::from(args.to_vec())))
+        .build()
+}
+
+/// Put the sUDT into the cell
+fn with_sudt(
+    cell: ckb_types::packed::CellOutput,
+    sudt_script: &ckb_types::packed::Script,
+    amount: u128,
+) -> (ckb_types::packed::CellOutput, Bytes) {
+    let cell = cell
+        .as_builder()
+        .type_(CKBPack::pack(&Some(sudt_script.clone())))
+        .build();
+    (cell, Bytes::from(amount.to_le_bytes().to_vec()))
+}
+
+fn with_deposit_value(
+    cell: ckb_types::packed::CellOutput,
+    deposit: &TestDeposit,
+) -> (ckb_types::packed::CellOutput, Bytes) {
+    with_token(cell, deposit.token.as_ref())
+}
+
+/// Set the fee of the deposit, extra field 3 of the deposit lock args,
+/// see `gw_utils::deposit`
+fn with_deposit_fee(deposit: TestDeposit, ckb_fee: u64, sudt_fee: u128) -> TestDeposit {
+    let fee = {
+        let mut fee = ckb_fee.to_le_bytes().to_vec();
+        fee.extend_from_slice(&sudt_fee.to_le_bytes());
+        Bytes::from(fee)
+    };
+    let lock_args = DepositLockArgs::new_unchecked(extend_table(
+        deposit.lock_args.as_slice(),
+        &[Bytes::new(), Bytes::new(), Bytes::new(), fee],
+    ));
+    TestDeposit {
+        lock_args,
+        ckb_fee,
+        sudt_fee,
+        ..deposit
+    }
+}
+
+/// Put the token type script and data into the cell
+fn with_token(
+    cell: ckb_types::packed::CellOutput,
+    token: Option<&(ckb_types::packed::Script, Bytes)>,
+) -> (ckb_types::packed::CellOutput, Bytes) {
+    match token {
+        Some((type_script, data)) => {
+            let cell = cell
+                .as_builder()
+                .type_(CKBPack::pack(&Some(type_script.clone())))
+                .build();
+            (cell, data.clone())
+        }
+        None => (cell, Bytes::new()),
+    }
+}
+
+/// Set extra fields of the rollup config by the index, the skipped fields are empty
+fn with_extra_fields(config: RollupConfig, fields: &[(usize, Bytes)]) -> RollupConfig {
+    let count = fields.iter().map(|(index, _)| index + 1).max().unwrap_or(0);
+    let mut extra_fields = vec![Bytes::new(); count];
+    for (index, field) in fields {
+        extra_fields[*index] = field.clone();
+    }
+    build_rollup_config_with_extra_fields(&config, &extra_fields)
+}
+
+fn build_withdrawal_request(
+    account_script_hash: H256,
+    nonce: u32,
+    capacity: u64,
+) -> WithdrawalRequest {
+    let raw = RawWithdrawalRequest::new_builder()
+        .nonce(Pack::pack(&nonce))
+        .capacity(Pack::pack(&capacity))
+        .account_script_hash(Pack::pack(&account_script_hash))
+        .owner_lock_hash(Pack::pack(&[7u8; 32]))
+        .fee(
+            Fee::new_builder()
+                .sudt_id(Pack::pack(&CKB_SUDT_ACCOUNT_ID))
+                .build(),
+        )
+        .build();
+    WithdrawalRequest::new_builder().raw(raw).build()
+}
+
+fn assert_state_validator_error(err: ckb_error::Error, error_code: i8) {
+    let expected_err = ScriptError::ValidationFailure(
+        format!(
+            "by-data-hash/{}",
+            ckb_types::H256(*STATE_VALIDATOR_CODE_HASH)
+        ),
+        error_code,
+    )
+    .input_type_script(0);
+    assert_error_eq!(err, expected_err);
+}
 
 #[test]
 fn test_submit_block() {
@@ -1070,3 +1723,1583 @@ fn test_check_reverted_cells_in_submit_block() {
     .build();
     ctx.verify_tx(tx).expect("return success");
 }
+
+#[test]
+fn test_check_many_withdrawal_cells_in_submit_block() {
+    const WITHDRAWALS_COUNT: u64 = 512;
+    // the submit block transaction must fit in a CKB block
+    const MAX_CYCLES: u64 = 3_500_000_000;
+
+    let mut test = SubmitBlockTest::new(|config| config);
+    // withdraw distinct capacities from distinct accounts,
+    // the outputs are in the reverse order of the requests
+    let withdrawals: Vec<WithdrawalRequest> = (0..WITHDRAWALS_COUNT)
+        .map(|i| {
+            let capacity = 500_00000000u64 + i;
+            let account_script_hash =
+                test.create_account(i.to_le_bytes().to_vec().into(), capacity);
+            build_withdrawal_request(account_script_hash, 0, capacity)
+        })
+        .collect();
+    let total_capacity: u64 = withdrawals
+        .iter()
+        .map(|w| GWUnpack::<u64>::unpack(&w.raw().capacity()))
+        .sum();
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        withdrawals: withdrawals.clone(),
+        ..Default::default()
+    });
+    let input_custodian_cell = {
+        let cell = test.build_finalized_custodian_cell(total_capacity);
+        test.insert_input(cell, Bytes::new())
+    };
+    let output_withdrawal_cells: Vec<_> = withdrawals
+        .iter()
+        .rev()
+        .map(|w| test.build_withdrawal_cell(&produced, w, 0))
+        .collect();
+    let tx = test
+        .build_submit_tx(&produced)
+        .as_advanced_builder()
+        .input(input_custodian_cell)
+        .outputs(output_withdrawal_cells.clone())
+        .outputs_data(
+            output_withdrawal_cells
+                .iter()
+                .map(|_| CKBPack::pack(&Bytes::new())),
+        )
+        .build();
+    let cycles = test.ctx.verify_tx(tx).expect("return success");
+    assert!(
+        cycles < MAX_CYCLES,
+        "submit block with {} withdrawal cells, cycles: {}",
+        WITHDRAWALS_COUNT,
+        cycles
+    );
+}
+
+#[test]
+fn test_submit_block_withdrawal_valid_until() {
+    const ABSOLUTE_EPOCH_FLAG: u64 = 0x2000_0000_0000_0000;
+    const ABSOLUTE_TIMESTAMP_FLAG: u64 = 0x4000_0000_0000_0000;
+    const RELATIVE_BLOCK_NUMBER_FLAG: u64 = 0x8000_0000_0000_0000;
+    let capacity = 500_00000000u64;
+    // valid until of the number and the timestamp of the block
+    let cases: Vec<(fn(u64, u64) -> u64, Option<i8>)> = vec![
+        (|number, _| number, None),
+        (|number, _| number + 1, None),
+        (|_, timestamp| ABSOLUTE_TIMESTAMP_FLAG | timestamp, None),
+        // expired
+        (
+            |number, _| number - 1,
+            Some(INVALID_WITHDRAWAL_REQUEST_ERROR),
+        ),
+        (
+            |_, timestamp| ABSOLUTE_TIMESTAMP_FLAG | (timestamp - 1),
+            Some(INVALID_WITHDRAWAL_REQUEST_ERROR),
+        ),
+        // must be absolute
+        (
+            |number, _| RELATIVE_BLOCK_NUMBER_FLAG | (number + 1),
+            Some(INVALID_WITHDRAWAL_REQUEST_ERROR),
+        ),
+        // epoch isn't supported
+        (
+            |number, _| ABSOLUTE_EPOCH_FLAG | (number + 1),
+            Some(INVALID_WITHDRAWAL_REQUEST_ERROR),
+        ),
+    ];
+    for (valid_until, expected_err) in cases {
+        let mut test = SubmitBlockTest::new(|config| config);
+        let number: u64 = GWUnpack::unpack(&test.chain.global_state().block().count());
+        let valid_until = valid_until(number, test.next_timestamp());
+        let account_script_hash = test.create_account(Bytes::from_static(b"alice"), capacity);
+        let request = with_valid_until(
+            build_withdrawal_request(account_script_hash, 0, capacity),
+            valid_until,
+        );
+        let result = test.submit_withdrawals(&[(request, 0)]);
+        match expected_err {
+            None => {
+                result.expect("return success");
+            }
+            Some(code) => assert_state_validator_error(result.unwrap_err(), code),
+        }
+    }
+}
+
+#[test]
+fn test_submit_block_withdrawal_finality_tiers() {
+    let capacity = 1000_00000000u64;
+    let extra_finality_blocks = 100u64;
+    // sudt_script_hash | min_capacity | min_amount | extra_finality_blocks,
+    // withdrawing 1000 CKB waits 100 extra blocks
+    let tier = {
+        let mut tier = [0u8; 32].to_vec();
+        tier.extend_from_slice(&capacity.to_le_bytes());
+        tier.extend_from_slice(&u128::MAX.to_le_bytes());
+        tier.extend_from_slice(&extra_finality_blocks.to_le_bytes());
+        Bytes::from(tier)
+    };
+    let build_test = || {
+        SubmitBlockTest::new(|config| {
+            with_extra_fields(config, &[(WITHDRAWAL_FINALITY_TIERS, tier.clone())])
+        })
+    };
+
+    // a single withdrawal reaches the tier
+    let mut test = build_test();
+    let alice = test.create_account(Bytes::from_static(b"alice"), capacity);
+    let request = build_withdrawal_request(alice, 0, capacity);
+    test.submit_withdrawals(&[(request, extra_finality_blocks)])
+        .expect("return success");
+
+    let mut test = build_test();
+    let alice = test.create_account(Bytes::from_static(b"alice"), capacity);
+    let request = build_withdrawal_request(alice, 0, capacity);
+    let err = test.submit_withdrawals(&[(request, 0)]).unwrap_err();
+    assert_state_validator_error(err, INVALID_WITHDRAWAL_CELL_ERROR);
+
+    // a withdrawal split in a block still reaches the tier by the total of the account
+    let mut test = build_test();
+    let alice = test.create_account(Bytes::from_static(b"alice"), capacity);
+    let requests = vec![
+        build_withdrawal_request(alice, 0, capacity / 2),
+        build_withdrawal_request(alice, 1, capacity / 2),
+    ];
+    let withdrawals: Vec<_> = requests
+        .into_iter()
+        .map(|request| (request, extra_finality_blocks))
+        .collect();
+    test.submit_withdrawals(&withdrawals)
+        .expect("return success");
+
+    let mut test = build_test();
+    let alice = test.create_account(Bytes::from_static(b"alice"), capacity);
+    let requests = vec![
+        build_withdrawal_request(alice, 0, capacity / 2),
+        build_withdrawal_request(alice, 1, capacity / 2),
+    ];
+    let withdrawals: Vec<_> = requests.into_iter().map(|request| (request, 0)).collect();
+    let err = test.submit_withdrawals(&withdrawals).unwrap_err();
+    assert_state_validator_error(err, INVALID_WITHDRAWAL_CELL_ERROR);
+
+    // withdrawals of different accounts don't add up
+    let mut test = build_test();
+    let alice = test.create_account(Bytes::from_static(b"alice"), capacity);
+    let bob = test.create_account(Bytes::from_static(b"bob"), capacity);
+    let withdrawals = vec![
+        (build_withdrawal_request(alice, 0, capacity / 2), 0),
+        (build_withdrawal_request(bob, 0, capacity / 2), 0),
+    ];
+    test.submit_withdrawals(&withdrawals)
+        .expect("return success");
+}
+
+#[test]
+fn test_submit_block_paused() {
+    let max_pause_duration = 500u64;
+    let pause_cooldown = 300u64;
+    let capacity = 500_00000000u64;
+    // the rollup is paused `paused_for` before the block,
+    // the paused_at is removed from the output rollup cell if `clear`
+    let build_test = |paused_for: u64, clear: bool| {
+        let mut test = SubmitBlockTest::new(|config| {
+            with_extra_fields(
+                config,
+                &[
+                    (
+                        MAX_PAUSE_DURATION,
+                        Bytes::from(max_pause_duration.to_le_bytes().to_vec()),
+                    ),
+                    (
+                        PAUSE_COOLDOWN,
+                        Bytes::from(pause_cooldown.to_le_bytes().to_vec()),
+                    ),
+                ],
+            )
+        });
+        let paused_at = test.next_timestamp() - paused_for;
+        test.prev_paused_at = Some(paused_at);
+        test.post_paused_at = if clear { None } else { Some(paused_at) };
+        test
+    };
+    let submit_withdrawal = |test: &mut SubmitBlockTest| {
+        let account_script_hash = test.create_account(Bytes::from_static(b"alice"), capacity);
+        let request = build_withdrawal_request(account_script_hash, 0, capacity);
+        test.submit_withdrawals(&[(request, 0)])
+    };
+    let submit_deposit = |test: &mut SubmitBlockTest| {
+        let account_script = build_account_script(Bytes::from_static(b"alice"));
+        let deposit = test.build_deposit(account_script, capacity, None);
+        test.submit_deposits(&[deposit])
+    };
+
+    // paused, refuse withdrawals and deposits
+    let mut test = build_test(1, false);
+    let err = submit_withdrawal(&mut test).unwrap_err();
+    assert_state_validator_error(err, ROLLUP_PAUSED_ERROR);
+
+    let mut test = build_test(1, false);
+    let err = submit_deposit(&mut test).unwrap_err();
+    assert_state_validator_error(err, ROLLUP_PAUSED_ERROR);
+
+    // L2 blocks are still allowed
+    let mut test = build_test(1, false);
+    test.submit_deposits(&[]).expect("return success");
+
+    // a paused rollup can't be unpaused by submitting a block
+    let mut test = build_test(1, true);
+    let err = test.submit_deposits(&[]).unwrap_err();
+    assert_state_validator_error(err, INVALID_POST_GLOBAL_STATE);
+
+    // expired, the paused_at is kept in the cooldown
+    let mut test = build_test(max_pause_duration, false);
+    submit_withdrawal(&mut test).expect("return success");
+
+    let mut test = build_test(max_pause_duration, true);
+    let err = submit_withdrawal(&mut test).unwrap_err();
+    assert_state_validator_error(err, INVALID_POST_GLOBAL_STATE);
+
+    // the paused_at is cleared after the cooldown
+    let mut test = build_test(max_pause_duration + pause_cooldown, true);
+    submit_withdrawal(&mut test).expect("return success");
+
+    let mut test = build_test(max_pause_duration + pause_cooldown, false);
+    let err = submit_deposit(&mut test).unwrap_err();
+    assert_state_validator_error(err, INVALID_POST_GLOBAL_STATE);
+}
+
+#[test]
+fn test_submit_block_v0_global_state_unchecked_timestamp() {
+    const ABSOLUTE_TIMESTAMP_FLAG: u64 = 0x4000_0000_0000_0000;
+    let capacity = 500_00000000u64;
+
+    // the v0 global state is still allowed without a guardian
+    let mut test = SubmitBlockTest::new(|config| config);
+    test.v0_global_state = true;
+    test.submit_deposits(&[]).expect("return success");
+
+    // the producer chooses the block timestamp, which would expire the pause
+    let mut test = SubmitBlockTest::new(|config| {
+        with_extra_fields(
+            config,
+            &[(GUARDIAN_LOCK_HASH, Bytes::from([1u8; 32].to_vec()))],
+        )
+    });
+    test.v0_global_state = true;
+    let err = test.submit_deposits(&[]).unwrap_err();
+    assert_state_validator_error(err, INVALID_POST_GLOBAL_STATE);
+
+    // and would skip the withdrawal valid until by timestamp
+    let mut test = SubmitBlockTest::new(|config| config);
+    test.v0_global_state = true;
+    let valid_until = ABSOLUTE_TIMESTAMP_FLAG | test.next_timestamp();
+    let account_script_hash = test.create_account(Bytes::from_static(b"alice"), capacity);
+    let request = with_valid_until(
+        build_withdrawal_request(account_script_hash, 0, capacity),
+        valid_until,
+    );
+    let err = test.submit_withdrawals(&[(request, 0)]).unwrap_err();
+    assert_state_validator_error(err, INVALID_WITHDRAWAL_REQUEST_ERROR);
+
+    // the valid until by block number is checked by the block number
+    let mut test = SubmitBlockTest::new(|config| config);
+    test.v0_global_state = true;
+    let number: u64 = GWUnpack::unpack(&test.chain.global_state().block().count());
+    let account_script_hash = test.create_account(Bytes::from_static(b"alice"), capacity);
+    let request = with_valid_until(
+        build_withdrawal_request(account_script_hash, 0, capacity),
+        number,
+    );
+    test.submit_withdrawals(&[(request, 0)])
+        .expect("return success");
+}
+
+#[test]
+fn test_submit_block_exceeded_max_kv_pairs() {
+    let capacity = 1000_00000000u64;
+    let input_out_point = random_out_point();
+    let type_id = calculate_state_validator_type_id(input_out_point.clone());
+    let rollup_type_script = {
+        Script::new_builder()
+            .code_hash(Pack::pack(&*STATE_VALIDATOR_CODE_HASH))
+            .hash_type(ScriptHashType::Data.into())
+            .args(Pack::pack(&Bytes::from(type_id.to_vec())))
+            .build()
+    };
+    // rollup lock & config
+    let stake_lock_type = build_type_id_script(b"stake_lock_type_id");
+    let stake_script_type_hash: [u8; 32] = stake_lock_type.calc_script_hash().unpack();
+    // limits: max_txs_per_block, max_withdrawals_per_block, max_deposits_per_block, max_kv_pairs
+    let rollup_config = build_rollup_config_with_extra_fields(
+        &RollupConfig::new_builder()
+            .stake_script_type_hash(Pack::pack(&stake_script_type_hash))
+            .build(),
+        &[
+            Bytes::new(),
+            Bytes::new(),
+            Bytes::new(),
+            Bytes::from(0u32.to_le_bytes().to_vec()),
+        ],
+    );
+    // setup chain
+    let chain = setup_chain(rollup_type_script.clone(), rollup_config.clone());
+    // deploy scripts
+    let param = CellContextParam {
+        stake_lock_type,
+        ..Default::default()
+    };
+    let mut ctx = CellContext::new(&rollup_config, param);
+    let stake_capacity = 10000_00000000u64;
+    let output_stake_cell = {
+        let lock_args = StakeLockArgs::new_builder()
+            .stake_block_number(Pack::pack(&1))
+            .build();
+        build_rollup_locked_cell(
+            &rollup_type_script.hash(),
+            &stake_script_type_hash,
+            stake_capacity,
+            lock_args.as_bytes(),
+        )
+    };
+    // create a rollup cell
+    let rollup_cell = build_always_success_cell(
+        capacity,
+        Some(ckb_types::packed::Script::new_unchecked(
+            rollup_type_script.as_bytes(),
+        )),
+    );
+    let global_state = chain.local_state().last_global_state();
+    let initial_rollup_cell_data = global_state
+        .clone()
+        .as_builder()
+        .version(1u8.into())
+        .build()
+        .as_bytes();
+    // submit a new block
+    let block_result = {
+        let mem_pool = chain.mem_pool().as_ref().unwrap();
+        let mut mem_pool = smol::block_on(mem_pool.lock());
+        construct_block(&chain, &mut mem_pool, Vec::default()).unwrap()
+    };
+    let tip_block_timestamp = block_result.block.raw().timestamp();
+    let rollup_cell_data = block_result
+        .global_state
+        .as_builder()
+        .tip_block_timestamp(tip_block_timestamp.clone())
+        .version(1u8.into())
+        .build()
+        .as_bytes();
+    let build_tx = |ctx: &mut CellContext, block: L2Block| {
+        let input_stake_cell = {
+            let cell = build_rollup_locked_cell(
+                &rollup_type_script.hash(),
+                &stake_script_type_hash,
+                stake_capacity,
+                StakeLockArgs::default().as_bytes(),
+            );
+            let out_point = ctx.insert_cell(cell, Bytes::default());
+            CellInput::new_builder().previous_output(out_point).build()
+        };
+        let witness = {
+            let rollup_action = RollupAction::new_builder()
+                .set(RollupActionUnion::RollupSubmitBlock(
+                    RollupSubmitBlock::new_builder().block(block).build(),
+                ))
+                .build();
+            ckb_types::packed::WitnessArgs::new_builder()
+                .output_type(CKBPack::pack(&Some(rollup_action.as_bytes())))
+                .build()
+        };
+        build_simple_tx_with_out_point_and_since(
+            &mut ctx.inner,
+            (rollup_cell.clone(), initial_rollup_cell_data.clone()),
+            (
+                random_out_point(),
+                since_timestamp(GWUnpack::unpack(&tip_block_timestamp)),
+            ),
+            (rollup_cell.clone(), rollup_cell_data.clone()),
+        )
+        .as_advanced_builder()
+        .input(input_stake_cell)
+        .output(output_stake_cell.clone())
+        .output_data(CKBPack::pack(&Bytes::default()))
+        .cell_dep(ctx.stake_lock_dep.clone())
+        .cell_dep(ctx.always_success_dep.clone())
+        .cell_dep(ctx.state_validator_dep.clone())
+        .cell_dep(ctx.rollup_config_dep.clone())
+        .witness(CKBPack::pack(&witness.as_bytes()))
+        .build()
+    };
+
+    // the block has no kv pairs
+    let tx = build_tx(&mut ctx, block_result.block.clone());
+    ctx.verify_tx(tx).expect("return success");
+
+    // a block with kv pairs exceeds the limit
+    let block = block_result
+        .block
+        .as_builder()
+        .kv_state(KVPairVec::new_builder().push(KVPair::default()).build())
+        .build();
+    let tx = build_tx(&mut ctx, block);
+    let err = ctx.verify_tx(tx).unwrap_err();
+    let expected_err = ScriptError::ValidationFailure(
+        format!(
+            "by-data-hash/{}",
+            ckb_types::H256(*STATE_VALIDATOR_CODE_HASH)
+        ),
+        EXCEEDED_BLOCK_LIMIT_ERROR,
+    )
+    .input_type_script(0);
+    assert_error_eq!(err, expected_err);
+}
+
+#[test]
+fn test_submit_block_index_rollup_cells() {
+    let mut test = SubmitBlockTest::new(|config| config);
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        ..Default::default()
+    });
+    let rollup_type_hash = test.rollup_type_hash();
+    let custodian_script_type_hash = test.custodian_script_type_hash;
+    let malformed_args = Bytes::from(vec![0u8; 3]);
+
+    // cells of another rollup are skipped, even with malformed args
+    let other_rollup_cell = build_rollup_locked_cell(
+        &[1u8; 32],
+        &custodian_script_type_hash,
+        1000_00000000u64,
+        malformed_args.clone(),
+    );
+    let tx = test.build_submit_tx_with_cells(
+        &produced,
+        vec![(other_rollup_cell.clone(), Bytes::new())],
+        vec![(other_rollup_cell, Bytes::new())],
+    );
+    test.ctx.verify_tx(tx).expect("return success");
+
+    // malformed args of a rollup lock
+    let malformed_custodian_cell = build_rollup_locked_cell(
+        &rollup_type_hash,
+        &custodian_script_type_hash,
+        1000_00000000u64,
+        malformed_args,
+    );
+    let tx = test.build_submit_tx_with_cells(
+        &produced,
+        vec![(malformed_custodian_cell.clone(), Bytes::new())],
+        vec![(malformed_custodian_cell, Bytes::new())],
+    );
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, ENCODING_ERROR);
+
+    // more than 1 output stake cell
+    let stake_cell = build_rollup_locked_cell(
+        &rollup_type_hash,
+        &test.stake_script_type_hash,
+        10000_00000000u64,
+        StakeLockArgs::default().as_bytes(),
+    );
+    let tx =
+        test.build_submit_tx_with_cells(&produced, Vec::new(), vec![(stake_cell, Bytes::new())]);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, INVALID_STAKE_CELL_ERROR);
+}
+
+#[test]
+fn test_submit_block_with_wrong_block_merkle_root() {
+    let mut test = SubmitBlockTest::new(|config| config);
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        ..Default::default()
+    });
+    let with_block_root = |global_state: &GlobalState, root: [u8; 32]| {
+        let block = global_state
+            .block()
+            .as_builder()
+            .merkle_root(Pack::pack(&root))
+            .build();
+        global_state.clone().as_builder().block(block).build()
+    };
+    let prev_block_root: [u8; 32] = produced.prev_global_state.block().merkle_root().unpack();
+    let post_block_root: [u8; 32] = produced.global_state.block().merkle_root().unpack();
+
+    // the post root doesn't contain the block
+    let wrong_post = ProducedBlock {
+        block: produced.block.clone(),
+        prev_global_state: produced.prev_global_state.clone(),
+        global_state: with_block_root(&produced.global_state, prev_block_root),
+    };
+    let tx = test.build_submit_tx(&wrong_post);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, MERKLE_PROOF_ERROR);
+
+    // the prev root already contains the block
+    let wrong_prev = ProducedBlock {
+        block: produced.block.clone(),
+        prev_global_state: with_block_root(&produced.prev_global_state, post_block_root),
+        global_state: produced.global_state.clone(),
+    };
+    let tx = test.build_submit_tx(&wrong_prev);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, MERKLE_PROOF_ERROR);
+
+    let tx = test.build_submit_tx(&produced);
+    test.ctx.verify_tx(tx).expect("return success");
+}
+
+#[test]
+fn test_submit_block_sudt_deposit_cap() {
+    let sudt_script = build_l1_sudt_script(b"capped_sudt");
+    let sudt_script_hash: [u8; 32] = sudt_script.calc_script_hash().unpack();
+    let mut test = SubmitBlockTest::new(|config| {
+        let mut caps = sudt_script_hash.to_vec();
+        caps.extend_from_slice(&100u128.to_le_bytes());
+        with_extra_fields(config, &[(SUDT_DEPOSIT_CAPS, caps.into())])
+    });
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    let account_script_hash: H256 = account_script.hash().into();
+    let submit_deposit = |test: &mut SubmitBlockTest, amount: u128| {
+        let deposit = test.build_deposit(
+            account_script.clone(),
+            1000_00000000u64,
+            Some((sudt_script.clone(), amount)),
+        );
+        test.submit_deposits(&[deposit])
+    };
+
+    submit_deposit(&mut test, 60).expect("deposit under the cap");
+
+    // withdraw 30, the change custodian holds more than the cap,
+    // the sUDT was bridged before the cap is set
+    let withdrawal = {
+        let request = build_withdrawal_request(account_script_hash, 0, 400_00000000u64);
+        let raw = request
+            .raw()
+            .as_builder()
+            .sudt_script_hash(Pack::pack(&sudt_script_hash))
+            .amount(Pack::pack(&30u128))
+            .build();
+        request.as_builder().raw(raw).build()
+    };
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        withdrawals: vec![withdrawal.clone()],
+        ..Default::default()
+    });
+    let input_custodian_cell = with_sudt(
+        test.build_finalized_custodian_cell(1000_00000000u64),
+        &sudt_script,
+        200,
+    );
+    let withdrawal_cell = with_sudt(
+        test.build_withdrawal_cell(&produced, &withdrawal, 0),
+        &sudt_script,
+        30,
+    );
+    let change_custodian_cell = with_sudt(
+        test.build_finalized_custodian_cell(600_00000000u64),
+        &sudt_script,
+        170,
+    );
+    let tx = test.build_submit_tx_with_cells(
+        &produced,
+        vec![input_custodian_cell],
+        vec![withdrawal_cell, change_custodian_cell],
+    );
+    test.ctx
+        .verify_tx(tx)
+        .expect("withdraw with change custodian");
+
+    // the cap is checked against the custodian cells of a block
+    submit_deposit(&mut test, 100).expect("deposit up to the cap");
+    let err = submit_deposit(&mut test, 101).unwrap_err();
+    assert_state_validator_error(err, EXCEEDED_DEPOSIT_CAP_ERROR);
+}
+
+#[test]
+fn test_submit_block_sudt_deposit_cap_merged_custodian() {
+    let sudt_script = build_l1_sudt_script(b"capped_sudt");
+    let sudt_script_hash: [u8; 32] = sudt_script.calc_script_hash().unpack();
+    let mut test = SubmitBlockTest::new(|config| {
+        let mut caps = sudt_script_hash.to_vec();
+        caps.extend_from_slice(&100u128.to_le_bytes());
+        with_extra_fields(config, &[(SUDT_DEPOSIT_CAPS, caps.into())])
+    });
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    let deposit = test.build_deposit(
+        account_script,
+        1000_00000000u64,
+        Some((sudt_script.clone(), 60)),
+    );
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        deposits: vec![L2Deposit {
+            request: deposit.request.clone(),
+            ckb_fee: deposit.ckb_fee,
+            sudt_fee: deposit.sudt_fee,
+            nft: deposit.nft,
+        }],
+        ..Default::default()
+    });
+    // the deposit is under the cap, but the block raises the custodian balance to 110
+    let finalized_custodian_cell = with_sudt(
+        test.build_finalized_custodian_cell(1000_00000000u64),
+        &sudt_script,
+        50,
+    );
+    let deposit_cell = test.build_deposit_cell(&deposit);
+    let deposit_custodian_cell = test.build_deposit_custodian_cell(&produced, &deposit);
+    let tx = test.build_submit_tx_with_cells(
+        &produced,
+        vec![deposit_cell, finalized_custodian_cell.clone()],
+        vec![deposit_custodian_cell, finalized_custodian_cell],
+    );
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, EXCEEDED_DEPOSIT_CAP_ERROR);
+}
+
+#[test]
+fn test_submit_block_min_deposit_capacity() {
+    let min_deposit_capacity = 500_00000000u64;
+    let mut test = SubmitBlockTest::new(|config| {
+        let field = Bytes::from(min_deposit_capacity.to_le_bytes().to_vec());
+        with_extra_fields(config, &[(MIN_DEPOSIT_CAPACITY, field)])
+    });
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    for (capacity, expected_err) in vec![
+        (min_deposit_capacity, None),
+        (min_deposit_capacity - 1, Some(INVALID_DEPOSIT_CELL_ERROR)),
+    ] {
+        let deposit = test.build_deposit(account_script.clone(), capacity, None);
+        let result = test.submit_deposits(&[deposit]);
+        match expected_err {
+            None => {
+                result.expect("return success");
+            }
+            Some(code) => assert_state_validator_error(result.unwrap_err(), code),
+        }
+    }
+}
+
+#[test]
+fn test_submit_block_min_deposit_cancel_timeout() {
+    // relative 100 blocks
+    let min_cancel_timeout = 0x8000_0000_0000_0000u64 | 100;
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    for (cancel_timeout, expected_err) in vec![
+        (min_cancel_timeout, None),
+        // relative 101 blocks
+        (min_cancel_timeout + 1, None),
+        // relative 99 blocks
+        (min_cancel_timeout - 1, Some(INVALID_DEPOSIT_CELL_ERROR)),
+        // absolute block 1000
+        (1000, Some(INVALID_DEPOSIT_CELL_ERROR)),
+        // relative epoch 100
+        (
+            0xa000_0000_0000_0000u64 | 100,
+            Some(INVALID_DEPOSIT_CELL_ERROR),
+        ),
+        // malformed, invalid flags
+        (
+            0x8100_0000_0000_0000u64 | 100,
+            Some(INVALID_DEPOSIT_CELL_ERROR),
+        ),
+    ] {
+        let mut test = SubmitBlockTest::new(|config| {
+            let field = Bytes::from(min_cancel_timeout.to_le_bytes().to_vec());
+            with_extra_fields(config, &[(MIN_DEPOSIT_CANCEL_TIMEOUT, field)])
+        });
+        let deposit = test.build_deposit(account_script.clone(), 1000_00000000u64, None);
+        let lock_args = deposit
+            .lock_args
+            .clone()
+            .as_builder()
+            .cancel_timeout(Pack::pack(&cancel_timeout))
+            .build();
+        let deposit = TestDeposit {
+            lock_args,
+            ..deposit
+        };
+        let result = test.submit_deposits(&[deposit]);
+        match expected_err {
+            None => {
+                result.expect("return success");
+            }
+            Some(code) => assert_state_validator_error(result.unwrap_err(), code),
+        }
+    }
+}
+
+#[test]
+fn test_submit_block_deposit_fee() {
+    let capacity = 1000_00000000u64;
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    let short_address = to_short_address(&account_script.hash().into()).to_vec();
+    let sudt_script = build_l1_sudt_script(b"fee_sudt");
+    let sudt_script_hash: H256 = sudt_script.calc_script_hash().unpack();
+
+    // CKB fee, up to the deposited capacity
+    for ckb_fee in vec![0, 1_00000000u64, capacity] {
+        let mut test = SubmitBlockTest::new(|config| config);
+        let producer_short_address =
+            to_short_address(&test.chain.block_producer_script_hash()).to_vec();
+        let producer_balance = test
+            .chain
+            .state
+            .get_sudt_balance(CKB_SUDT_ACCOUNT_ID, &producer_short_address)
+            .unwrap();
+        let deposit = test.build_deposit(account_script.clone(), capacity, None);
+        let deposit = with_deposit_fee(deposit, ckb_fee, 0);
+        test.submit_deposits(&[deposit]).expect("deposit with fee");
+        let state = &test.chain.state;
+        let balance = state
+            .get_sudt_balance(CKB_SUDT_ACCOUNT_ID, &short_address)
+            .unwrap();
+        assert_eq!(balance, u128::from(capacity - ckb_fee));
+        let balance = state
+            .get_sudt_balance(CKB_SUDT_ACCOUNT_ID, &producer_short_address)
+            .unwrap();
+        assert_eq!(balance, producer_balance + u128::from(ckb_fee));
+    }
+
+    // sUDT fee, up to the deposited amount
+    for sudt_fee in vec![0, 10u128, 100] {
+        let mut test = SubmitBlockTest::new(|config| config);
+        let producer_short_address =
+            to_short_address(&test.chain.block_producer_script_hash()).to_vec();
+        let deposit = test.build_deposit(
+            account_script.clone(),
+            capacity,
+            Some((sudt_script.clone(), 100)),
+        );
+        let deposit = with_deposit_fee(deposit, 1_00000000u64, sudt_fee);
+        test.submit_deposits(&[deposit]).expect("deposit with fee");
+        let chain = &mut test.chain;
+        let sudt_id = get_or_create_sudt_id(
+            &mut chain.state,
+            &chain.rollup_type_hash,
+            &chain.rollup_config,
+            &sudt_script_hash,
+        );
+        let balance = chain
+            .state
+            .get_sudt_balance(sudt_id, &short_address)
+            .unwrap();
+        assert_eq!(balance, 100 - sudt_fee);
+        let balance = chain
+            .state
+            .get_sudt_balance(sudt_id, &producer_short_address)
+            .unwrap();
+        assert_eq!(balance, sudt_fee);
+    }
+}
+
+#[test]
+fn test_submit_block_invalid_deposit_fee() {
+    let capacity = 1000_00000000u64;
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    let sudt_script = build_l1_sudt_script(b"fee_sudt");
+
+    // the CKB fee exceeds the deposited capacity
+    let mut test = SubmitBlockTest::new(|config| config);
+    let deposit = test.build_deposit(account_script.clone(), capacity, None);
+    let deposit = TestDeposit {
+        ckb_fee: 0,
+        ..with_deposit_fee(deposit, capacity + 1, 0)
+    };
+    let err = test.submit_deposits(&[deposit]).unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+
+    // sUDT fee of a CKB deposit
+    let mut test = SubmitBlockTest::new(|config| config);
+    let deposit = test.build_deposit(account_script.clone(), capacity, None);
+    let deposit = TestDeposit {
+        sudt_fee: 0,
+        ..with_deposit_fee(deposit, 0, 1)
+    };
+    let err = test.submit_deposits(&[deposit]).unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+
+    // the sUDT fee exceeds the deposited amount
+    let mut test = SubmitBlockTest::new(|config| config);
+    let deposit = test.build_deposit(
+        account_script.clone(),
+        capacity,
+        Some((sudt_script.clone(), 100)),
+    );
+    let deposit = TestDeposit {
+        sudt_fee: 0,
+        ..with_deposit_fee(deposit, 0, 101)
+    };
+    let err = test.submit_deposits(&[deposit]).unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+
+    // the block pays a wrong fee to the block producer,
+    // the state checkpoint before the transactions must include the fee,
+    // it's the prev state of the first transaction a challenge starts from
+    let mut test = SubmitBlockTest::new(|config| config);
+    let deposit = test.build_deposit(account_script, capacity, None);
+    let deposit = TestDeposit {
+        ckb_fee: 1,
+        ..with_deposit_fee(deposit, 1_00000000u64, 0)
+    };
+    let err = test.submit_deposits(&[deposit]).unwrap_err();
+    assert_state_validator_error(err, INVALID_STATE_CHECKPOINT_ERROR);
+}
+
+#[test]
+fn test_submit_block_l1_token_allowlist_and_blocklist() {
+    let allowed_sudt_script = build_l1_sudt_script(b"allowed_sudt");
+    let blocked_sudt_script = build_l1_sudt_script(b"blocked_sudt");
+    let nft_type_id = build_type_id_script(b"nft_type_id");
+    let nft_code_hash: [u8; 32] = nft_type_id.calc_script_hash().unpack();
+    let nft_script = ckb_types::packed::Script::new_builder()
+        .code_hash(CKBPack::pack(&nft_code_hash))
+        .hash_type(ScriptHashType::Type.into())
+        .args(CKBPack::pack(&Bytes::from_static(b"nft_1")))
+        .build();
+    let blocked_nft_script = nft_script
+        .clone()
+        .as_builder()
+        .args(CKBPack::pack(&Bytes::from_static(b"nft_2")))
+        .build();
+    let script_hash =
+        |script: &ckb_types::packed::Script| -> [u8; 32] { script.calc_script_hash().unpack() };
+    let build_test = || {
+        let allowlist = script_hash(&allowed_sudt_script).to_vec();
+        let mut blocklist = script_hash(&blocked_sudt_script).to_vec();
+        blocklist.extend_from_slice(&script_hash(&blocked_nft_script));
+        let mut test = SubmitBlockTest::new(|config| {
+            with_extra_fields(
+                config,
+                &[
+                    (L1_SUDT_ALLOWLIST, allowlist.into()),
+                    (L1_SUDT_BLOCKLIST, blocklist.into()),
+                    (L1_NFT_SCRIPT_CODE_HASHES, nft_code_hash.to_vec().into()),
+                    (
+                        L2_NFT_VALIDATOR_SCRIPT_TYPE_HASH,
+                        ALWAYS_SUCCESS_CODE_HASH.to_vec().into(),
+                    ),
+                ],
+            )
+        });
+        test.deploy_type_script(nft_type_id.clone());
+        test
+    };
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    let capacity = 1000_00000000u64;
+
+    // an allowed sUDT and a NFT out of the sUDT allowlist
+    let mut test = build_test();
+    let sudt_deposit = test.build_deposit(
+        account_script.clone(),
+        capacity,
+        Some((allowed_sudt_script.clone(), 100)),
+    );
+    let nft_deposit = test.build_nft_deposit(
+        account_script.clone(),
+        capacity,
+        nft_script,
+        Bytes::from_static(b"nft content"),
+    );
+    test.submit_deposits(&[sudt_deposit, nft_deposit])
+        .expect("deposit allowed tokens");
+
+    // a sUDT out of the allowlist
+    let mut test = build_test();
+    let deposit = test.build_deposit(
+        account_script.clone(),
+        capacity,
+        Some((build_l1_sudt_script(b"unknown_sudt"), 100)),
+    );
+    let err = test.submit_deposits(&[deposit]).unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+
+    // the blocklist applies to sUDT and NFT
+    let mut test = build_test();
+    let deposit = test.build_deposit(
+        account_script.clone(),
+        capacity,
+        Some((blocked_sudt_script, 100)),
+    );
+    let err = test.submit_deposits(&[deposit]).unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+
+    let mut test = build_test();
+    let deposit = test.build_nft_deposit(
+        account_script,
+        capacity,
+        blocked_nft_script,
+        Bytes::from_static(b"nft content"),
+    );
+    let err = test.submit_deposits(&[deposit]).unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+}
+
+#[test]
+fn test_submit_block_xudt_deposit() {
+    let xudt_type_id = build_type_id_script(b"xudt_type_id");
+    let xudt_code_hash: [u8; 32] = xudt_type_id.calc_script_hash().unpack();
+    let build_xudt_script = |args: Vec<u8>| {
+        ckb_types::packed::Script::new_builder()
+            .code_hash(CKBPack::pack(&xudt_code_hash))
+            .hash_type(ScriptHashType::Type.into())
+            .args(CKBPack::pack(&Bytes::from(args)))
+            .build()
+    };
+    let xudt_args = |flags: Option<u32>, extra: &[u8]| {
+        let mut args = vec![7u8; 32];
+        if let Some(flags) = flags {
+            args.extend_from_slice(&flags.to_le_bytes());
+        }
+        args.extend_from_slice(extra);
+        args
+    };
+    let account_script = build_account_script(Bytes::from_static(b"alice"));
+    let capacity = 1000_00000000u64;
+    let amount = 100u128;
+    // extension data after the amount is ignored
+    let mut data = amount.to_le_bytes().to_vec();
+    data.extend_from_slice(b"extension data");
+
+    for (args, expected_err) in vec![
+        // no flags
+        (xudt_args(None, &[]), None),
+        // owner mode only
+        (xudt_args(Some(0x8000_0000), &[]), None),
+        (xudt_args(Some(0xe000_0000), &[]), None),
+        (xudt_args(Some(0), &[]), None),
+        // with extension scripts
+        (
+            xudt_args(Some(0x1), &[0u8; 32]),
+            Some(INVALID_SUDT_CELL_ERROR),
+        ),
+        (
+            xudt_args(Some(0x8000_0002), &[0u8; 32]),
+            Some(INVALID_SUDT_CELL_ERROR),
+        ),
+        (
+            xudt_args(Some(0x1000_0000), &[0u8; 32]),
+            Some(INVALID_SUDT_CELL_ERROR),
+        ),
+        // malformed args
+        (vec![7u8; 20], Some(INVALID_SUDT_CELL_ERROR)),
+        (xudt_args(None, &[0u8; 2]), Some(INVALID_SUDT_CELL_ERROR)),
+    ] {
+        let mut test = SubmitBlockTest::new(|config| {
+            with_extra_fields(
+                config,
+                &[(L1_XUDT_SCRIPT_TYPE_HASH, xudt_code_hash.to_vec().into())],
+            )
+        });
+        test.deploy_type_script(xudt_type_id.clone());
+        let deposit = test.build_token_deposit(
+            account_script.clone(),
+            capacity,
+            Some((build_xudt_script(args), data.clone().into())),
+            amount,
+        );
+        let result = test.submit_deposits(&[deposit]);
+        match expected_err {
+            None => {
+                result.expect("return success");
+            }
+            Some(code) => assert_state_validator_error(result.unwrap_err(), code),
+        }
+    }
+}
+
+/// NFT script and data of the NFT tests
+fn build_nft() -> (ckb_types::packed::Script, Bytes) {
+    let nft_code_hash: [u8; 32] = build_type_id_script(b"nft_type_id")
+        .calc_script_hash()
+        .unpack();
+    let nft_script = ckb_types::packed::Script::new_builder()
+        .code_hash(CKBPack::pack(&nft_code_hash))
+        .hash_type(ScriptHashType::Type.into())
+        .args(CKBPack::pack(&Bytes::from_static(b"nft_1")))
+        .build();
+    (nft_script, Bytes::from_static(b"nft content"))
+}
+
+/// Test of a rollup bridging NFTs of `build_nft`
+fn build_nft_test(l2_nft_validator: bool) -> SubmitBlockTest {
+    let nft_type_id = build_type_id_script(b"nft_type_id");
+    let nft_code_hash: [u8; 32] = nft_type_id.calc_script_hash().unpack();
+    let mut test = SubmitBlockTest::new(|config| {
+        let mut fields = vec![(L1_NFT_SCRIPT_CODE_HASHES, nft_code_hash.to_vec().into())];
+        if l2_nft_validator {
+            fields.push((
+                L2_NFT_VALIDATOR_SCRIPT_TYPE_HASH,
+                ALWAYS_SUCCESS_CODE_HASH.to_vec().into(),
+            ));
+        }
+        with_extra_fields(config, &fields)
+    });
+    test.deploy_type_script(nft_type_id);
+    test
+}
+
+/// Submit a block withdrawing the NFT of `build_nft` from a finalized custodian cell
+fn submit_nft_withdrawal(
+    test: &mut SubmitBlockTest,
+    account_script_hash: H256,
+    nonce: u32,
+) -> Result<u64, ckb_error::Error> {
+    let nft = build_nft();
+    let nft_type_hash: [u8; 32] = nft.0.calc_script_hash().unpack();
+    let withdrawal = {
+        let request = build_withdrawal_request(account_script_hash, nonce, 400_00000000u64);
+        let raw = request
+            .raw()
+            .as_builder()
+            .sudt_script_hash(Pack::pack(&nft_type_hash))
+            .amount(Pack::pack(&1u128))
+            .build();
+        request.as_builder().raw(raw).build()
+    };
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        withdrawals: vec![withdrawal.clone()],
+        ..Default::default()
+    });
+    let input_custodian_cell = with_token(
+        test.build_finalized_custodian_cell(1000_00000000u64),
+        Some(&nft),
+    );
+    let withdrawal_cell = with_token(
+        test.build_withdrawal_cell(&produced, &withdrawal, 0),
+        Some(&nft),
+    );
+    let change_custodian_cell = (
+        test.build_finalized_custodian_cell(600_00000000u64),
+        Bytes::new(),
+    );
+    let tx = test.build_submit_tx_with_cells(
+        &produced,
+        vec![input_custodian_cell],
+        vec![withdrawal_cell, change_custodian_cell],
+    );
+    test.ctx.verify_tx(tx)
+}
+
+#[test]
+fn test_submit_block_nft_deposit_and_withdrawal() {
+    let (nft_script, nft_data) = build_nft();
+    let alice_script = build_account_script(Bytes::from_static(b"alice"));
+    let alice_script_hash: H256 = alice_script.hash().into();
+    let bob_script = build_account_script(Bytes::from_static(b"bob"));
+    let capacity = 1000_00000000u64;
+    let deposit_nft = |test: &mut SubmitBlockTest, account_script: &Script| {
+        let deposit = test.build_nft_deposit(
+            account_script.clone(),
+            capacity,
+            nft_script.clone(),
+            nft_data.clone(),
+        );
+        test.submit_deposits(&[deposit])
+    };
+
+    // the NFT can come back to layer2 after the withdrawal
+    let mut test = build_nft_test(true);
+    deposit_nft(&mut test, &alice_script).expect("deposit NFT");
+    submit_nft_withdrawal(&mut test, alice_script_hash, 0).expect("withdraw NFT");
+    deposit_nft(&mut test, &bob_script).expect("deposit withdrawn NFT");
+    // the NFT is on layer2, another cell of the same type hash is refused
+    let err = deposit_nft(&mut test, &alice_script).unwrap_err();
+    assert_state_validator_error(err, INVALID_NFT_CELL_ERROR);
+
+    // two cells of the same type hash in a block
+    let mut test = build_nft_test(true);
+    let deposits: Vec<_> = [&alice_script, &bob_script]
+        .iter()
+        .map(|account_script| {
+            test.build_nft_deposit(
+                (*account_script).clone(),
+                capacity,
+                nft_script.clone(),
+                nft_data.clone(),
+            )
+        })
+        .collect();
+    let err = test.submit_deposits(&deposits).unwrap_err();
+    assert_state_validator_error(err, INVALID_NFT_CELL_ERROR);
+
+    // NFTs can't be deposited without the layer2 NFT validator
+    let mut test = build_nft_test(false);
+    let err = deposit_nft(&mut test, &alice_script).unwrap_err();
+    assert_state_validator_error(err, INVALID_NFT_CELL_ERROR);
+}
+
+#[test]
+fn test_submit_block_withdraw_nft_of_others() {
+    let (nft_script, nft_data) = build_nft();
+    let alice_script = build_account_script(Bytes::from_static(b"alice"));
+    let bob_script = build_account_script(Bytes::from_static(b"bob"));
+    let bob_script_hash: H256 = bob_script.hash().into();
+    let capacity = 1000_00000000u64;
+    let mut test = build_nft_test(true);
+    let deposits = vec![
+        test.build_nft_deposit(alice_script, capacity, nft_script, nft_data),
+        test.build_deposit(bob_script, capacity, None),
+    ];
+    test.submit_deposits(&deposits).expect("deposit");
+    let err = submit_nft_withdrawal(&mut test, bob_script_hash, 0).unwrap_err();
+    assert_state_validator_error(err, INVALID_WITHDRAWAL_REQUEST_ERROR);
+}
+
+const MESSAGE_OWNER_LOCK_HASH: [u8; 32] = [9u8; 32];
+const MESSAGE_CAPACITY: u64 = 200_00000000u64;
+
+/// A L1 -> L2 message cell, see `gw_utils::cells::message`
+struct TestMessage {
+    to_id: u32,
+    payload: Bytes,
+}
+
+impl TestMessage {
+    /// hash(owner_lock_hash | to_id | payload_hash)
+    fn hash(&self) -> H256 {
+        let mut payload_hash = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&self.payload);
+        hasher.finalize(&mut payload_hash);
+        let mut hash = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&MESSAGE_OWNER_LOCK_HASH);
+        hasher.update(&self.to_id.to_le_bytes());
+        hasher.update(&payload_hash);
+        hasher.finalize(&mut hash);
+        hash.into()
+    }
+
+    fn l2_message(&self) -> L2Message {
+        L2Message {
+            to_id: self.to_id,
+            hash: self.hash(),
+        }
+    }
+}
+
+/// Test of a rollup accepting messages
+fn build_message_test() -> SubmitBlockTest {
+    let message_lock_type = build_type_id_script(MESSAGE_LOCK_TYPE_ID);
+    let message_script_type_hash: [u8; 32] = message_lock_type.calc_script_hash().unpack();
+    let mut test = SubmitBlockTest::new(|config| {
+        with_extra_fields(
+            config,
+            &[(
+                MESSAGE_SCRIPT_TYPE_HASH,
+                message_script_type_hash.to_vec().into(),
+            )],
+        )
+    });
+    test.deploy_type_script(message_lock_type);
+    test
+}
+
+/// The message cell, marked with the (hash, number) of the block if it is delivered
+fn build_message_cell(
+    test: &SubmitBlockTest,
+    message: &TestMessage,
+    delivered_block: Option<(H256, u64)>,
+) -> (ckb_types::packed::CellOutput, Bytes) {
+    let message_script_type_hash: [u8; 32] = build_type_id_script(MESSAGE_LOCK_TYPE_ID)
+        .calc_script_hash()
+        .unpack();
+    let mut args = MESSAGE_OWNER_LOCK_HASH.to_vec();
+    args.extend_from_slice(&message.to_id.to_le_bytes());
+    // relative 100 blocks
+    args.extend_from_slice(&(0x8000_0000_0000_0000u64 | 100).to_le_bytes());
+    if let Some((block_hash, block_number)) = delivered_block {
+        args.extend_from_slice(block_hash.as_slice());
+        args.extend_from_slice(&block_number.to_le_bytes());
+    }
+    let cell = build_rollup_locked_cell(
+        &test.rollup_type_hash(),
+        &message_script_type_hash,
+        MESSAGE_CAPACITY,
+        args.into(),
+    );
+    (cell, message.payload.clone())
+}
+
+/// Produce and submit a block delivering the messages,
+/// `delivered_block` overrides the mark of the output message cells
+fn submit_messages(
+    test: &mut SubmitBlockTest,
+    messages: &[TestMessage],
+    delivered_block: Option<(H256, u64)>,
+) -> (ProducedBlock, Result<u64, ckb_error::Error>) {
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        messages: messages.iter().map(TestMessage::l2_message).collect(),
+        ..Default::default()
+    });
+    let delivered_block = delivered_block.unwrap_or_else(|| {
+        (
+            produced.block.raw().hash().into(),
+            GWUnpack::unpack(&produced.block.raw().number()),
+        )
+    });
+    let inputs = messages
+        .iter()
+        .map(|message| build_message_cell(test, message, None))
+        .collect();
+    let outputs = messages
+        .iter()
+        .map(|message| build_message_cell(test, message, Some(delivered_block)))
+        .collect();
+    let tx = test.build_submit_tx_with_cells(&produced, inputs, outputs);
+    let result = test.ctx.verify_tx(tx);
+    (produced, result)
+}
+
+#[test]
+fn test_submit_block_deliver_and_restore_messages() {
+    let mut test = build_message_test();
+    let to_id = test.chain.block_producer_id;
+    let messages = vec![
+        TestMessage {
+            to_id,
+            payload: Bytes::from_static(b"hello"),
+        },
+        TestMessage {
+            to_id,
+            payload: Bytes::from_static(b"world"),
+        },
+    ];
+    let (produced, result) = submit_messages(&mut test, &messages, None);
+    result.expect("deliver messages");
+    // the receiver reads the messages from its account state
+    for (n, message) in messages.iter().enumerate() {
+        let value = test
+            .chain
+            .state
+            .get_value(to_id, &build_message_key(n as u64))
+            .unwrap();
+        assert_eq!(value, message.hash());
+    }
+
+    // the next block reverts the delivered block and restores the messages
+    let delivered_block: (H256, u64) = (
+        produced.block.raw().hash().into(),
+        GWUnpack::unpack(&produced.block.raw().number()),
+    );
+    test.revert_blocks(vec![delivered_block.0]);
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        ..Default::default()
+    });
+    let inputs = messages
+        .iter()
+        .map(|message| build_message_cell(&test, message, Some(delivered_block)))
+        .collect();
+    let outputs = messages
+        .iter()
+        .map(|message| build_message_cell(&test, message, None))
+        .collect();
+    let tx = test.build_submit_tx_with_cells(&produced, inputs, outputs);
+    test.ctx.verify_tx(tx).expect("restore messages");
+}
+
+#[test]
+fn test_submit_block_invalid_message_cells() {
+    let message = TestMessage {
+        to_id: 0,
+        payload: Bytes::from_static(b"hello"),
+    };
+
+    // the output message cell is marked with a wrong block
+    let mut test = build_message_test();
+    let (_produced, result) = submit_messages(
+        &mut test,
+        std::slice::from_ref(&message),
+        Some((H256::zero(), 1)),
+    );
+    assert_state_validator_error(result.unwrap_err(), INVALID_MESSAGE_CELL_ERROR);
+
+    // the receiver isn't exist
+    let mut test = build_message_test();
+    let unknown_receiver = TestMessage {
+        to_id: 100,
+        payload: message.payload.clone(),
+    };
+    let (_produced, result) = submit_messages(&mut test, &[unknown_receiver], None);
+    assert_state_validator_error(result.unwrap_err(), INVALID_MESSAGE_CELL_ERROR);
+
+    // the capacity of the message is taken by the block producer
+    let mut test = build_message_test();
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        messages: vec![message.l2_message()],
+        ..Default::default()
+    });
+    let inputs = vec![build_message_cell(&test, &message, None)];
+    let tx = test.build_submit_tx_with_cells(&produced, inputs, Vec::new());
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, INVALID_MESSAGE_CELL_ERROR);
+
+    // restore a message of an unreverted block
+    let mut test = build_message_test();
+    let produced = test.chain.produce_block(BlockParam {
+        timestamp: test.next_timestamp(),
+        ..Default::default()
+    });
+    let inputs = vec![build_message_cell(
+        &test,
+        &message,
+        Some((H256::from([1u8; 32]), 1)),
+    )];
+    let outputs = vec![build_message_cell(&test, &message, None)];
+    let tx = test.build_submit_tx_with_cells(&produced, inputs, outputs);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, INVALID_MESSAGE_CELL_ERROR);
+}
+
+/// Deposit to the account alice with a layer2 call to the block producer,
+/// alice is created by the deposit if she doesn't exist,
+/// return the deposit and the call transaction
+fn build_deposit_call(
+    test: &mut SubmitBlockTest,
+    call_args: &[u8],
+) -> (TestDeposit, L2Transaction) {
+    let alice_script_hash: H256 = build_account_script(Bytes::from_static(b"alice"))
+        .hash()
+        .into();
+    let state = &test.chain.state;
+    let alice_id = match state
+        .get_account_id_by_script_hash(&alice_script_hash)
+        .unwrap()
+    {
+        Some(id) => id,
+        None => state.get_account_count().unwrap(),
+    };
+    let to_id = test.chain.block_producer_id;
+    let deposit = test.build_deposit(
+        build_account_script(Bytes::from_static(b"alice")),
+        400_00000000u64,
+        None,
+    );
+    // extra field 0 of the deposit lock args, see `gw_utils::deposit`
+    let call = {
+        let mut call = to_id.to_le_bytes().to_vec();
+        call.extend_from_slice(call_args);
+        Bytes::from(call)
+    };
+    let lock_args =
+        DepositLockArgs::new_unchecked(extend_table(deposit.lock_args.as_slice(), &[call]));
+    let call_tx = L2Transaction::new_builder()
+        .raw(
+            RawL2Transaction::new_builder()
+                .from_id(Pack::pack(&alice_id))
+                .to_id(Pack::pack(&to_id))
+                .nonce(Pack::pack(&0u32))
+                .args(Pack::pack(&Bytes::from(call_args.to_vec())))
+                .build(),
+        )
+        .build();
+    (
+        TestDeposit {
+            lock_args,
+            ..deposit
+        },
+        call_tx,
+    )
+}
+
+/// Submit the deposit in a block of the transactions,
+/// `deposit_call_count` is the extra field 1 of the raw block, see `gw_utils::block`
+fn submit_deposit_call(
+    test: &mut SubmitBlockTest,
+    deposit: TestDeposit,
+    transactions: Vec<L2Transaction>,
+    deposit_call_count: Option<u32>,
+) -> Result<(ProducedBlock, u64), ckb_error::Error> {
+    let raw_block_extra_fields = match deposit_call_count {
+        Some(count) => vec![Bytes::new(), Bytes::from(count.to_le_bytes().to_vec())],
+        None => Vec::new(),
+    };
+    test.submit_deposits_with(
+        &[deposit],
+        BlockParam {
+            transactions,
+            raw_block_extra_fields,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn test_submit_block_deposit_call() {
+    let mut test = SubmitBlockTest::new(|config| config);
+    let (deposit, call_tx) = build_deposit_call(&mut test, b"call");
+    submit_deposit_call(&mut test, deposit, vec![call_tx], Some(1)).expect("deposit call");
+
+    // the deposit call count is missing
+    let mut test = SubmitBlockTest::new(|config| config);
+    let (deposit, call_tx) = build_deposit_call(&mut test, b"call");
+    let err = submit_deposit_call(&mut test, deposit, vec![call_tx], None).unwrap_err();
+    assert_state_validator_error(err, INVALID_BLOCK_ERROR);
+
+    // the call isn't in the block
+    let mut test = SubmitBlockTest::new(|config| config);
+    let (deposit, _call_tx) = build_deposit_call(&mut test, b"call");
+    let err = submit_deposit_call(&mut test, deposit, Vec::new(), Some(1)).unwrap_err();
+    assert_state_validator_error(err, INVALID_BLOCK_ERROR);
+
+    // the leading transaction isn't the call
+    let mut test = SubmitBlockTest::new(|config| config);
+    let (deposit, call_tx) = build_deposit_call(&mut test, b"call");
+    let other_tx = {
+        let raw = call_tx
+            .raw()
+            .as_builder()
+            .args(Pack::pack(&Bytes::from_static(b"other")))
+            .build();
+        call_tx.as_builder().raw(raw).build()
+    };
+    let err = submit_deposit_call(&mut test, deposit, vec![other_tx], Some(1)).unwrap_err();
+    assert_state_validator_error(err, INVALID_BLOCK_ERROR);
+
+    // a transaction of no deposit is counted as a call
+    let mut test = SubmitBlockTest::new(|config| config);
+    let (_deposit, call_tx) = build_deposit_call(&mut test, b"call");
+    let deposit = test.build_deposit(
+        build_account_script(Bytes::from_static(b"alice")),
+        400_00000000u64,
+        None,
+    );
+    let err = submit_deposit_call(&mut test, deposit, vec![call_tx], Some(1)).unwrap_err();
+    assert_state_validator_error(err, INVALID_BLOCK_ERROR);
+}
+
+#[test]
+fn test_submit_block_deposit_call_from_existing_account() {
+    // anyone can deposit to an existing account, the call isn't authorized by the account
+    let mut test = SubmitBlockTest::new(|config| config);
+    test.create_account(Bytes::from_static(b"alice"), 1000_00000000u64);
+    let (deposit, call_tx) = build_deposit_call(&mut test, b"transfer to me");
+    let err = submit_deposit_call(&mut test, deposit, vec![call_tx], Some(1)).unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+
+    // another deposit to the account created in the same block
+    let mut test = SubmitBlockTest::new(|config| config);
+    let (deposit, call_tx) = build_deposit_call(&mut test, b"call");
+    let other_deposit = test.build_deposit(
+        build_account_script(Bytes::from_static(b"alice")),
+        400_00000000u64,
+        None,
+    );
+    let err = test
+        .submit_deposits_with(
+            &[deposit, other_deposit],
+            BlockParam {
+                transactions: vec![call_tx],
+                raw_block_extra_fields: vec![
+                    Bytes::new(),
+                    Bytes::from(1u32.to_le_bytes().to_vec()),
+                ],
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+    assert_state_validator_error(err, INVALID_DEPOSIT_CELL_ERROR);
+}
+
+/// Enter a challenge of the first transaction of the produced block
+fn build_enter_challenge_tx(
+    test: &mut SubmitBlockTest,
+    produced: &ProducedBlock,
+    target_type: ChallengeTargetType,
+) -> TransactionView {
+    let challenge_script_type_hash: [u8; 32] = build_type_id_script(CHALLENGE_LOCK_TYPE_ID)
+        .calc_script_hash()
+        .unpack();
+    let raw_block = produced.block.raw();
+    let challenge_cell = {
+        let lock_args = ChallengeLockArgs::new_builder()
+            .target(
+                ChallengeTarget::new_builder()
+                    .target_index(Pack::pack(&0u32))
+                    .target_type(target_type.into())
+                    .block_hash(Pack::pack(&raw_block.hash()))
+                    .build(),
+            )
+            .build();
+        build_rollup_locked_cell(
+            &test.rollup_type_hash(),
+            &challenge_script_type_hash,
+            10000_00000000u64,
+            lock_args.as_bytes(),
+        )
+    };
+    let witness = {
+        let challenge_witness = ChallengeWitness::new_builder()
+            .raw_l2block(raw_block.clone())
+            .block_proof(Pack::pack(&test.chain.block_proof(&raw_block)))
+            .build();
+        let rollup_action = RollupAction::new_builder()
+            .set(RollupActionUnion::RollupEnterChallenge(
+                RollupEnterChallenge::new_builder()
+                    .witness(challenge_witness)
+                    .build(),
+            ))
+            .build();
+        ckb_types::packed::WitnessArgs::new_builder()
+            .output_type(CKBPack::pack(&Some(rollup_action.as_bytes())))
+            .build()
+    };
+    let global_state = test.chain.global_state();
+    let post_global_state = global_state
+        .clone()
+        .as_builder()
+        .status(Status::Halting.into())
+        .build();
+    build_simple_tx(
+        &mut test.ctx.inner,
+        (test.rollup_cell.clone(), global_state.as_bytes()),
+        Default::default(),
+        (test.rollup_cell.clone(), post_global_state.as_bytes()),
+    )
+    .as_advanced_builder()
+    .output(challenge_cell)
+    .output_data(CKBPack::pack(&Bytes::new()))
+    .cell_dep(test.ctx.always_success_dep.clone())
+    .cell_dep(test.ctx.state_validator_dep.clone())
+    .cell_dep(test.ctx.rollup_config_dep.clone())
+    .witness(CKBPack::pack(&witness.as_bytes()))
+    .build()
+}
+
+#[test]
+fn test_enter_challenge_of_deposit_call() {
+    let challenge_script_type_hash: [u8; 32] = build_type_id_script(CHALLENGE_LOCK_TYPE_ID)
+        .calc_script_hash()
+        .unpack();
+    let mut test = SubmitBlockTest::new(|config| {
+        config
+            .as_builder()
+            .challenge_script_type_hash(Pack::pack(&challenge_script_type_hash))
+            .build()
+    });
+    let (deposit, call_tx) = build_deposit_call(&mut test, b"call");
+    let (produced, _cycles) =
+        submit_deposit_call(&mut test, deposit, vec![call_tx], Some(1)).expect("deposit call");
+
+    // the execution of the call can be challenged
+    let tx = build_enter_challenge_tx(&mut test, &produced, ChallengeTargetType::TxExecution);
+    test.ctx
+        .verify_tx(tx)
+        .expect("challenge the call execution");
+
+    // the call is authorized by the deposit cell, it has no signature to challenge
+    let tx = build_enter_challenge_tx(&mut test, &produced, ChallengeTargetType::TxSignature);
+    let err = test.ctx.verify_tx(tx).unwrap_err();
+    assert_state_validator_error(err, INVALID_CHALLENGE_TARGET_ERROR);
+}
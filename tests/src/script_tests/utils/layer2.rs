@@ -0,0 +1,743 @@
+//! Layer2 state and blocks built by hand
+//!
+//! The mem pool of the test chain limits the withdrawals and deposits of a block,
+//! and can't produce blocks with the extra fields of this repo,
+//! the tests build such blocks here instead.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use gw_common::{
+    blake2b::new_blake2b,
+    builtins::CKB_SUDT_ACCOUNT_ID,
+    error::Error,
+    merkle_utils::{calculate_ckb_merkle_root, calculate_state_checkpoint, ckb_merkle_leaf_hash},
+    smt::SMT,
+    sparse_merkle_tree::default_store::DefaultStore,
+    state::{to_short_address, State},
+    CKB_SUDT_SCRIPT_ARGS, H256,
+};
+use gw_types::{
+    bytes::Bytes,
+    core::{ScriptHashType, Status},
+    packed::{
+        AccountMerkleState, BlockMerkleState, Byte32Vec, DepositRequest, GlobalState, L2Block,
+        L2Transaction, L2TransactionVec, RawL2Block, RollupConfig, Script, SubmitTransactions,
+        SubmitWithdrawals, WithdrawalRequest, WithdrawalRequestVec,
+    },
+    prelude::*,
+};
+
+/// Extra field of the layer2 NFT validator type hash
+const L2_NFT_VALIDATOR_SCRIPT_TYPE_HASH: usize = 15;
+/// Number of the messages delivered to an account, see `gw_utils::cells::message`
+const MESSAGE_COUNT_KEY: [u8; 32] = [0xfe; 32];
+
+/// Layer2 state in memory, records the touched keys to build the kv state of a block
+#[derive(Default)]
+pub struct TestState {
+    tree: SMT<DefaultStore<H256>>,
+    account_count: u32,
+    // touched keys and the values before the first touch
+    tracker: RefCell<Option<HashMap<H256, H256>>>,
+}
+
+/// Kv state of a block, proved under the prev account root
+pub struct StateTransition {
+    pub kv_state: Vec<(H256, H256)>,
+    pub kv_state_proof: Bytes,
+    pub prev_account: AccountMerkleState,
+    pub post_account: AccountMerkleState,
+}
+
+impl TestState {
+    pub fn merkle_state(&self) -> AccountMerkleState {
+        AccountMerkleState::new_builder()
+            .merkle_root(self.tree.root().pack())
+            .count(self.account_count.pack())
+            .build()
+    }
+
+    /// Apply the changes of `f`, return the kv state of the touched keys and its proof
+    pub fn apply<F: FnOnce(&mut Self)>(&mut self, f: F) -> StateTransition {
+        let prev_account = self.merkle_state();
+        *self.tracker.borrow_mut() = Some(HashMap::new());
+        f(self);
+        let touched = self.tracker.borrow_mut().take().expect("tracker");
+        let post_account = self.merkle_state();
+
+        // roll back to prove the touched keys under the prev root
+        let post_kv: Vec<(H256, H256)> = touched
+            .keys()
+            .map(|k| (*k, self.tree.get(k).expect("get")))
+            .collect();
+        for (k, v) in touched.iter() {
+            self.tree.update(*k, *v).expect("update");
+        }
+        let kv_state: Vec<(H256, H256)> = touched.into_iter().collect();
+        let kv_state_proof: Bytes = if kv_state.is_empty() {
+            Bytes::new()
+        } else {
+            let keys = kv_state.iter().map(|(k, _v)| *k).collect();
+            self.tree
+                .merkle_proof(keys)
+                .expect("merkle proof")
+                .compile(kv_state.clone())
+                .expect("compile proof")
+                .0
+                .into()
+        };
+        for (k, v) in post_kv {
+            self.tree.update(k, v).expect("update");
+        }
+        StateTransition {
+            kv_state,
+            kv_state_proof,
+            prev_account,
+            post_account,
+        }
+    }
+
+    fn track(&self, key: &H256) {
+        if let Some(tracker) = self.tracker.borrow_mut().as_mut() {
+            if !tracker.contains_key(key) {
+                tracker.insert(*key, self.tree.get(key).expect("get"));
+            }
+        }
+    }
+}
+
+impl State for TestState {
+    fn get_raw(&self, key: &H256) -> Result<H256, Error> {
+        self.track(key);
+        Ok(self.tree.get(key)?)
+    }
+    fn update_raw(&mut self, key: H256, value: H256) -> Result<(), Error> {
+        self.track(&key);
+        self.tree.update(key, value)?;
+        Ok(())
+    }
+    fn get_account_count(&self) -> Result<u32, Error> {
+        Ok(self.account_count)
+    }
+    fn set_account_count(&mut self, count: u32) -> Result<(), Error> {
+        self.account_count = count;
+        Ok(())
+    }
+    fn calculate_root(&self) -> Result<H256, Error> {
+        Ok(*self.tree.root())
+    }
+}
+
+/// A deposit with the fee of its lock args, see `gw_utils::deposit`
+pub struct L2Deposit {
+    pub request: DepositRequest,
+    pub ckb_fee: u64,
+    pub sudt_fee: u128,
+    /// the deposit cell is a L1 NFT
+    pub nft: bool,
+}
+
+/// A L1 -> L2 message delivered by the block, see `gw_utils::cells::message`
+pub struct L2Message {
+    pub to_id: u32,
+    pub hash: H256,
+}
+
+#[derive(Default)]
+pub struct BlockParam {
+    pub timestamp: u64,
+    pub withdrawals: Vec<WithdrawalRequest>,
+    pub deposits: Vec<L2Deposit>,
+    /// the message root is set as the first extra field of the raw block
+    pub messages: Vec<L2Message>,
+    /// transactions of the block, they aren't executed since the state validator
+    /// trusts the post account state
+    pub transactions: Vec<L2Transaction>,
+    /// extra fields of the raw block, see `gw_utils::block`
+    pub raw_block_extra_fields: Vec<Bytes>,
+}
+
+pub struct ProducedBlock {
+    pub block: L2Block,
+    pub prev_global_state: GlobalState,
+    pub global_state: GlobalState,
+}
+
+/// A layer2 chain producing blocks by hand,
+/// the state can be changed out of blocks to set up the genesis
+pub struct TestChain {
+    pub rollup_type_hash: H256,
+    pub rollup_config: RollupConfig,
+    pub state: TestState,
+    pub block_producer_id: u32,
+    block_tree: SMT<DefaultStore<H256>>,
+    reverted_block_tree: SMT<DefaultStore<H256>>,
+    block_count: u64,
+    tip_block_hash: H256,
+    tip_block_timestamp: u64,
+}
+
+impl TestChain {
+    pub fn new(rollup_type_hash: H256, rollup_config: RollupConfig) -> Self {
+        let mut state = TestState::default();
+        // reserved meta contract account
+        let meta_script_hash: H256 = Script::new_builder()
+            .args(rollup_type_hash.as_slice().to_vec().pack())
+            .build()
+            .hash()
+            .into();
+        state.create_account(meta_script_hash).unwrap();
+        // CKB simple UDT account
+        let ckb_sudt_id = state
+            .create_account(build_l2_sudt_script_hash(
+                &rollup_type_hash,
+                &rollup_config,
+                &CKB_SUDT_SCRIPT_ARGS.into(),
+            ))
+            .unwrap();
+        assert_eq!(ckb_sudt_id, CKB_SUDT_ACCOUNT_ID);
+        let block_producer_script_hash: H256 = Script::new_builder()
+            .args(b"block_producer".to_vec().pack())
+            .build()
+            .hash()
+            .into();
+        let block_producer_id = state.create_account(block_producer_script_hash).unwrap();
+        // genesis block
+        let genesis_hash = H256::from([42u8; 32]);
+        let mut block_tree: SMT<DefaultStore<H256>> = Default::default();
+        block_tree
+            .update(RawL2Block::compute_smt_key(0).into(), genesis_hash)
+            .unwrap();
+        TestChain {
+            rollup_type_hash,
+            rollup_config,
+            state,
+            block_producer_id,
+            block_tree,
+            reverted_block_tree: Default::default(),
+            block_count: 1,
+            tip_block_hash: genesis_hash,
+            tip_block_timestamp: 0,
+        }
+    }
+
+    pub fn block_producer_script_hash(&self) -> H256 {
+        self.state.get_script_hash(self.block_producer_id).unwrap()
+    }
+
+    /// Global state of the tip block, paired with the current layer2 state
+    pub fn global_state(&self) -> GlobalState {
+        let rollup_config_hash = {
+            let mut hash = [0u8; 32];
+            let mut hasher = new_blake2b();
+            hasher.update(self.rollup_config.as_slice());
+            hasher.finalize(&mut hash);
+            hash
+        };
+        let last_finalized_block_number =
+            (self.block_count - 1).saturating_sub(self.rollup_config.finality_blocks().unpack());
+        GlobalState::new_builder()
+            .rollup_config_hash(rollup_config_hash.pack())
+            .account(self.state.merkle_state())
+            .block(
+                BlockMerkleState::new_builder()
+                    .merkle_root(self.block_tree.root().pack())
+                    .count(self.block_count.pack())
+                    .build(),
+            )
+            .tip_block_hash(self.tip_block_hash.pack())
+            .tip_block_timestamp(self.tip_block_timestamp.pack())
+            .reverted_block_root(self.reverted_block_tree.root().pack())
+            .last_finalized_block_number(last_finalized_block_number.pack())
+            .status(Status::Running.into())
+            .version(1u8.into())
+            .build()
+    }
+
+    /// Proof of the block under the block merkle root, to challenge the block
+    pub fn block_proof(&self, raw_block: &RawL2Block) -> Bytes {
+        let block_smt_key: H256 = RawL2Block::compute_smt_key(raw_block.number().unpack()).into();
+        self.block_tree
+            .merkle_proof(vec![block_smt_key])
+            .unwrap()
+            .compile(vec![(block_smt_key, raw_block.hash().into())])
+            .unwrap()
+            .0
+            .into()
+    }
+
+    /// Add the block hashes to the reverted block root, as a revert before the next block
+    pub fn revert_block_hashes(&mut self, block_hashes: &[H256]) {
+        for block_hash in block_hashes {
+            self.reverted_block_tree
+                .update(*block_hash, H256::one())
+                .unwrap();
+        }
+    }
+
+    /// Proof of the reverted block hashes under the reverted block root
+    pub fn reverted_block_proof(&self, block_hashes: &[H256]) -> Bytes {
+        if block_hashes.is_empty() {
+            return Bytes::new();
+        }
+        self.reverted_block_tree
+            .merkle_proof(block_hashes.to_vec())
+            .unwrap()
+            .compile(
+                block_hashes
+                    .iter()
+                    .map(|block_hash| (*block_hash, H256::one()))
+                    .collect(),
+            )
+            .unwrap()
+            .0
+            .into()
+    }
+
+    /// Produce a block of the withdrawals, deposits, messages and transactions
+    pub fn produce_block(&mut self, param: BlockParam) -> ProducedBlock {
+        let BlockParam {
+            timestamp,
+            withdrawals,
+            deposits,
+            messages,
+            transactions,
+            raw_block_extra_fields,
+        } = param;
+        let prev_global_state = self.global_state();
+        let rollup_type_hash = self.rollup_type_hash;
+        let rollup_config = self.rollup_config.clone();
+        let block_producer_id = self.block_producer_id;
+        let transition = self.state.apply(|state| {
+            if !withdrawals.is_empty() {
+                let block_producer = state.get_script_hash(block_producer_id).unwrap();
+                for request in withdrawals.iter() {
+                    withdraw(
+                        state,
+                        &rollup_type_hash,
+                        &rollup_config,
+                        &block_producer,
+                        request,
+                    );
+                }
+            }
+            if !deposits.is_empty() {
+                let block_producer = state.get_script_hash(block_producer_id).unwrap();
+                for deposit in deposits.iter() {
+                    deposit_to(
+                        state,
+                        &rollup_type_hash,
+                        &rollup_config,
+                        &block_producer,
+                        deposit,
+                    );
+                }
+            }
+            for message in messages.iter() {
+                deliver_message(state, message);
+            }
+        });
+
+        let number = self.block_count;
+        let post_checkpoint = calculate_state_checkpoint(
+            &transition.post_account.merkle_root().unpack(),
+            transition.post_account.count().unpack(),
+        );
+        let withdrawal_witness_root = calculate_ckb_merkle_root(
+            withdrawals
+                .iter()
+                .enumerate()
+                .map(|(idx, w)| ckb_merkle_leaf_hash(idx as u32, &w.witness_hash().into()))
+                .collect(),
+        )
+        .unwrap();
+        let tx_witness_root = calculate_ckb_merkle_root(
+            transactions
+                .iter()
+                .enumerate()
+                .map(|(idx, tx)| ckb_merkle_leaf_hash(idx as u32, &tx.witness_hash().into()))
+                .collect(),
+        )
+        .unwrap();
+        let raw_block = RawL2Block::new_builder()
+            .number(number.pack())
+            .block_producer_id(block_producer_id.pack())
+            .parent_block_hash(self.tip_block_hash.pack())
+            .timestamp(timestamp.pack())
+            .prev_account(transition.prev_account)
+            .post_account(transition.post_account)
+            .state_checkpoint_list(
+                Byte32Vec::new_builder()
+                    .set(vec![
+                        post_checkpoint.pack();
+                        withdrawals.len() + transactions.len()
+                    ])
+                    .build(),
+            )
+            .submit_withdrawals(
+                SubmitWithdrawals::new_builder()
+                    .withdrawal_witness_root(withdrawal_witness_root.pack())
+                    .withdrawal_count((withdrawals.len() as u32).pack())
+                    .build(),
+            )
+            .submit_transactions(
+                SubmitTransactions::new_builder()
+                    .tx_witness_root(tx_witness_root.pack())
+                    .tx_count((transactions.len() as u32).pack())
+                    .prev_state_checkpoint(post_checkpoint.pack())
+                    .build(),
+            )
+            .build();
+        let raw_block_extra_fields = if messages.is_empty() {
+            raw_block_extra_fields
+        } else {
+            assert!(raw_block_extra_fields.is_empty(), "set by the messages");
+            let message_root = calculate_ckb_merkle_root(
+                messages
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, message)| ckb_merkle_leaf_hash(idx as u32, &message.hash))
+                    .collect(),
+            )
+            .unwrap();
+            vec![Bytes::from(message_root.as_slice().to_vec())]
+        };
+        let raw_block = append_extra_fields(raw_block, &raw_block_extra_fields);
+        let block_hash: H256 = raw_block.hash().into();
+
+        // append the block to the block merkle tree
+        let block_smt_key: H256 = RawL2Block::compute_smt_key(number).into();
+        let block_proof: Bytes = self
+            .block_tree
+            .merkle_proof(vec![block_smt_key])
+            .unwrap()
+            .compile(vec![(block_smt_key, H256::zero())])
+            .unwrap()
+            .0
+            .into();
+        self.block_tree.update(block_smt_key, block_hash).unwrap();
+        self.block_count += 1;
+        self.tip_block_hash = block_hash;
+        self.tip_block_timestamp = timestamp;
+
+        let block = L2Block::new_builder()
+            .raw(raw_block)
+            .kv_state(transition.kv_state.pack())
+            .kv_state_proof(transition.kv_state_proof.pack())
+            .block_proof(block_proof.pack())
+            .withdrawals(WithdrawalRequestVec::new_builder().set(withdrawals).build())
+            .transactions(L2TransactionVec::new_builder().set(transactions).build())
+            .build();
+        ProducedBlock {
+            block,
+            prev_global_state,
+            global_state: self.global_state(),
+        }
+    }
+}
+
+pub fn build_l2_sudt_script_hash(
+    rollup_type_hash: &H256,
+    rollup_config: &RollupConfig,
+    l1_sudt_script_hash: &H256,
+) -> H256 {
+    let mut args = rollup_type_hash.as_slice().to_vec();
+    args.extend_from_slice(l1_sudt_script_hash.as_slice());
+    Script::new_builder()
+        .code_hash(rollup_config.l2_sudt_validator_script_type_hash())
+        .hash_type(ScriptHashType::Type.into())
+        .args(args.pack())
+        .build()
+        .hash()
+        .into()
+}
+
+/// Script hash of the layer2 NFT account of a L1 NFT,
+/// None if the rollup config has no layer2 NFT validator
+pub fn build_l2_nft_script_hash(
+    rollup_type_hash: &H256,
+    rollup_config: &RollupConfig,
+    l1_nft_type_hash: &H256,
+) -> Option<H256> {
+    let mut code_hash = [0u8; 32];
+    code_hash.copy_from_slice(rollup_config_extra_field(
+        rollup_config,
+        L2_NFT_VALIDATOR_SCRIPT_TYPE_HASH,
+    )?);
+    let mut args = rollup_type_hash.as_slice().to_vec();
+    args.extend_from_slice(l1_nft_type_hash.as_slice());
+    let script_hash = Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(args.pack())
+        .build()
+        .hash();
+    Some(script_hash.into())
+}
+
+/// Find or create the layer2 sUDT account of a L1 sUDT
+pub fn get_or_create_sudt_id(
+    state: &mut TestState,
+    rollup_type_hash: &H256,
+    rollup_config: &RollupConfig,
+    l1_sudt_script_hash: &H256,
+) -> u32 {
+    let script_hash =
+        build_l2_sudt_script_hash(rollup_type_hash, rollup_config, l1_sudt_script_hash);
+    match state.get_account_id_by_script_hash(&script_hash).unwrap() {
+        Some(id) => id,
+        None => state.create_account(script_hash).unwrap(),
+    }
+}
+
+/// Changes of a withdrawal, the same as the state validator
+fn withdraw(
+    state: &mut TestState,
+    rollup_type_hash: &H256,
+    rollup_config: &RollupConfig,
+    block_producer: &H256,
+    request: &WithdrawalRequest,
+) {
+    let raw = request.raw();
+    let account_script_hash: H256 = raw.account_script_hash().unpack();
+    let id = state
+        .get_account_id_by_script_hash(&account_script_hash)
+        .unwrap()
+        .expect("withdrawal account");
+    let short_address = to_short_address(&account_script_hash);
+    let fee_sudt_id = raw.fee().sudt_id().unpack();
+    let fee_amount = raw.fee().amount().unpack();
+    pay_fee(
+        state,
+        short_address,
+        block_producer,
+        fee_sudt_id,
+        fee_amount,
+    );
+    let capacity: u64 = raw.capacity().unpack();
+    state
+        .burn_sudt(CKB_SUDT_ACCOUNT_ID, short_address, capacity.into())
+        .unwrap();
+    let sudt_script_hash: H256 = raw.sudt_script_hash().unpack();
+    let amount: u128 = raw.amount().unpack();
+    let nft_id = if sudt_script_hash.as_slice() == CKB_SUDT_SCRIPT_ARGS {
+        None
+    } else {
+        build_l2_nft_script_hash(rollup_type_hash, rollup_config, &sudt_script_hash)
+            .and_then(|script_hash| state.get_account_id_by_script_hash(&script_hash).unwrap())
+    };
+    if let Some(nft_id) = nft_id {
+        assert_eq!(amount, 1, "NFT withdrawal amount");
+        state
+            .update_value(nft_id, &build_nft_owner_key(), H256::zero())
+            .unwrap();
+    } else {
+        let l2_sudt_script_hash =
+            build_l2_sudt_script_hash(rollup_type_hash, rollup_config, &sudt_script_hash);
+        let sudt_id = state
+            .get_account_id_by_script_hash(&l2_sudt_script_hash)
+            .unwrap()
+            .expect("sudt account");
+        state.burn_sudt(sudt_id, short_address, amount).unwrap();
+    }
+    let nonce = state.get_nonce(id).unwrap();
+    state.set_nonce(id, nonce + 1).unwrap();
+}
+
+/// Changes of a deposit, the same as the state validator
+fn deposit_to(
+    state: &mut TestState,
+    rollup_type_hash: &H256,
+    rollup_config: &RollupConfig,
+    block_producer: &H256,
+    deposit: &L2Deposit,
+) {
+    let request = &deposit.request;
+    let account_script_hash: H256 = request.script().hash().into();
+    if state
+        .get_account_id_by_script_hash(&account_script_hash)
+        .unwrap()
+        .is_none()
+    {
+        state.create_account(account_script_hash).unwrap();
+    }
+    let short_address = to_short_address(&account_script_hash);
+    let capacity: u64 = request.capacity().unpack();
+    state
+        .mint_sudt(CKB_SUDT_ACCOUNT_ID, short_address, capacity.into())
+        .unwrap();
+    pay_fee(
+        state,
+        short_address,
+        block_producer,
+        CKB_SUDT_ACCOUNT_ID,
+        deposit.ckb_fee.into(),
+    );
+    let sudt_script_hash: H256 = request.sudt_script_hash().unpack();
+    if sudt_script_hash.as_slice() == CKB_SUDT_SCRIPT_ARGS {
+        return;
+    }
+    if deposit.nft {
+        let script_hash =
+            build_l2_nft_script_hash(rollup_type_hash, rollup_config, &sudt_script_hash)
+                .expect("layer2 NFT validator");
+        let nft_id = match state.get_account_id_by_script_hash(&script_hash).unwrap() {
+            Some(id) => id,
+            None => state.create_account(script_hash).unwrap(),
+        };
+        state
+            .update_value(nft_id, &build_nft_owner_key(), account_script_hash)
+            .unwrap();
+        return;
+    }
+    let sudt_id = get_or_create_sudt_id(state, rollup_type_hash, rollup_config, &sudt_script_hash);
+    let amount: u128 = request.amount().unpack();
+    state.mint_sudt(sudt_id, short_address, amount).unwrap();
+    pay_fee(
+        state,
+        short_address,
+        block_producer,
+        sudt_id,
+        deposit.sudt_fee,
+    );
+}
+
+/// Changes of a message delivery, the same as the state validator
+fn deliver_message(state: &mut TestState, message: &L2Message) {
+    let value = state
+        .get_value(message.to_id, &MESSAGE_COUNT_KEY.into())
+        .unwrap();
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&value.as_slice()[..8]);
+    let count = u64::from_le_bytes(buf);
+    state
+        .update_value(message.to_id, &build_message_key(count), message.hash)
+        .unwrap();
+    let mut value = [0u8; 32];
+    value[..8].copy_from_slice(&(count + 1).to_le_bytes());
+    state
+        .update_value(message.to_id, &MESSAGE_COUNT_KEY.into(), value.into())
+        .unwrap();
+}
+
+/// Key of the n-th message delivered to an account, see `gw_utils::cells::message`
+pub fn build_message_key(n: u64) -> H256 {
+    let mut key = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(b"message");
+    hasher.update(&n.to_le_bytes());
+    hasher.finalize(&mut key);
+    key.into()
+}
+
+fn pay_fee(
+    state: &mut TestState,
+    payer_short_address: &[u8],
+    block_producer: &H256,
+    sudt_id: u32,
+    amount: u128,
+) {
+    if amount == 0 {
+        return;
+    }
+    state
+        .burn_sudt(sudt_id, payer_short_address, amount)
+        .unwrap();
+    state
+        .mint_sudt(sudt_id, to_short_address(block_producer), amount)
+        .unwrap();
+}
+
+/// Append extra fields to the raw block table, see `gw_utils::block`
+fn append_extra_fields(raw_block: RawL2Block, extra_fields: &[Bytes]) -> RawL2Block {
+    if extra_fields.is_empty() {
+        return raw_block;
+    }
+    RawL2Block::new_unchecked(extend_table(raw_block.as_slice(), extra_fields))
+}
+
+/// Fields of a molecule table
+fn table_fields(slice: &[u8]) -> Vec<&[u8]> {
+    let read_u32 = |offset: usize| {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(&slice[offset..offset + 4]);
+        u32::from_le_bytes(buf) as usize
+    };
+    let field_count = if slice.len() == 4 {
+        0
+    } else {
+        read_u32(4) / 4 - 1
+    };
+    (0..field_count)
+        .map(|i| {
+            let start = read_u32(4 * (i + 1));
+            let end = if i + 1 < field_count {
+                read_u32(4 * (i + 2))
+            } else {
+                slice.len()
+            };
+            &slice[start..end]
+        })
+        .collect()
+}
+
+/// Extra field of the rollup config, see `gw_utils::rollup_config`
+fn rollup_config_extra_field(rollup_config: &RollupConfig, index: usize) -> Option<&[u8]> {
+    table_fields(rollup_config.as_slice())
+        .get(RollupConfig::FIELD_COUNT + index)
+        .copied()
+        .filter(|field| !field.is_empty())
+}
+
+/// Key of the owner of a layer2 NFT account, see `gw_utils::cells::utils::build_nft_owner_key`
+fn build_nft_owner_key() -> H256 {
+    let mut key = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(b"nft_owner");
+    hasher.finalize(&mut key);
+    key.into()
+}
+
+/// Append fields to a molecule table
+pub fn extend_table(slice: &[u8], extra_fields: &[Bytes]) -> Bytes {
+    let mut fields = table_fields(slice);
+    fields.extend(extra_fields.iter().map(|field| field.as_ref()));
+
+    let header_size = 4 * (fields.len() + 1);
+    let total_size = header_size + fields.iter().map(|field| field.len()).sum::<usize>();
+    let mut data = Vec::with_capacity(total_size);
+    data.extend_from_slice(&(total_size as u32).to_le_bytes());
+    let mut offset = header_size;
+    for field in &fields {
+        data.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    for field in &fields {
+        data.extend_from_slice(field);
+    }
+    data.into()
+}
+
+/// Set the expiry of the withdrawal, extra field 0 of the request,
+/// see `gw_utils::withdrawal`
+pub fn with_valid_until(request: WithdrawalRequest, valid_until: u64) -> WithdrawalRequest {
+    let field = Bytes::from(valid_until.to_le_bytes().to_vec());
+    WithdrawalRequest::new_unchecked(extend_table(request.as_slice(), &[field]))
+}
+
+/// Signing message of a withdrawal with an expiry,
+/// hash(rollup_type_hash | raw | valid_until)
+pub fn calculate_withdrawal_message(
+    rollup_type_hash: &H256,
+    request: &WithdrawalRequest,
+    valid_until: u64,
+) -> H256 {
+    let mut message = [0u8; 32];
+    let mut hasher = new_blake2b();
+    hasher.update(rollup_type_hash.as_slice());
+    hasher.update(request.raw().as_slice());
+    hasher.update(&valid_until.to_le_bytes());
+    hasher.finalize(&mut message);
+    message.into()
+}
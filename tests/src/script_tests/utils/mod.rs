@@ -1,4 +1,5 @@
 pub mod layer1;
+pub mod layer2;
 pub mod rollup;
 
 pub fn init_env_log() {
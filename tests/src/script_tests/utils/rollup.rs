@@ -1,6 +1,7 @@
 use crate::script_tests::utils::layer1::{
     always_success_script, build_resolved_tx, random_out_point, DummyDataLoader, MAX_CYCLES,
 };
+use crate::script_tests::utils::layer2::extend_table;
 use crate::testing_tool::programs::{
     ALWAYS_SUCCESS_CODE_HASH, ALWAYS_SUCCESS_PROGRAM, CHALLENGE_LOCK_PROGRAM,
     ETH_ACCOUNT_LOCK_PROGRAM, SECP256K1_DATA, STATE_VALIDATOR_PROGRAM,
@@ -359,3 +360,12 @@ pub fn calculate_state_validator_type_id(input_out_point: ckb_types::packed::Out
     hasher.finalize(&mut expected_type_id);
     expected_type_id
 }
+
+/// Append extra fields to the rollup config table,
+/// see `gw_utils::rollup_config` for the fields
+pub fn build_rollup_config_with_extra_fields(
+    rollup_config: &RollupConfig,
+    extra_fields: &[Bytes],
+) -> RollupConfig {
+    RollupConfig::new_unchecked(extend_table(rollup_config.as_slice(), extra_fields))
+}
@@ -8,12 +8,18 @@ const SCRIPT_DIR: &'static str = "../build/debug";
 const CHALLENGE_LOCK_PATH: &'static str = "challenge-lock";
 const STATE_VALIDATOR: &'static str = "state-validator";
 const ALWAYS_SUCCESS_PATH: &'static str = "always-success";
+const DEPOSIT_LOCK_PATH: &'static str = "deposit-lock";
+const CUSTODIAN_LOCK_PATH: &'static str = "custodian-lock";
+const STAKE_LOCK_PATH: &'static str = "stake-lock";
+const MESSAGE_LOCK_PATH: &'static str = "message-lock";
+const WITHDRAWAL_LOCK_PATH: &'static str = "withdrawal-lock";
 const SECP256K1_DATA_PATH: &'static str = "../c/deps/ckb-production-scripts/build/secp256k1_data";
 const C_SCRIPTS_DIR: &'static str = "../../godwoken-scripts/c/build";
 const META_CONTRACT_BIN_NAME: &'static str = "meta-contract-validator";
 // account locks
 const ETH_LOCK_PATH: &'static str = "eth-account-lock";
 const TRON_LOCK_PATH: &'static str = "tron-account-lock";
+const RECOVERY_LOCK_PATH: &'static str = "recovery-account-lock";
 
 lazy_static! {
     pub static ref ALWAYS_SUCCESS_PROGRAM: Bytes = {
@@ -64,6 +70,86 @@ lazy_static! {
         hasher.finalize(&mut buf);
         buf
     };
+    pub static ref DEPOSIT_LOCK_PROGRAM: Bytes = {
+        let mut buf = Vec::new();
+        let mut path = PathBuf::new();
+        path.push(&SCRIPT_DIR);
+        path.push(&DEPOSIT_LOCK_PATH);
+        let mut f = fs::File::open(&path).expect("load program");
+        f.read_to_end(&mut buf).expect("read program");
+        Bytes::from(buf.to_vec())
+    };
+    pub static ref DEPOSIT_LOCK_CODE_HASH: [u8; 32] = {
+        let mut buf = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&DEPOSIT_LOCK_PROGRAM);
+        hasher.finalize(&mut buf);
+        buf
+    };
+    pub static ref CUSTODIAN_LOCK_PROGRAM: Bytes = {
+        let mut buf = Vec::new();
+        let mut path = PathBuf::new();
+        path.push(&SCRIPT_DIR);
+        path.push(&CUSTODIAN_LOCK_PATH);
+        let mut f = fs::File::open(&path).expect("load program");
+        f.read_to_end(&mut buf).expect("read program");
+        Bytes::from(buf.to_vec())
+    };
+    pub static ref CUSTODIAN_LOCK_CODE_HASH: [u8; 32] = {
+        let mut buf = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&CUSTODIAN_LOCK_PROGRAM);
+        hasher.finalize(&mut buf);
+        buf
+    };
+    pub static ref STAKE_LOCK_PROGRAM: Bytes = {
+        let mut buf = Vec::new();
+        let mut path = PathBuf::new();
+        path.push(&SCRIPT_DIR);
+        path.push(&STAKE_LOCK_PATH);
+        let mut f = fs::File::open(&path).expect("load program");
+        f.read_to_end(&mut buf).expect("read program");
+        Bytes::from(buf.to_vec())
+    };
+    pub static ref STAKE_LOCK_CODE_HASH: [u8; 32] = {
+        let mut buf = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&STAKE_LOCK_PROGRAM);
+        hasher.finalize(&mut buf);
+        buf
+    };
+    pub static ref MESSAGE_LOCK_PROGRAM: Bytes = {
+        let mut buf = Vec::new();
+        let mut path = PathBuf::new();
+        path.push(&SCRIPT_DIR);
+        path.push(&MESSAGE_LOCK_PATH);
+        let mut f = fs::File::open(&path).expect("load program");
+        f.read_to_end(&mut buf).expect("read program");
+        Bytes::from(buf.to_vec())
+    };
+    pub static ref MESSAGE_LOCK_CODE_HASH: [u8; 32] = {
+        let mut buf = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&MESSAGE_LOCK_PROGRAM);
+        hasher.finalize(&mut buf);
+        buf
+    };
+    pub static ref WITHDRAWAL_LOCK_PROGRAM: Bytes = {
+        let mut buf = Vec::new();
+        let mut path = PathBuf::new();
+        path.push(&SCRIPT_DIR);
+        path.push(&WITHDRAWAL_LOCK_PATH);
+        let mut f = fs::File::open(&path).expect("load program");
+        f.read_to_end(&mut buf).expect("read program");
+        Bytes::from(buf.to_vec())
+    };
+    pub static ref WITHDRAWAL_LOCK_CODE_HASH: [u8; 32] = {
+        let mut buf = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&WITHDRAWAL_LOCK_PROGRAM);
+        hasher.finalize(&mut buf);
+        buf
+    };
     pub static ref ETH_ACCOUNT_LOCK_PROGRAM: Bytes = {
         let mut buf = Vec::new();
         let mut path = PathBuf::new();
@@ -96,6 +182,22 @@ lazy_static! {
         hasher.finalize(&mut buf);
         buf
     };
+    pub static ref RECOVERY_ACCOUNT_LOCK_PROGRAM: Bytes = {
+        let mut buf = Vec::new();
+        let mut path = PathBuf::new();
+        path.push(&SCRIPT_DIR);
+        path.push(&RECOVERY_LOCK_PATH);
+        let mut f = fs::File::open(&path).expect("load program");
+        f.read_to_end(&mut buf).expect("read program");
+        Bytes::from(buf.to_vec())
+    };
+    pub static ref RECOVERY_ACCOUNT_LOCK_CODE_HASH: [u8; 32] = {
+        let mut buf = [0u8; 32];
+        let mut hasher = new_blake2b();
+        hasher.update(&RECOVERY_ACCOUNT_LOCK_PROGRAM);
+        hasher.finalize(&mut buf);
+        buf
+    };
     pub static ref SECP256K1_DATA: Bytes = {
         let mut buf = Vec::new();
         let mut f = fs::File::open(&SECP256K1_DATA_PATH).expect("load secp256k1 data");